@@ -0,0 +1,93 @@
+//! A minimal stand-in for a signing host, which can be used together with the
+//! `--signer-cmd` and `--signer-socket` options.
+//!
+//! Command mode (data on stdin, hex signature on stdout):
+//!
+//!     amebazii ota resign fw1.bin --signer-cmd "signing_host keys.json"
+//!
+//! Socket mode (JSON lines, one request per connection):
+//!
+//!     signing_host keys.json --listen 127.0.0.1:7788
+//!     amebazii ota resign fw1.bin --signer-socket 127.0.0.1:7788
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+};
+
+use amebazii::{
+    keystore::{KeyStore, HASH_KEY_NAME},
+    signer::{ENV_HASH_ALGO, ENV_KEY_ID},
+    types::HashAlgo,
+};
+
+fn sign(keys: &KeyStore, algo: &str, key_id: &str, data: &[u8]) -> Result<Vec<u8>, String> {
+    let algo = match algo {
+        "sha256" => HashAlgo::Sha256,
+        "md5" => HashAlgo::Md5,
+        _ => return Err(format!("unsupported hash algorithm: {}", algo)),
+    };
+
+    // firmware keys (fw1_hash_key, fw2_hash_key) fall back to fw_hash_key
+    let key = match keys.get_key(key_id) {
+        Some(key) => key,
+        None if key_id == HASH_KEY_NAME => keys.get_hash_key(),
+        None if key_id.starts_with("fw") => keys.get_fw_hash_key(),
+        None => return Err(format!("unknown key: {}", key_id)),
+    };
+    algo.compute_hash(data, Some(key))
+        .map_err(|e| e.to_string())
+}
+
+fn serve(keys: &KeyStore, address: &str) {
+    let listener = TcpListener::bind(address).unwrap();
+    for stream in listener.incoming() {
+        let mut stream = stream.unwrap();
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line).unwrap();
+
+        // request: {"algo": "sha256", "key": "fw_hash_key", "data": "<hex>"}
+        let request: serde_json::Value = serde_json::from_str(&line).unwrap();
+        let result = sign(
+            keys,
+            request["algo"].as_str().unwrap_or_default(),
+            request["key"].as_str().unwrap_or_default(),
+            &hex::decode(request["data"].as_str().unwrap_or_default()).unwrap(),
+        );
+
+        let response = match result {
+            Ok(signature) => serde_json::json!({ "signature": hex::encode(signature) }),
+            Err(error) => serde_json::json!({ "error": error }),
+        };
+        writeln!(stream, "{}", response).unwrap();
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        eprintln!("usage: {} KEYFILE [--listen ADDR]", args[0]);
+        std::process::exit(2);
+    }
+
+    // the private keys only ever live within this process
+    let mut keys = KeyStore::new();
+    keys.load_file(&args[1]).unwrap();
+
+    if args.len() == 4 && args[2] == "--listen" {
+        serve(&keys, &args[3]);
+        return;
+    }
+
+    let mut data = Vec::new();
+    std::io::stdin().read_to_end(&mut data).unwrap();
+
+    let algo = std::env::var(ENV_HASH_ALGO).unwrap_or_default();
+    let key_id = std::env::var(ENV_KEY_ID).unwrap_or_default();
+    match sign(&keys, &algo, &key_id, &data) {
+        Ok(signature) => println!("{}", hex::encode(signature)),
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,454 @@
+//! Security audit of flash and OTA images.
+//!
+//! An audit inspects the configuration of an image and reports weaknesses, such as images
+//! signed using one of the public default keys, disabled encryption or active debug and
+//! recovery options. Each [`Finding`] has a [`Severity`] and the [`AuditReport`] keeps all
+//! findings ordered by severity (most severe first).
+//!
+//! All hashes are identified using the built-in keys only (see [`KnownKey::defaults`]), so
+//! that keys from a key store never hide the use of a default key.
+
+use serde::Serialize;
+use std::{fmt, io};
+
+use crate::{
+    error::Error,
+    keys::{KnownKey, XIP_IV, XIP_KEY},
+    keystore::KeyStore,
+    types::{
+        enums::{KeyExportOp, PartitionType},
+        image::pt::PartitionTableImage,
+        EncryptedOr, Flash, ForceOldImage, KeyBlock, KeyIdentification, OTAImage, PartTab,
+        Partition,
+    },
+};
+
+/// The severity of a finding.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum Severity {
+    /// Informational, no action required.
+    Info,
+    /// Minor weakness.
+    Low,
+    /// Weakness that should be fixed for production images.
+    Medium,
+    /// Weakness that allows to bypass a security feature.
+    High,
+    /// The image can be modified or re-signed by anyone.
+    Critical,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "INFO"),
+            Severity::Low => write!(f, "LOW"),
+            Severity::Medium => write!(f, "MEDIUM"),
+            Severity::High => write!(f, "HIGH"),
+            Severity::Critical => write!(f, "CRITICAL"),
+        }
+    }
+}
+
+/// A single finding of an audit.
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    /// The severity of this finding.
+    pub severity: Severity,
+
+    /// A short identifier of the check (e.g. `default-hash-key`).
+    pub id: String,
+
+    /// The affected part of the image (e.g. `Fw1/subimage 0`).
+    pub location: String,
+
+    /// A human-readable description of the finding.
+    pub message: String,
+}
+
+/// All findings of an audit, ordered by severity (most severe first).
+///
+/// # Example
+/// ```
+/// use amebazii::audit::{AuditReport, Severity};
+///
+/// let mut report = AuditReport::default();
+/// report.add_finding(Severity::Low, "no-rdp", "PartTab", "No RDP partition");
+/// report.add_finding(Severity::High, "dbg-skip", "Fw1", "Debug skip enabled");
+/// assert_eq!(report.get_findings()[0].id, "dbg-skip");
+/// assert_eq!(report.max_severity(), Some(Severity::High));
+/// ```
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AuditReport {
+    findings: Vec<Finding>,
+}
+
+impl AuditReport {
+    /// Adds a new finding to this report.
+    ///
+    /// Findings with the same severity keep the order in which they were added.
+    ///
+    /// # Arguments:
+    /// - `severity`: The severity of the finding.
+    /// - `id`: A short identifier of the check.
+    /// - `location`: The affected part of the image.
+    /// - `message`: A human-readable description.
+    pub fn add_finding(&mut self, severity: Severity, id: &str, location: &str, message: &str) {
+        let idx = self
+            .findings
+            .iter()
+            .position(|finding| finding.severity < severity)
+            .unwrap_or(self.findings.len());
+        self.findings.insert(
+            idx,
+            Finding {
+                severity,
+                id: id.to_string(),
+                location: location.to_string(),
+                message: message.to_string(),
+            },
+        );
+    }
+
+    /// Returns all findings, ordered by severity (most severe first).
+    pub fn get_findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    /// Returns the number of findings with the given severity.
+    pub fn count(&self, severity: Severity) -> usize {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity == severity)
+            .count()
+    }
+
+    /// Returns the highest severity of all findings, or `None` if there are no findings.
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.findings.first().map(|finding| finding.severity)
+    }
+}
+
+/// Audits a flash image.
+///
+/// The partition table, the boot image, both firmware images and the system data (if the
+/// partition table has a record for it) are inspected.
+///
+/// # Arguments:
+/// - `flash`: The parsed flash image.
+/// - `reader`: The stream the flash image was read from.
+///
+/// # Returns:
+/// - `Ok(AuditReport)` containing all findings.
+/// - `Err(Error)` if there is no partition table or the image data could not be read.
+pub fn audit_flash<R>(flash: &Flash, reader: &mut R) -> Result<AuditReport, Error>
+where
+    R: io::Read + io::Seek,
+{
+    let mut report = AuditReport::default();
+    let pt_image = match flash.get_partition(PartitionType::PartTab) {
+        Some(Partition::PartitionTable(pt_image)) => pt_image,
+        _ => {
+            return Err(Error::InvalidState(
+                "Flash has no partition table".to_string(),
+            ))
+        }
+    };
+
+    audit_partition_table(&mut report, pt_image);
+    let results = flash.identify_keys(reader, &KeyStore::new())?;
+    for (part_type, part_results) in &results {
+        audit_key_identification(&mut report, &format!("{:?}", part_type), part_results);
+    }
+
+    for part_type in [PartitionType::Fw1, PartitionType::Fw2] {
+        match flash.get_partition(part_type) {
+            Some(Partition::Fw1(image)) | Some(Partition::Fw2(image)) => {
+                audit_ota_image(&mut report, &format!("{:?}", part_type), image);
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(Partition::Bootloader(boot_image)) = flash.get_partition(PartitionType::Boot) {
+        audit_keyblock(&mut report, "Boot", &boot_image.keyblock, false);
+        if !boot_image.header.is_encrypt {
+            report.add_finding(
+                Severity::Medium,
+                "no-encryption",
+                "Boot",
+                "Boot image is not encrypted",
+            );
+        }
+    }
+
+    if let Some(Partition::System(sys)) = flash.get_partition(PartitionType::Sys) {
+        // erased flash (0xFFFFFFFF) does not configure a trap
+        let trap = sys.old_img_trap;
+        let raw_trap: u32 = trap.into();
+        let erased: u32 = ForceOldImage::from(0xFFFF_FFFF).into();
+        if trap.is_active() && raw_trap != erased {
+            report.add_finding(
+                Severity::Medium,
+                "force-old-image",
+                "Sys",
+                &format!(
+                    "ForceOldImage trap is active (port {}, pin {}), the previous firmware image can be booted using a GPIO",
+                    trap.port(),
+                    trap.pin()
+                ),
+            );
+        }
+    }
+    Ok(report)
+}
+
+/// Audits a single OTA image.
+///
+/// # Arguments:
+/// - `image`: The parsed OTA image.
+/// - `reader`: The stream this image was read from, positioned at the start of the image.
+///
+/// # Returns:
+/// - `Ok(AuditReport)` containing all findings.
+/// - `Err(Error)` if the image data could not be read.
+pub fn audit_ota<R>(image: &OTAImage, reader: &mut R) -> Result<AuditReport, Error>
+where
+    R: io::Read + io::Seek,
+{
+    let mut report = AuditReport::default();
+    let results = image.identify_keys(reader, &KnownKey::defaults())?;
+    audit_key_identification(&mut report, "OTA", &results);
+    audit_ota_image(&mut report, "OTA", image);
+    Ok(report)
+}
+
+fn audit_key_identification(
+    report: &mut AuditReport,
+    location: &str,
+    results: &[KeyIdentification],
+) {
+    let defaults = KnownKey::defaults();
+    for result in results {
+        let location = format!("{}/{}", location, result.kind);
+        match &result.matched {
+            Some(matched) => match &matched.name {
+                Some(name) if defaults.iter().any(|key| key.get_name() == name) => {
+                    report.add_finding(
+                        Severity::Critical,
+                        "default-hash-key",
+                        &location,
+                        &format!("Hash was created using the public default key {}", name),
+                    );
+                }
+                None => {
+                    report.add_finding(
+                        Severity::High,
+                        "no-hash-key",
+                        &location,
+                        &format!("Hash was created without a key ({:?})", matched.algo),
+                    );
+                }
+                _ => {}
+            },
+            None => {
+                report.add_finding(
+                    Severity::Info,
+                    "unknown-hash-key",
+                    &location,
+                    "Hash was created using an unknown key",
+                );
+            }
+        }
+    }
+}
+
+fn audit_keyblock(report: &mut AuditReport, location: &str, keyblock: &KeyBlock, is_ota: bool) {
+    let pubkeys = if is_ota {
+        // the key block of an OTA image stores the OTA signature followed by the encryption
+        // public key
+        vec![("encryption", keyblock.get_hash_pubkey())]
+    } else {
+        vec![
+            ("encryption", keyblock.get_enc_pubkey()),
+            ("hash", keyblock.get_hash_pubkey()),
+        ]
+    };
+
+    for (kind, pubkey) in pubkeys {
+        if let Some(key) = KnownKey::defaults()
+            .iter()
+            .find(|key| key.get_key() == pubkey)
+        {
+            report.add_finding(
+                Severity::Medium,
+                "default-keyblock-key",
+                location,
+                &format!(
+                    "Key block stores the public default key {} as {} key",
+                    key.get_name(),
+                    kind
+                ),
+            );
+        }
+    }
+}
+
+fn audit_partition_table(report: &mut AuditReport, pt_image: &PartitionTableImage) {
+    audit_keyblock(report, "PartTab", &pt_image.keyblock, false);
+    if !pt_image.header.is_encrypt {
+        report.add_finding(
+            Severity::Low,
+            "no-encryption",
+            "PartTab",
+            "Partition table is not encrypted",
+        );
+    }
+
+    let pt: &PartTab = match &pt_image.pt {
+        EncryptedOr::Plain(pt) => pt,
+        EncryptedOr::Encrypted(_) => {
+            report.add_finding(
+                Severity::Info,
+                "encrypted-partition-table",
+                "PartTab",
+                "Partition table could not be decrypted, records were not inspected",
+            );
+            return;
+        }
+    };
+
+    match pt.key_exp_op {
+        KeyExportOp::Both => report.add_finding(
+            Severity::High,
+            "key-export",
+            "PartTab",
+            "Key export operation exports both the latest and the previous key",
+        ),
+        KeyExportOp::Latest => report.add_finding(
+            Severity::Low,
+            "key-export",
+            "PartTab",
+            "Key export operation exports the latest key",
+        ),
+        KeyExportOp::None => {}
+    }
+
+    for (name, state) in [
+        ("rma_w_state", pt.rma_w_state),
+        ("rma_ov_state", pt.rma_ov_state),
+    ] {
+        if state != 0xFF {
+            report.add_finding(
+                Severity::Medium,
+                "rma-state",
+                "PartTab",
+                &format!("RMA state {} is set (0x{:02x})", name, state),
+            );
+        }
+    }
+
+    if pt.ota_trap.valid {
+        report.add_finding(
+            Severity::Low,
+            "ota-trap",
+            "PartTab",
+            &format!(
+                "OTA trap is enabled (port {}, pin {}), the firmware image can be selected using a GPIO",
+                pt.ota_trap.port, pt.ota_trap.pin
+            ),
+        );
+    }
+    if pt.mp_trap.valid {
+        report.add_finding(
+            Severity::Medium,
+            "mp-trap",
+            "PartTab",
+            &format!(
+                "MP trap is enabled (port {}, pin {}), the mass production mode can be entered using a GPIO",
+                pt.mp_trap.port, pt.mp_trap.pin
+            ),
+        );
+    }
+
+    let defaults = KnownKey::defaults();
+    for record in pt.get_records() {
+        let location = format!("PartTab/{:?}", record.part_type);
+        if record.dbg_skip {
+            report.add_finding(
+                Severity::Medium,
+                "dbg-skip",
+                &location,
+                "Debug skip is enabled for this partition",
+            );
+        }
+        if let Some(hash_key) = record.get_hash_key() {
+            if let Some(key) = defaults.iter().find(|key| key.get_key() == hash_key) {
+                report.add_finding(
+                    Severity::High,
+                    "default-record-key",
+                    &location,
+                    &format!(
+                        "Partition record stores the public default key {}",
+                        key.get_name()
+                    ),
+                );
+            }
+        }
+    }
+
+    if pt.get_record(PartitionType::Rdp).is_none() {
+        report.add_finding(
+            Severity::Low,
+            "no-rdp",
+            "PartTab",
+            "There is no RDP (read protection) partition",
+        );
+    }
+}
+
+fn audit_ota_image(report: &mut AuditReport, location: &str, image: &OTAImage) {
+    audit_keyblock(report, location, &image.keyblock, true);
+
+    let mut sce_enabled = false;
+    for (idx, subimage) in image.get_subimages().iter().enumerate() {
+        if subimage.is_encrypted() {
+            report.add_finding(
+                Severity::Info,
+                "encrypted-subimage",
+                &format!("{}/subimage {}", location, idx),
+                "Subimage could not be decrypted, sections were not inspected",
+            );
+            continue;
+        }
+
+        for (sect_idx, section) in subimage.get_sections().iter().enumerate() {
+            if !section.is_sce_enabled() {
+                continue;
+            }
+
+            sce_enabled = true;
+            if section.get_sce_key_iv() == (XIP_KEY, XIP_IV) {
+                report.add_finding(
+                    Severity::High,
+                    "default-xip-key",
+                    &format!("{}/subimage {}/section {}", location, idx, sect_idx),
+                    "XIP section is encrypted using the default XIP_KEY and XIP_IV",
+                );
+            }
+        }
+    }
+
+    let encrypted = image
+        .get_subimages()
+        .iter()
+        .any(|subimage| subimage.header.is_encrypt);
+    if !encrypted && !sce_enabled {
+        report.add_finding(
+            Severity::Medium,
+            "no-encryption",
+            location,
+            "Firmware image is neither encrypted nor uses flash (XIP) encryption",
+        );
+    }
+}
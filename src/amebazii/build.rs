@@ -0,0 +1,450 @@
+//! Builders creating images from ELF files.
+//!
+//! The application ELF file is split into sub-images according to the memory regions of
+//! the linker script (see [`crate::map`]):
+//!
+//! | Region                                      | Sub-Image | Section |
+//! |---------------------------------------------|-----------|---------|
+//! | `RAM_FUN_TABLE`, `RAM_IMG_SIGN`, `DTCM_RAM` | `FHWSS`   | `SRAM`  |
+//! | `PSRAM`                                     | `FHWSS`   | `PSRAM` |
+//! | `XIP_FLASH_C`                               | `Xip`     | `XIP`   |
+//! | `XIP_FLASH_P`                               | `Xip`     | `XIP`   |
+//!
+//! All allocated sections within a region are merged into a single image section. Sections
+//! outside of these regions (e.g. the vector table or heap) are not part of the image.
+//!
+//! Boot images are built from the load segments of the bootloader ELF file, which must be
+//! placed within RAM (see [`build_boot_image`]). Existing OTA images can be updated with the contents of a
+//! (relinked) ELF file using [`repack_ota_image`].
+
+use object::{elf, Object, ObjectSection, ObjectSegment, SectionFlags};
+
+use crate::{
+    conf::ImageCfg,
+    error::Error,
+    keys::{APP_DEFAULT_USER_KEY2, BOOT_DEFAULT_USER_KEY1},
+    keystore::KeyStore,
+    map::{self, AddressRange, DTCM_RAM, RAM_FUN_TABLE, XIP_FLASH_C, XIP_FLASH_P},
+    signer::Signer,
+    types::{
+        enums::{ImageType, SectionType},
+        header::ImageHeader,
+        section::Section,
+        set_default_segment_size, set_signature_with, BootImage, OTAImage, OTAImageBuilder,
+        SectionBuilder, SubImageBuilder, VerifyReport,
+    },
+};
+
+/// A memory region of the application and its location within the OTA image.
+struct AppRegion {
+    img_type: ImageType,
+    sect_type: SectionType,
+    range: AddressRange,
+}
+
+/// All memory regions stored in an OTA image, in the order they are placed in the image.
+const APP_REGIONS: [AppRegion; 4] = [
+    // RAM function table, RAM image signature and DTCM (text, data and rodata)
+    AppRegion {
+        img_type: ImageType::FHWSS,
+        sect_type: SectionType::SRAM,
+        range: AddressRange::new(RAM_FUN_TABLE.start(), DTCM_RAM.end()),
+    },
+    AppRegion {
+        img_type: ImageType::FHWSS,
+        sect_type: SectionType::PSRAM,
+        range: map::PSRAM,
+    },
+    AppRegion {
+        img_type: ImageType::Xip,
+        sect_type: SectionType::XIP,
+        range: XIP_FLASH_C,
+    },
+    AppRegion {
+        img_type: ImageType::Xip,
+        sect_type: SectionType::XIP,
+        range: XIP_FLASH_P,
+    },
+];
+
+/// The RAM region a boot image is loaded into.
+const BOOT_RAM: AddressRange = AddressRange::new(RAM_FUN_TABLE.start(), DTCM_RAM.end());
+
+/// Parses the given ELF file.
+fn parse_elf(elf_data: &[u8]) -> Result<object::File<'_>, Error> {
+    object::File::parse(elf_data)
+        .map_err(|e| Error::InvalidState(format!("Failed to parse ELF file: {}", e)))
+}
+
+/// Loads all allocated sections with data within the given address range.
+///
+/// Gaps between sections are filled with zeros.
+///
+/// # Arguments:
+/// - `file`: The parsed ELF file.
+/// - `range`: The address range to load.
+///
+/// # Returns:
+/// - `Ok(Some((address, data)))` containing the lowest section address and the merged data.
+/// - `Ok(None)` if no section is placed within the range.
+/// - `Err(Error)` if a section exceeds the range or its data could not be read.
+fn load_range(
+    file: &object::File<'_>,
+    range: &AddressRange,
+) -> Result<Option<(u32, Vec<u8>)>, Error> {
+    let mut sections = Vec::new();
+    for section in file.sections() {
+        let is_alloc = match section.flags() {
+            SectionFlags::Elf { sh_flags } => sh_flags & elf::SHF_ALLOC as u64 != 0,
+            _ => false,
+        };
+        // uninitialized sections (.bss) do not have any file data
+        if !is_alloc || section.file_range().is_none() || section.size() == 0 {
+            continue;
+        }
+
+        let address = section.address();
+        if !range.contains(address) {
+            continue;
+        }
+        if address + section.size() > range.end() {
+            return Err(Error::InvalidState(format!(
+                "Section {} (0x{:08x}, {} bytes) exceeds its memory region (0x{:08x}-0x{:08x})",
+                section.name().unwrap_or("<unknown>"),
+                address,
+                section.size(),
+                range.start(),
+                range.end()
+            )));
+        }
+        let data = section
+            .data()
+            .map_err(|e| Error::InvalidState(format!("Failed to read section data: {}", e)))?;
+        sections.push((address, data));
+    }
+    Ok(merge_data(sections))
+}
+
+/// Loads all load segments (`PT_LOAD`) with data, which must be placed within the given
+/// address range.
+///
+/// Gaps between segments are filled with zeros.
+///
+/// # Arguments:
+/// - `file`: The parsed ELF file.
+/// - `range`: The address range all segments must be placed in.
+///
+/// # Returns:
+/// - `Ok(Some((address, data)))` containing the lowest segment address and the merged data.
+/// - `Ok(None)` if there is no load segment with data.
+/// - `Err(Error)` if a segment exceeds the range or its data could not be read.
+fn load_segments(
+    file: &object::File<'_>,
+    range: &AddressRange,
+) -> Result<Option<(u32, Vec<u8>)>, Error> {
+    let mut segments = Vec::new();
+    for segment in file.segments() {
+        // uninitialized data (.bss) is not stored in the file
+        if segment.file_range().1 == 0 {
+            continue;
+        }
+
+        let address = segment.address();
+        let data = segment
+            .data()
+            .map_err(|e| Error::InvalidState(format!("Failed to read segment data: {}", e)))?;
+        if !range.contains(address) || address + data.len() as u64 > range.end() {
+            return Err(Error::InvalidState(format!(
+                "Load segment (0x{:08x}, {} bytes) exceeds its memory region (0x{:08x}-0x{:08x})",
+                address,
+                data.len(),
+                range.start(),
+                range.end()
+            )));
+        }
+        segments.push((address, data));
+    }
+    Ok(merge_data(segments))
+}
+
+/// Merges the given data blocks into a single buffer, filling all gaps with zeros.
+///
+/// # Returns:
+/// - `Some((address, data))` containing the lowest address and the merged data.
+/// - `None` if there is no data block.
+fn merge_data(blocks: Vec<(u64, &[u8])>) -> Option<(u32, Vec<u8>)> {
+    let start = blocks.iter().map(|(address, _)| *address).min()?;
+    let end = blocks
+        .iter()
+        .map(|(address, data)| *address + data.len() as u64)
+        .max()
+        .unwrap_or(start);
+
+    let mut buffer = vec![0x00; (end - start) as usize];
+    for (address, data) in blocks {
+        let offset = (address - start) as usize;
+        buffer[offset..offset + data.len()].copy_from_slice(data);
+    }
+    Some((start as u32, buffer))
+}
+
+/// Creates a section of the given type storing the provided data.
+fn build_section(
+    config: &ImageCfg,
+    sect_type: SectionType,
+    load_address: u32,
+    data: Vec<u8>,
+) -> SectionBuilder {
+    let mut section = SectionBuilder::new(sect_type)
+        .load_address(load_address)
+        .data(data);
+    if let Some(secfg) = config.get_section_cfg(sect_type) {
+        if secfg.entry_addr.is_some() {
+            section = section.entry_address(secfg.entry_addr);
+        }
+        section = section
+            .xip_page_size(secfg.xip_page_size)
+            .xip_block_size(secfg.xip_block_size);
+    }
+    section
+}
+
+/// Creates the image header of the given type from the configuration.
+///
+/// If no user key has been configured, the default user key of the image type is used.
+fn build_header(config: &ImageCfg, img_type: ImageType) -> Result<ImageHeader, Error> {
+    let mut header: ImageHeader = config.imghdr.clone().try_into()?;
+    header.img_type = img_type;
+    if !config.imghdr.has_user_keys() {
+        match img_type {
+            ImageType::Boot => header.set_user_key1(Some(*BOOT_DEFAULT_USER_KEY1)),
+            _ => header.set_user_key2(Some(*APP_DEFAULT_USER_KEY2)),
+        }
+    }
+    Ok(header)
+}
+
+/// Builds a signed OTA image from the given application ELF file.
+///
+/// The RAM code is stored in the first sub-image (`FHWSS`), followed by one `Xip` sub-image
+/// per XIP region. The image header of the first sub-image is taken from the configuration.
+/// The key block stores the public key of the encryption private key
+/// (see [`KeyStore::get_enc_priv_key`]) and all hashes are computed using the given signer,
+/// which should use the firmware hash key (see [`KeyStore::get_fw_hash_key`]).
+///
+/// # Arguments:
+/// - `elf_data`: The raw application ELF file.
+/// - `config`: The image configuration.
+/// - `keys`: The key store.
+/// - `signer`: The signer used to compute all hashes.
+///
+/// # Returns:
+/// - `Ok(OTAImage)` containing the signed OTA image.
+/// - `Err(Error)` if the ELF file could not be parsed, contains no RAM code, the hash
+///   algorithm is not SHA-256 or the image could not be signed.
+///
+/// # Example
+/// ```no_run
+/// use amebazii::{build::build_ota_image, conf::ImageCfg, keystore::KeyStore, signer::KeySigner};
+///
+/// let keys = KeyStore::new();
+/// let signer = KeySigner::new(Some(keys.get_fw_hash_key()));
+/// let elf_data = std::fs::read("application.axf").unwrap();
+/// let image = build_ota_image(&elf_data, &ImageCfg::default(), &keys, &signer).unwrap();
+/// assert!(image.get_subimages().len() > 0);
+/// ```
+pub fn build_ota_image(
+    elf_data: &[u8],
+    config: &ImageCfg,
+    keys: &KeyStore,
+    signer: &dyn Signer,
+) -> Result<OTAImage, Error> {
+    let file = parse_elf(elf_data)?;
+
+    let mut sections = Vec::new();
+    for region in &APP_REGIONS {
+        if let Some((load_address, data)) = load_range(&file, &region.range)? {
+            let section = build_section(config, region.sect_type, load_address, data);
+            sections.push((region.img_type, section));
+        }
+    }
+    if !matches!(sections.first(), Some((ImageType::FHWSS, _))) {
+        return Err(Error::InvalidState(
+            "ELF file does not contain any RAM code".to_string(),
+        ));
+    }
+
+    // all RAM sections share one sub-image, each XIP region gets its own sub-image
+    let mut builder = OTAImageBuilder::new();
+    let mut subimage = SubImageBuilder::new(ImageType::FHWSS)
+        .header(build_header(config, ImageType::FHWSS)?)
+        .fst(config.build_fst());
+    for (img_type, section) in sections {
+        if img_type != ImageType::FHWSS {
+            builder = builder.subimage(subimage);
+            subimage = SubImageBuilder::new(img_type).fst(config.build_fst());
+        }
+        subimage = subimage.section(section);
+    }
+    builder.subimage(subimage).finalize_with(keys, signer)
+}
+
+/// Returns the address range a section of an existing OTA image may occupy.
+///
+/// Sections within one of the known memory regions may grow up to the end of that region,
+/// all other sections are limited to their current length.
+fn section_range(section: &Section) -> AddressRange {
+    let start = section.entry_header.load_address as u64;
+    match APP_REGIONS
+        .iter()
+        .find(|region| region.range.contains(start))
+    {
+        Some(region) => AddressRange::new(start, region.range.end()),
+        None => AddressRange::new(start, start + section.get_data().len() as u64),
+    }
+}
+
+/// Replaces the section data of an existing OTA image with the contents of the given ELF file.
+///
+/// The data of each section is taken from all allocated ELF sections starting at its load
+/// address (up to the end of its memory region, see the module documentation), which
+/// matches the sections created by `ota relink`. Sections without any data in the ELF file
+/// are left untouched. The ELF data is stored as it is.
+///
+/// Afterward, all lengths, offsets and hashes are recomputed (see [`OTAImage::normalize_with`]).
+///
+/// # Arguments:
+/// - `elf_data`: The raw ELF file, e.g. created by `ota relink`.
+/// - `image`: The OTA image used as template. All sub-images must be decrypted.
+/// - `signer`: The signer used to compute all hashes.
+///
+/// # Returns:
+/// - `Ok(VerifyReport)` containing all fields that have been recomputed.
+/// - `Err(Error)` if the ELF file could not be parsed, a sub-image is encrypted, an ELF section
+///   exceeds its memory region or the image could not be signed.
+///
+/// # Example
+/// ```
+/// use amebazii::{build::repack_ota_image, keystore::KeyStore, signer::KeySigner};
+/// use amebazii::types::{from_stream, to_bytes, OTAImage};
+/// use object::build::elf::{Builder, SectionData};
+/// use std::io::Cursor;
+///
+/// let data = include_bytes!("../../assets/fw1.bin");
+/// let mut image: OTAImage = from_stream(&mut Cursor::new(data)).unwrap();
+///
+/// // an ELF file storing each section at its load address (like `ota relink`)
+/// let mut builder = Builder::new(object::Endianness::Little, false);
+/// builder.header.e_type = object::elf::ET_EXEC;
+/// builder.header.e_machine = object::elf::EM_ARM;
+/// let shstrtab = builder.sections.add();
+/// shstrtab.name = b".shstrtab"[..].into();
+/// shstrtab.sh_type = object::elf::SHT_STRTAB;
+/// shstrtab.data = SectionData::SectionString;
+/// for subimage in image.get_subimages_mut() {
+///     for section in subimage.get_sections_mut() {
+///         let address = section.entry_header.load_address;
+///         let elf_section = builder.sections.add();
+///         elf_section.name = format!(".text_{:08x}", address).into_bytes().into();
+///         elf_section.sh_type = object::elf::SHT_PROGBITS;
+///         elf_section.sh_flags = object::elf::SHF_ALLOC as u64;
+///         elf_section.sh_addr = address as u64;
+///         elf_section.sh_size = section.get_data().len() as u64;
+///         elf_section.data = SectionData::Data(section.get_data().to_vec().into());
+///
+///         // the template data is replaced by the ELF data
+///         section.set_data(vec![0x00; section.get_data().len()]);
+///     }
+/// }
+/// let mut elf_data = Vec::new();
+/// builder.write(&mut elf_data).unwrap();
+///
+/// let signer = KeySigner::new(Some(KeyStore::new().get_fw_hash_key()));
+/// repack_ota_image(&elf_data, &mut image, &signer).unwrap();
+/// assert_eq!(to_bytes(&image).unwrap(), data);
+/// ```
+pub fn repack_ota_image(
+    elf_data: &[u8],
+    image: &mut OTAImage,
+    signer: &dyn Signer,
+) -> Result<VerifyReport, Error> {
+    let file = parse_elf(elf_data)?;
+    for (idx, subimage) in image.get_subimages_mut().iter_mut().enumerate() {
+        if subimage.is_encrypted() {
+            return Err(Error::InvalidState(format!(
+                "Subimage {} is encrypted and must be decrypted first",
+                idx
+            )));
+        }
+
+        for section in subimage.get_sections_mut() {
+            let load_address = section.entry_header.load_address;
+            let (start, data) = match load_range(&file, &section_range(section))? {
+                Some(loaded) => loaded,
+                None => continue,
+            };
+
+            // the section data starts at its load address
+            let mut text = vec![0x00; (start - load_address) as usize];
+            text.extend_from_slice(&data);
+            section.set_data(text);
+        }
+    }
+    image.normalize_with(signer)
+}
+
+/// Builds a signed boot image from the given bootloader ELF file.
+///
+/// The text of the boot image contains all load segments (`PT_LOAD`), starting at the lowest
+/// segment address (gaps are filled with zeros). All segments must be placed within RAM
+/// (`0x10000480-0x1003FA00`). The entry address is taken from the ELF header. The image header is taken from the configuration and the hash is
+/// computed using the given signer, which should use the hash key
+/// (see [`KeyStore::get_hash_key`]).
+///
+/// # Arguments:
+/// - `elf_data`: The raw bootloader ELF file.
+/// - `config`: The image configuration (only the image header is used).
+/// - `signer`: The signer used to compute the hash.
+///
+/// # Returns:
+/// - `Ok(BootImage)` containing the signed boot image.
+/// - `Err(Error)` if the ELF file could not be parsed, contains no loadable data, a load
+///   segment is placed outside of RAM or the image could not be signed.
+///
+/// # Example
+/// ```no_run
+/// use amebazii::{build::build_boot_image, conf::ImageCfg, keystore::KeyStore, signer::KeySigner};
+///
+/// let keys = KeyStore::new();
+/// let signer = KeySigner::new(Some(keys.get_hash_key()));
+/// let elf_data = std::fs::read("bootloader.axf").unwrap();
+/// let image = build_boot_image(&elf_data, &ImageCfg::default(), &signer).unwrap();
+/// assert!(image.get_text().len() > 0);
+/// ```
+pub fn build_boot_image(
+    elf_data: &[u8],
+    config: &ImageCfg,
+    signer: &dyn Signer,
+) -> Result<BootImage, Error> {
+    let file = parse_elf(elf_data)?;
+
+    let (load_address, text) = match load_segments(&file, &BOOT_RAM)? {
+        Some(loaded) => loaded,
+        None => {
+            return Err(Error::InvalidState(
+                "ELF file does not contain any loadable segment".to_string(),
+            ))
+        }
+    };
+
+    let mut image = BootImage::default();
+    image.header = build_header(config, ImageType::Boot)?;
+    image.entry.load_address = load_address;
+    image.entry.entry_address = Some(file.entry() as u32);
+    image.set_text(text);
+
+    // the text is padded to 0x20 bytes
+    set_default_segment_size(&mut image);
+    set_signature_with(&mut image, signer)?;
+    Ok(image)
+}
@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    expect_length,
+    types::{
+        enums::{HashAlgo, SectionType, XipPageRemapSize},
+        fst::FST,
+        header::ImageHeader,
+        key_from_hex,
+    },
+};
+
+/// Configuration of the image header of the first sub-image (or the boot image).
+///
+/// If no user key is set, the default user key of the image type is used
+/// ([`crate::keys::APP_DEFAULT_USER_KEY2`] for OTA images and
+/// [`crate::keys::BOOT_DEFAULT_USER_KEY1`] for boot images).
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ImageHeaderCfg {
+    /// The serial number (version) of the image.
+    pub serial: u32,
+
+    /// The first user key as hex string.
+    pub user_key1: Option<String>,
+
+    /// The second user key as hex string.
+    pub user_key2: Option<String>,
+}
+
+impl ImageHeaderCfg {
+    /// Returns `true` if at least one user key has been configured.
+    pub fn has_user_keys(&self) -> bool {
+        self.user_key1.is_some() || self.user_key2.is_some()
+    }
+}
+
+impl TryInto<ImageHeader> for ImageHeaderCfg {
+    type Error = crate::error::Error;
+
+    /// Converts an `ImageHeaderCfg` instance into an `ImageHeader` instance.
+    ///
+    /// The image type and segment size are not covered by the configuration and must be
+    /// set by the caller.
+    ///
+    /// # Returns:
+    /// - `Ok(ImageHeader)`: An `ImageHeader` with the serial and user keys set.
+    /// - `Err(Error)`: An error if one of the user keys is not a 64-character hex string.
+    fn try_into(self) -> Result<ImageHeader, Self::Error> {
+        let mut header = ImageHeader {
+            serial: self.serial,
+            ..ImageHeader::default()
+        };
+
+        if let Some(user_key1) = &self.user_key1 {
+            expect_length!(user_key1, 64);
+            header.set_user_key1(key_from_hex(user_key1));
+        }
+        if let Some(user_key2) = &self.user_key2 {
+            expect_length!(user_key2, 64);
+            header.set_user_key2(key_from_hex(user_key2));
+        }
+        Ok(header)
+    }
+}
+
+/// Configuration of the firmware security table (FST) of each sub-image.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct FSTCfg {
+    /// The hash algorithm used to hash the sub-images (default: `Sha256`).
+    pub hash_algo: Option<HashAlgo>,
+
+    /// The partition size stored in the FST.
+    pub partition_size: u32,
+}
+
+impl Default for FSTCfg {
+    /// Returns the default configuration for an `FSTCfg` instance.
+    ///
+    /// The default values are:
+    /// - `hash_algo`: `Some(HashAlgo::Sha256)`
+    /// - `partition_size`: `0`
+    fn default() -> Self {
+        Self {
+            hash_algo: Some(HashAlgo::Sha256),
+            partition_size: 0,
+        }
+    }
+}
+
+impl From<&FSTCfg> for FST {
+    /// Creates an unencrypted `FST` using the configured hash algorithm.
+    fn from(config: &FSTCfg) -> Self {
+        let mut fst = FST::default();
+        fst.hash_algo = config.hash_algo;
+        fst.partition_size = config.partition_size;
+        fst
+    }
+}
+
+/// Configuration applied to all sections of the given type.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SectionCfg {
+    /// The section type this configuration applies to.
+    pub sect_type: SectionType,
+
+    /// The entry address of the section. If not set, the load address is used for
+    /// `SRAM` sections and no entry address is stored for all other sections.
+    pub entry_addr: Option<u32>,
+
+    /// The XIP page remap size.
+    #[serde(default)]
+    pub xip_page_size: XipPageRemapSize,
+
+    /// The XIP block size.
+    #[serde(default)]
+    pub xip_block_size: u8,
+}
+
+/// Represents the configuration used to build an OTA image or boot image from an ELF file.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ImageCfg {
+    /// The header of the first sub-image or the boot image.
+    #[serde(default)]
+    pub imghdr: ImageHeaderCfg,
+
+    /// The FST used by all sub-images (see [`FSTCfg::default`] if not set).
+    pub fstcfg: Option<FSTCfg>,
+
+    /// Section specific settings.
+    #[serde(default)]
+    pub secfg: Vec<SectionCfg>,
+}
+
+impl ImageCfg {
+    /// Returns the section configuration of the given section type, if any.
+    ///
+    /// # Arguments:
+    /// - `sect_type`: The section type to search for.
+    ///
+    /// # Returns:
+    /// - The first matching section configuration or `None`.
+    pub fn get_section_cfg(&self, sect_type: SectionType) -> Option<&SectionCfg> {
+        self.secfg.iter().find(|cfg| cfg.sect_type == sect_type)
+    }
+
+    /// Builds the FST of a sub-image from this configuration.
+    pub fn build_fst(&self) -> FST {
+        match &self.fstcfg {
+            Some(fstcfg) => fstcfg.into(),
+            None => (&FSTCfg::default()).into(),
+        }
+    }
+}
@@ -7,6 +7,12 @@ pub use pt::{PartitionItemCfg, PartitionTableCfg};
 pub mod sysctrl;
 pub use sysctrl::SystemDataCfg;
 
+pub mod image;
+pub use image::{FSTCfg, ImageCfg, ImageHeaderCfg, SectionCfg};
+
+pub mod patch;
+pub use patch::{PatchCfg, PatchItemCfg};
+
 #[macro_export]
 macro_rules! expect_length {
     ($value:expr, $expected:literal) => {
@@ -0,0 +1,70 @@
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// A single binary patch at a virtual (runtime) address.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PatchItemCfg {
+    /// The virtual address of the first patched byte, either as number or as hex
+    /// string (e.g. `"0x9b000200"`).
+    #[serde(deserialize_with = "deserialize_address")]
+    pub address: u32,
+
+    /// The bytes expected at the given address (hex string).
+    #[serde(with = "hex")]
+    pub original: Vec<u8>,
+
+    /// The bytes that replace the original bytes (hex string).
+    #[serde(with = "hex")]
+    pub replacement: Vec<u8>,
+
+    /// An optional description of this patch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// Represents a patch file containing a list of binary patches.
+///
+/// # Example
+/// ```
+/// use amebazii::conf::PatchCfg;
+///
+/// let patch: PatchCfg = serde_json::from_str(r#"{
+///     "patches": [
+///         { "address": "0x9b000200", "original": "0120", "replacement": "0020" }
+///     ]
+/// }"#).unwrap();
+/// assert_eq!(patch.patches[0].address, 0x9b00_0200);
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PatchCfg {
+    /// All patches, applied in the given order.
+    pub patches: Vec<PatchItemCfg>,
+}
+
+/// Deserializes an address from a number or a (hex) string.
+fn deserialize_address<'de, D>(deserializer: D) -> Result<u32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Address {
+        Number(u32),
+        String(String),
+    }
+
+    match Address::deserialize(deserializer)? {
+        Address::Number(address) => Ok(address),
+        Address::String(address) => {
+            let result = match address
+                .strip_prefix("0x")
+                .or_else(|| address.strip_prefix("0X"))
+            {
+                Some(hex) => u32::from_str_radix(hex, 16),
+                None => address.parse(),
+            };
+            result.map_err(|e| {
+                serde::de::Error::custom(format!("invalid address {}: {}", address, e))
+            })
+        }
+    }
+}
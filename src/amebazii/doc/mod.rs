@@ -4,4 +4,7 @@ pub mod cli {
 
     #[doc = include_str!("cmd_flash.md")]
     pub mod flash {}
+
+    #[doc = include_str!("cmd_secure.md")]
+    pub mod secure {}
 }
@@ -12,10 +12,12 @@ pub enum Error {
     OpenSSLError(ErrorStack),
     Utf8Error(FromUtf8Error),
     UnsupportedHashAlgo(u8),
+    UnsupportedEncryptionAlgo(u8),
     NotImplemented(String),
     InvalidState(String),
     SerdeJSONError(serde_json::Error),
     UnknownNVDMType(String),
+    VerificationFailed(String),
 
     // REVISIT: must be reworked
     // individual parsing errors
@@ -1,5 +1,7 @@
 //! Default keys used to sign and verify images/ partition tables.
 
+use crate::{error::Error, util::x25519};
+
 /// Represents a pair of cryptographic keys: a private key and a public key.
 pub struct ConstKeyPair<'s> {
     priv_key: &'s [u8; 32], // The private key (32 bytes)
@@ -34,6 +36,25 @@ impl<'s> ConstKeyPair<'s> {
     pub const fn get_pub_key(&self) -> &'s [u8; 32] {
         self.pub_key
     }
+
+    /// Computes the X25519 shared secret of this key pair and the public key of the peer.
+    ///
+    /// # Parameters
+    /// - `peer_pub_key`: The 32-byte X25519 public key of the peer (e.g. the device).
+    ///
+    /// # Returns
+    /// Returns the 32-byte shared secret (see [`crate::util::x25519`]).
+    ///
+    /// # Example
+    /// ```
+    /// use amebazii::keys::{KEY_PAIR_000, KEY_PAIR_001};
+    ///
+    /// let secret = KEY_PAIR_000.shared_secret(KEY_PAIR_001.get_pub_key()).unwrap();
+    /// assert_eq!(secret, KEY_PAIR_001.shared_secret(KEY_PAIR_000.get_pub_key()).unwrap());
+    /// ```
+    pub fn shared_secret(&self, peer_pub_key: &[u8]) -> Result<[u8; 32], Error> {
+        x25519(self.priv_key, peer_pub_key)
+    }
 }
 
 // Default values for cryptographic keys and patterns used throughout the system.
@@ -112,3 +133,56 @@ pub const KEY_PAIR_003: ConstKeyPair = ConstKeyPair::new(
     b"\x00\x01\x02\x03\x04\x05\x06\x07\x08\t\n\x0b\x0c\r\x0e\x0f\x10\x11\x12\x13\x14\x15\x16\x17\x18\x19\x1a\x1b\x1c\x1d\x1e_",
     b"\x8f@\xc5\xad\xb6\x8f%bJ\xe5\xb2\x14\xeavzn\xc9M\x82\x9d={^\x1a\xd1\xbao>!8(_"
 );
+
+/// A named key that is tried when identifying which key was used to create a hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnownKey {
+    name: String,
+    key: Vec<u8>,
+}
+
+impl KnownKey {
+    /// Creates a new named key.
+    ///
+    /// # Parameters
+    /// - `name`: A human-readable name of the key (e.g. `HASH_KEY`).
+    /// - `key`: The raw key data.
+    pub fn new(name: &str, key: &[u8]) -> Self {
+        KnownKey {
+            name: name.to_string(),
+            key: key.to_vec(),
+        }
+    }
+
+    /// Returns the name of the key.
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the raw key data.
+    pub fn get_key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// Returns all built-in keys: [`HASH_KEY`] as well as the private and public key of
+    /// [`KEY_PAIR_000`] to [`KEY_PAIR_003`].
+    pub fn defaults() -> Vec<KnownKey> {
+        let mut keys = vec![KnownKey::new("HASH_KEY", HASH_KEY)];
+        for (name, pair) in [
+            ("KEY_PAIR_000", KEY_PAIR_000),
+            ("KEY_PAIR_001", KEY_PAIR_001),
+            ("KEY_PAIR_002", KEY_PAIR_002),
+            ("KEY_PAIR_003", KEY_PAIR_003),
+        ] {
+            keys.push(KnownKey::new(
+                &format!("{} (private)", name),
+                pair.get_priv_key(),
+            ));
+            keys.push(KnownKey::new(
+                &format!("{} (public)", name),
+                pair.get_pub_key(),
+            ));
+        }
+        keys
+    }
+}
@@ -0,0 +1,279 @@
+//! Named keys loaded from key files, used to resolve signing, verification and
+//! encryption keys.
+//!
+//! A key store replaces the hard-coded default keys of [`crate::keys`] with user-defined
+//! ones. Keys are stored under a name and can be loaded from JSON files (mapping names to
+//! hex strings), hex files or raw binary files:
+//!
+//! ```json
+//! {
+//!     "hash_key": "47e5661335a4c5e0a94d69f3c737d54f2383791332939753ef24279608f6d72b",
+//!     "fw_hash_key": "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e5f"
+//! }
+//! ```
+//!
+//! The well-known names [`HASH_KEY_NAME`], [`FW_HASH_KEY_NAME`], [`ENC_PRIV_KEY_NAME`] and
+//! [`HASH_PRIV_KEY_NAME`] override the corresponding default key. Per-partition hash keys (e.g. `fw1_hash_key`) can
+//! be taken from a parsed partition table using [`KeyStore::add_partition_keys`].
+
+use std::{fs, io, path::Path};
+
+use crate::{
+    error::Error,
+    keys::{KnownKey, HASH_KEY, KEY_PAIR_000, KEY_PAIR_001, KEY_PAIR_003},
+    signer::KeySigner,
+    types::{PartTab, PartitionType},
+};
+
+/// Name of the key used to sign the partition table and the boot image.
+pub const HASH_KEY_NAME: &str = "hash_key";
+
+/// Name of the key used to sign firmware (OTA) images.
+pub const FW_HASH_KEY_NAME: &str = "fw_hash_key";
+
+/// Name of the X25519 private key whose public key is stored as encryption public key in the
+/// key block.
+pub const ENC_PRIV_KEY_NAME: &str = "enc_priv_key";
+
+/// Name of the X25519 private key whose public key is stored as hash public key in the key
+/// block of the partition table and boot image.
+pub const HASH_PRIV_KEY_NAME: &str = "hash_priv_key";
+
+/// A collection of named keys.
+///
+/// # Example
+/// ```
+/// use amebazii::keys::HASH_KEY;
+/// use amebazii::keystore::{KeyStore, FW_HASH_KEY_NAME};
+///
+/// let mut keys = KeyStore::new();
+/// keys.add_key(FW_HASH_KEY_NAME, &[0x42; 32]);
+///
+/// assert_eq!(keys.get_fw_hash_key(), &[0x42; 32]);
+/// assert_eq!(keys.get_hash_key(), HASH_KEY);
+/// assert_eq!(keys.resolve(FW_HASH_KEY_NAME).unwrap(), vec![0x42; 32]);
+/// assert_eq!(keys.resolve("0102").unwrap(), vec![0x01, 0x02]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct KeyStore {
+    keys: Vec<KnownKey>,
+}
+
+impl KeyStore {
+    /// Creates a new, empty key store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a named key to this store, replacing an existing key with the same name.
+    ///
+    /// # Arguments:
+    /// - `name`: The name of the key.
+    /// - `key`: The raw key data.
+    pub fn add_key(&mut self, name: &str, key: &[u8]) {
+        let key = KnownKey::new(name, key);
+        match self.keys.iter_mut().find(|k| k.get_name() == name) {
+            Some(existing) => *existing = key,
+            None => self.keys.push(key),
+        }
+    }
+
+    /// Returns the key with the given name (if any).
+    pub fn get_key(&self, name: &str) -> Option<&[u8]> {
+        self.keys
+            .iter()
+            .find(|key| key.get_name() == name)
+            .map(KnownKey::get_key)
+    }
+
+    /// Returns all keys of this store.
+    pub fn get_keys(&self) -> &[KnownKey] {
+        &self.keys
+    }
+
+    /// Returns `true` if this store does not contain any keys.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Loads all keys from a JSON object mapping key names to hex strings.
+    ///
+    /// # Arguments:
+    /// - `reader`: The JSON input.
+    ///
+    /// # Returns:
+    /// - `Ok(())` if all keys were loaded.
+    /// - `Err(Error)` if the input is not a JSON object of hex strings.
+    pub fn load_json<R: io::Read>(&mut self, reader: R) -> Result<(), Error> {
+        let keys: serde_json::Map<String, serde_json::Value> = serde_json::from_reader(reader)?;
+        for (name, value) in keys {
+            match value.as_str() {
+                Some(key) => self.add_key(&name, &hex::decode(key)?),
+                None => {
+                    return Err(Error::InvalidState(format!(
+                        "Key {:?} must be a hex string",
+                        name
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads keys from the given file.
+    ///
+    /// Files ending with `.json` are parsed using [`KeyStore::load_json`]. Any other file
+    /// stores a single key (hex or raw binary) named after the file stem.
+    ///
+    /// # Arguments:
+    /// - `path`: The path of the key file.
+    ///
+    /// # Returns:
+    /// - `Ok(())` if the key file was loaded.
+    /// - `Err(Error)` if the file could not be read or parsed.
+    pub fn load_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            return self.load_json(io::BufReader::new(fs::File::open(path)?));
+        }
+
+        let name = match path.file_stem() {
+            Some(stem) => stem.to_string_lossy().to_string(),
+            None => {
+                return Err(Error::InvalidState(format!(
+                    "Invalid key file: {}",
+                    path.display()
+                )))
+            }
+        };
+        let key = read_key_file(path)?;
+        self.add_key(&name, &key);
+        Ok(())
+    }
+
+    /// Adds the hash keys of all partition records of the given partition table.
+    ///
+    /// Each key is stored as `<type>_hash_key` (e.g. `fw1_hash_key`), replacing existing keys
+    /// with the same name.
+    ///
+    /// # Arguments:
+    /// - `pt`: The parsed partition table.
+    pub fn add_partition_keys(&mut self, pt: &PartTab) {
+        for record in pt.get_records() {
+            if let Some(key) = record.get_hash_key() {
+                self.add_key(&partition_key_name(record.part_type), key);
+            }
+        }
+    }
+
+    /// Resolves a key given on the command line or in a configuration file.
+    ///
+    /// The value is interpreted as (in this order) the name of a key in this store, the path
+    /// of a key file (hex or raw binary) or a hex string.
+    ///
+    /// # Arguments:
+    /// - `name_or_key`: The key name, key file or hex string.
+    ///
+    /// # Returns:
+    /// - `Ok(Vec<u8>)` containing the raw key data.
+    /// - `Err(Error)` if the value is neither a known name, an existing file nor a hex string.
+    pub fn resolve(&self, name_or_key: &str) -> Result<Vec<u8>, Error> {
+        if let Some(key) = self.get_key(name_or_key) {
+            return Ok(key.to_vec());
+        }
+
+        if fs::exists(name_or_key)? {
+            read_key_file(name_or_key)
+        } else {
+            Ok(hex::decode(name_or_key)?)
+        }
+    }
+
+    /// Returns the key used to sign the partition table and the boot image (defaults to
+    /// [`HASH_KEY`]).
+    pub fn get_hash_key(&self) -> &[u8] {
+        self.get_key(HASH_KEY_NAME).unwrap_or(HASH_KEY)
+    }
+
+    /// Returns the key used to sign firmware images (defaults to the private key of
+    /// [`KEY_PAIR_003`]).
+    pub fn get_fw_hash_key(&self) -> &[u8] {
+        self.get_key(FW_HASH_KEY_NAME)
+            .unwrap_or(KEY_PAIR_003.get_priv_key())
+    }
+
+    /// Returns the X25519 private key of the key block encryption public key (defaults to the
+    /// private key of [`KEY_PAIR_000`]).
+    pub fn get_enc_priv_key(&self) -> &[u8] {
+        self.get_key(ENC_PRIV_KEY_NAME)
+            .unwrap_or(KEY_PAIR_000.get_priv_key())
+    }
+
+    /// Returns the X25519 private key of the hash key pair (defaults to the private key of
+    /// [`KEY_PAIR_001`]).
+    pub fn get_hash_priv_key(&self) -> &[u8] {
+        self.get_key(HASH_PRIV_KEY_NAME)
+            .unwrap_or(KEY_PAIR_001.get_priv_key())
+    }
+
+    /// Returns the hash key of the given partition.
+    ///
+    /// The key added by [`KeyStore::add_partition_keys`] is preferred. Otherwise, firmware
+    /// partitions use [`KeyStore::get_fw_hash_key`] and all other partitions use
+    /// [`KeyStore::get_hash_key`].
+    pub fn get_partition_hash_key(&self, part_type: PartitionType) -> &[u8] {
+        if let Some(key) = self.get_key(&partition_key_name(part_type)) {
+            return key;
+        }
+
+        match part_type {
+            PartitionType::Fw1 | PartitionType::Fw2 => self.get_fw_hash_key(),
+            _ => self.get_hash_key(),
+        }
+    }
+
+    /// Returns a signer using the hash key of the given partition (see
+    /// [`KeyStore::get_partition_hash_key`]).
+    ///
+    /// # Example
+    /// ```
+    /// use amebazii::keys::HASH_KEY;
+    /// use amebazii::keystore::KeyStore;
+    /// use amebazii::signer::{KeySigner, Signer};
+    /// use amebazii::types::{HashAlgo, PartitionType};
+    ///
+    /// let signer = KeyStore::new().get_signer(PartitionType::PartTab);
+    /// assert_eq!(
+    ///     signer.sign(HashAlgo::Sha256, b"data").unwrap(),
+    ///     KeySigner::new(Some(HASH_KEY)).sign(HashAlgo::Sha256, b"data").unwrap()
+    /// );
+    /// ```
+    pub fn get_signer(&self, part_type: PartitionType) -> KeySigner {
+        KeySigner::new(Some(self.get_partition_hash_key(part_type)))
+    }
+
+    /// Returns all keys of this store followed by all built-in keys (see
+    /// [`KnownKey::defaults`]).
+    pub fn known_keys(&self) -> Vec<KnownKey> {
+        let mut keys = self.keys.clone();
+        keys.extend(KnownKey::defaults());
+        keys
+    }
+}
+
+/// Returns the name of the hash key of the given partition (e.g. `fw1_hash_key`).
+pub fn partition_key_name(part_type: PartitionType) -> String {
+    format!("{:?}_hash_key", part_type).to_lowercase()
+}
+
+/// Reads a key file, which either stores a hex string or the raw key data.
+fn read_key_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, Error> {
+    let data = fs::read(path)?;
+    let text = data.trim_ascii();
+    if !text.is_empty() && text.iter().all(u8::is_ascii_hexdigit) {
+        if let Ok(key) = hex::decode(text) {
+            return Ok(key);
+        }
+    }
+    Ok(data)
+}
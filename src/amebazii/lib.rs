@@ -3,13 +3,20 @@
     doc = "See the [CLI documentation](./doc/cli/index.html)."
 )]
 
+pub mod audit;
+pub mod build;
+pub mod conf;
 pub mod error;
-pub mod util;
-pub mod types;
 pub mod keys;
+pub mod keystore;
 pub mod map;
-pub mod conf;
+pub mod provision;
+pub mod scan;
+pub mod signer;
+pub mod symbolize;
+pub mod types;
+pub mod util;
 
 #[cfg(feature = "documentation")]
 /// Documentation
-pub mod doc;
\ No newline at end of file
+pub mod doc;
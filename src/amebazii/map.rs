@@ -1,3 +1,15 @@
+use serde::Serialize;
+
+use crate::{
+    error::Error,
+    types::{
+        enums::{PartitionType, SectionType},
+        fst::FST,
+        header::{EntryHeader, ImageHeader, SectionHeader},
+        BinarySize, EncryptedOr, Flash, OTAImage, Partition,
+    },
+};
+
 /// Represents a memory address range, defined by a starting address and an ending address.
 ///
 /// This struct is used to define ranges of memory addresses, providing utilities to check
@@ -36,6 +48,34 @@ pub const XIP_FLASH_C: AddressRange = AddressRange::new(0x9B000140, 0x9B800000);
 /// bytes. We don't include them here.
 pub const XIP_FLASH_P: AddressRange = AddressRange::new(0x9B800140, 0x9BFF0000);
 
+/// All named memory regions, used to describe runtime addresses.
+pub const REGIONS: [(&str, AddressRange); 8] = [
+    ("VECTORS_RAM", VECTORS_RAM),
+    ("RAM_FUN_TABLE", RAM_FUN_TABLE),
+    ("RAM_IMG_SIGN", RAM_IMG_SIGN),
+    ("DTCM_RAM", DTCM_RAM),
+    ("EXTENSION_RAM", EXTENSION_RAM),
+    ("PSRAM", PSRAM),
+    ("XIP_FLASH_C", XIP_FLASH_C),
+    ("XIP_FLASH_P", XIP_FLASH_P),
+];
+
+/// Returns the name of the memory region containing the given address.
+///
+/// # Example
+/// ```
+/// use amebazii::map::region_name;
+///
+/// assert_eq!(region_name(0x9B00_0200), Some("XIP_FLASH_C"));
+/// assert_eq!(region_name(0x0000_0000), None);
+/// ```
+pub fn region_name(address: u64) -> Option<&'static str> {
+    REGIONS
+        .iter()
+        .find(|(_, range)| range.contains(address))
+        .map(|(name, _)| *name)
+}
+
 impl AddressRange {
     /// Creates a new `AddressRange` instance with a given start and end address.
     ///
@@ -85,3 +125,375 @@ impl AddressRange {
         self.0 <= addr && addr < self.1
     }
 }
+
+/// The location of a runtime address within an OTA image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ImageLocation {
+    /// The runtime (virtual) address.
+    pub address: u64,
+
+    /// The index of the sub-image storing the address.
+    pub subimage: usize,
+
+    /// The index of the section within the sub-image.
+    pub section: usize,
+
+    /// The offset relative to the start of the section data.
+    pub section_offset: u64,
+
+    /// The offset relative to the start of the OTA image.
+    pub file_offset: u64,
+
+    /// The absolute offset in flash, if the partition offset is known.
+    pub flash_offset: Option<u64>,
+}
+
+/// The XIP remapping of a section.
+///
+/// The flash controller maps pages of `page_size` bytes starting at `virtual_base` to the
+/// physical flash starting at `physical_base`. Both bases are aligned to the page size, which
+/// is why XIP data starts behind the reserved header data of its sub-image (e.g. at
+/// `0x9B000140`).
+///
+/// **Note:** The translation is linear: all pages of the window are expected to be stored
+/// contiguously in flash, so the virtual address `virtual_base + n` always maps to the file
+/// offset `physical_base + n`. The page size and block size only align and limit the window,
+/// they never change the resolved offset. Remapping individual pages to other locations is not
+/// supported.
+///
+/// # Example
+/// ```
+/// use amebazii::{
+///     keystore::KeyStore,
+///     map::AddressTranslator,
+///     types::{
+///         enums::XipPageRemapSize, ImageType, OTAImageBuilder, SectionBuilder, SectionType,
+///         SubImageBuilder,
+///     },
+/// };
+///
+/// let image = OTAImageBuilder::new()
+///     .subimage(
+///         SubImageBuilder::new(ImageType::FHWSS).section(
+///             SectionBuilder::new(SectionType::SRAM)
+///                 .load_address(0x1000_0480)
+///                 .data(vec![0x00; 0x100]),
+///         ),
+///     )
+///     .subimage(
+///         SubImageBuilder::new(ImageType::Xip).section(
+///             SectionBuilder::new(SectionType::XIP)
+///                 .load_address(0x9B00_0140)
+///                 .xip_page_size(XipPageRemapSize::_32K)
+///                 .xip_block_size(1)
+///                 .data(vec![0x00; 0x10000]),
+///         ),
+///     )
+///     .finalize(&KeyStore::new())
+///     .unwrap();
+///
+/// let translator = AddressTranslator::new(&image);
+/// let remap = translator.get_mappings()[1].remap.unwrap();
+/// assert_eq!((remap.page_size, remap.virtual_base, remap.size), (0x8000, 0x9B00_0000, 0x8000));
+///
+/// // the section data starts at file offset 0x4140, the offset grows linearly
+/// let location = translator.resolve_address(0x9B00_4150).unwrap();
+/// assert_eq!(location.file_offset, 0x8150);
+///
+/// // the second page is part of the section, but not covered by the window (one block)
+/// assert!(translator.resolve_address(0x9B00_8000).is_none());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XipRemap {
+    /// The page remap size in bytes (see [`crate::types::enums::XipPageRemapSize::page_size`]).
+    pub page_size: u64,
+
+    /// The first remapped virtual address.
+    pub virtual_base: u64,
+
+    /// The file offset the virtual base address is mapped to.
+    pub physical_base: u64,
+
+    /// The size of the remapped window in bytes. It covers `xip_block_size` pages or, if the
+    /// block size is not defined (`0`), all pages of the section.
+    pub size: u64,
+}
+
+impl XipRemap {
+    /// Returns the virtual address range covered by this remapping.
+    pub fn range(&self) -> AddressRange {
+        AddressRange::new(self.virtual_base, self.virtual_base + self.size)
+    }
+}
+
+/// The mapping of a single section from its load address to its data in the OTA image.
+#[derive(Debug, Clone, Copy)]
+pub struct SectionMapping {
+    /// The index of the sub-image storing this section.
+    pub subimage: usize,
+
+    /// The index of the section within the sub-image.
+    pub section: usize,
+
+    /// The type of the section.
+    pub sect_type: SectionType,
+
+    /// The address range the section data is loaded to.
+    pub range: AddressRange,
+
+    /// The offset of the section data relative to the start of the OTA image.
+    pub file_offset: u64,
+
+    /// The XIP remapping, only present for `XIP` sections.
+    pub remap: Option<XipRemap>,
+}
+
+impl SectionMapping {
+    /// Checks whether the given address can be resolved through this section.
+    ///
+    /// XIP addresses must also be covered by the remapped window, because the data behind
+    /// the window is not accessible at runtime.
+    pub fn contains(&self, address: u64) -> bool {
+        self.range.contains(address)
+            && self
+                .remap
+                .as_ref()
+                .map_or(true, |remap| remap.range().contains(address))
+    }
+}
+
+/// Translates runtime addresses to locations in an OTA image (and flash) and back.
+///
+/// The translator stores the mapping of every section of all plain (decrypted) sub-images.
+/// Encrypted sub-images are skipped, because the layout of their sections is unknown. XIP
+/// addresses are translated linearly (see [`XipRemap`]).
+///
+/// # Example
+/// ```
+/// use amebazii::{
+///     keystore::KeyStore,
+///     map::AddressTranslator,
+///     types::{ImageType, OTAImageBuilder, SectionBuilder, SectionType, SubImageBuilder},
+/// };
+///
+/// let image = OTAImageBuilder::new()
+///     .subimage(
+///         SubImageBuilder::new(ImageType::FHWSS).section(
+///             SectionBuilder::new(SectionType::SRAM)
+///                 .load_address(0x1000_0480)
+///                 .data(vec![0x00; 0x100]),
+///         ),
+///     )
+///     .subimage(
+///         SubImageBuilder::new(ImageType::Xip).section(
+///             SectionBuilder::new(SectionType::XIP)
+///                 .load_address(0x9B00_0140)
+///                 .data(vec![0x00; 0x100]),
+///         ),
+///     )
+///     .finalize(&KeyStore::new())
+///     .unwrap();
+///
+/// let translator = AddressTranslator::new(&image).with_flash_offset(0x10000);
+/// let location = translator.resolve_address(0x9B00_0150).unwrap();
+/// assert_eq!((location.subimage, location.section), (1, 0));
+/// assert_eq!(location.section_offset, 0x10);
+/// assert_eq!(location.file_offset, 0x4150);
+/// assert_eq!(location.flash_offset, Some(0x14150));
+///
+/// let location = translator.resolve_flash_offset(0x14150).unwrap();
+/// assert_eq!(location.address, 0x9B00_0150);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AddressTranslator {
+    sections: Vec<SectionMapping>,
+    flash_offset: Option<u64>,
+}
+
+impl AddressTranslator {
+    /// Creates a new translator for the given OTA image.
+    ///
+    /// # Arguments:
+    /// - `image`: The parsed OTA image.
+    pub fn new(image: &OTAImage) -> Self {
+        let mut sections = Vec::new();
+        let mut offset = 224_u64;
+        for (idx, subimage) in image.get_subimages().iter().enumerate() {
+            if !subimage.is_encrypted() {
+                let mut sect_offset =
+                    offset + (ImageHeader::binary_size() + FST::binary_size()) as u64;
+                for (sect_idx, section) in subimage.get_sections().iter().enumerate() {
+                    let start = section.entry_header.load_address as u64;
+                    let file_offset = sect_offset
+                        + (SectionHeader::binary_size() + EntryHeader::binary_size()) as u64;
+                    let length = section.get_data().len() as u64;
+
+                    let remap = if section.header.sect_type == SectionType::XIP {
+                        let page_size = section.header.xip_page_size.page_size() as u64;
+                        let virtual_base = start & !(page_size - 1);
+                        let pages = match section.header.xip_block_size {
+                            0 => (start + length - virtual_base).div_ceil(page_size),
+                            block_size => block_size as u64,
+                        };
+                        Some(XipRemap {
+                            page_size,
+                            virtual_base,
+                            physical_base: file_offset - (start - virtual_base),
+                            size: pages * page_size,
+                        })
+                    } else {
+                        None
+                    };
+
+                    sections.push(SectionMapping {
+                        subimage: idx,
+                        section: sect_idx,
+                        sect_type: section.header.sect_type,
+                        range: AddressRange::new(start, start + length),
+                        file_offset,
+                        remap,
+                    });
+                    sect_offset += section.build_aligned_size() as u64;
+                }
+            }
+
+            match subimage.header.next_offset {
+                Some(next_offset) => offset += next_offset as u64,
+                None => break,
+            }
+        }
+        Self {
+            sections,
+            flash_offset: None,
+        }
+    }
+
+    /// Creates a new translator for a firmware partition of the given flash image.
+    ///
+    /// # Arguments:
+    /// - `flash`: The parsed flash image.
+    /// - `part_type`: The firmware partition (`Fw1` or `Fw2`).
+    ///
+    /// # Returns:
+    /// - `Ok(AddressTranslator)` using the start address of the partition as flash offset.
+    /// - `Err(Error)` if the partition table is encrypted or the flash image does not contain
+    ///   the given firmware partition.
+    pub fn from_flash(flash: &Flash, part_type: PartitionType) -> Result<Self, Error> {
+        let start_addr = match flash.get_partition(PartitionType::PartTab) {
+            Some(Partition::PartitionTable(pt_image)) => match &pt_image.pt {
+                EncryptedOr::Plain(pt) => pt.get_record(part_type).map(|record| record.start_addr),
+                EncryptedOr::Encrypted(_) => {
+                    return Err(Error::InvalidState(
+                        "Partition table must be decrypted to resolve flash offsets".to_string(),
+                    ))
+                }
+            },
+            _ => None,
+        };
+
+        match (start_addr, flash.get_partition(part_type)) {
+            (Some(start_addr), Some(Partition::Fw1(image) | Partition::Fw2(image))) => {
+                Ok(Self::new(image).with_flash_offset(start_addr as u64))
+            }
+            _ => Err(Error::InvalidState(format!(
+                "Flash image does not contain a firmware partition of type {:?}",
+                part_type
+            ))),
+        }
+    }
+
+    /// Sets the absolute flash offset of the OTA image (start of the partition).
+    pub fn with_flash_offset(mut self, offset: u64) -> Self {
+        self.flash_offset = Some(offset);
+        self
+    }
+
+    /// Returns the flash offset of the OTA image, if set.
+    pub fn get_flash_offset(&self) -> Option<u64> {
+        self.flash_offset
+    }
+
+    /// Returns the mapping of all sections.
+    pub fn get_mappings(&self) -> &[SectionMapping] {
+        &self.sections
+    }
+
+    /// Resolves a runtime address.
+    ///
+    /// # Arguments:
+    /// - `address`: The runtime (virtual) address.
+    ///
+    /// # Returns:
+    /// - `Some(ImageLocation)` if the address is stored in a section (and, for XIP sections,
+    ///   within the remapped window).
+    /// - `None` otherwise.
+    pub fn resolve_address(&self, address: u64) -> Option<ImageLocation> {
+        self.sections
+            .iter()
+            .find(|mapping| mapping.contains(address))
+            .map(|mapping| self.location(mapping, address - mapping.range.start()))
+    }
+
+    /// Resolves an offset relative to the data of a section.
+    ///
+    /// # Arguments:
+    /// - `subimage`: The index of the sub-image.
+    /// - `section`: The index of the section within the sub-image.
+    /// - `offset`: The offset relative to the start of the section data.
+    pub fn resolve_section_offset(
+        &self,
+        subimage: usize,
+        section: usize,
+        offset: u64,
+    ) -> Option<ImageLocation> {
+        self.sections
+            .iter()
+            .find(|mapping| mapping.subimage == subimage && mapping.section == section)
+            .filter(|mapping| offset < mapping.range.len())
+            .map(|mapping| self.location(mapping, offset))
+    }
+
+    /// Resolves an offset relative to the start of the OTA image.
+    ///
+    /// # Returns:
+    /// - `Some(ImageLocation)` if the offset points into the data of a section.
+    /// - `None` otherwise (e.g. headers, hashes or padding).
+    pub fn resolve_file_offset(&self, offset: u64) -> Option<ImageLocation> {
+        self.sections
+            .iter()
+            .find(|mapping| {
+                mapping.file_offset <= offset && offset < mapping.file_offset + mapping.range.len()
+            })
+            .map(|mapping| self.location(mapping, offset - mapping.file_offset))
+    }
+
+    /// Resolves an absolute flash offset.
+    ///
+    /// # Returns:
+    /// - `Some(ImageLocation)` if the flash offset of the image is set and the offset points
+    ///   into the data of a section.
+    /// - `None` otherwise.
+    pub fn resolve_flash_offset(&self, offset: u64) -> Option<ImageLocation> {
+        self.flash_offset
+            .and_then(|base| offset.checked_sub(base))
+            .and_then(|offset| self.resolve_file_offset(offset))
+    }
+
+    fn location(&self, mapping: &SectionMapping, section_offset: u64) -> ImageLocation {
+        let file_offset = match &mapping.remap {
+            // remapped pages are contiguous in flash
+            Some(remap) => {
+                remap.physical_base + (mapping.range.start() + section_offset - remap.virtual_base)
+            }
+            None => mapping.file_offset + section_offset,
+        };
+        ImageLocation {
+            address: mapping.range.start() + section_offset,
+            subimage: mapping.subimage,
+            section: mapping.section,
+            section_offset,
+            file_offset,
+            flash_offset: self.flash_offset.map(|base| base + file_offset),
+        }
+    }
+}
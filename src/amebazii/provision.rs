@@ -0,0 +1,302 @@
+//! Secure boot provisioning bundles.
+//!
+//! Enabling secure boot requires eFuse values that match the keys used to sign the flash
+//! image. A [`ProvisionBundle`] is built from a [`KeyStore`] and a partition table
+//! configuration and contains:
+//!
+//! - all eFuse values to program (see [`EfuseValue`]),
+//! - the partition table, signed using the hash key of the key store,
+//! - a summary of all public values (see [`ProvisionSummary`]), which never contains secret
+//!   keys.
+//!
+//! **Tool-specific:** the eFuse values and their encoding (e.g. `secure_boot_pubkey_hash` being
+//! the SHA-256 digest of the raw Ed25519 public key) are defined by this tool and are not taken
+//! from vendor documentation. Check them against the eFuse layout of the target device before
+//! programming anything.
+//!
+//! The following key names are used in addition to the names defined in [`crate::keystore`]:
+//!
+//! | Name                 | Usage                                              | Required |
+//! |----------------------|----------------------------------------------------|----------|
+//! | `secure_boot_key`    | Ed25519 secure boot private key                    | yes      |
+//! | `secure_key`         | secure key (eFuse), e.g. the device X25519 key     | no       |
+//! | `super_secure_key`   | super-secure key (eFuse)                           | no       |
+
+use serde::Serialize;
+
+use crate::{
+    conf::PartitionTableCfg,
+    error::Error,
+    keystore::KeyStore,
+    types::{
+        enums::{KeyExportOp, PartitionType},
+        image::{pt::PartitionTableImage, set_default_segment_size, set_signature_from},
+        EncryptedOr, PartTab,
+    },
+    util::{ed25519_public_key, sha256, x25519_public_key},
+};
+
+/// Name of the Ed25519 secure boot private key.
+pub const SECURE_BOOT_KEY_NAME: &str = "secure_boot_key";
+
+/// Name of the secure key programmed into the eFuse.
+pub const SECURE_KEY_NAME: &str = "secure_key";
+
+/// Name of the super-secure key programmed into the eFuse.
+pub const SUPER_SECURE_KEY_NAME: &str = "super_secure_key";
+
+/// A single value to be programmed into the eFuse (tool-specific, see the [module
+/// documentation](self)).
+#[derive(Debug, Clone, Serialize)]
+pub struct EfuseValue {
+    /// The name of the value (e.g. `secure_boot_pubkey_hash`).
+    pub name: String,
+
+    /// The raw value.
+    #[serde(with = "hex")]
+    pub value: Vec<u8>,
+
+    /// Whether the value is secret and must not be part of any summary.
+    pub secret: bool,
+}
+
+impl EfuseValue {
+    fn new(name: &str, value: &[u8], secret: bool) -> Self {
+        EfuseValue {
+            name: name.to_string(),
+            value: value.to_vec(),
+            secret,
+        }
+    }
+}
+
+/// A public summary of an eFuse value. Secret values are replaced by their SHA-256 digest.
+#[derive(Debug, Clone, Serialize)]
+pub struct EfuseSummary {
+    /// The name of the value.
+    pub name: String,
+
+    /// The raw value (public values only).
+    pub value: Option<String>,
+
+    /// The SHA-256 digest of the value (secret values only).
+    pub digest: Option<String>,
+}
+
+/// A public summary of a partition record.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordSummary {
+    /// The type of the partition.
+    pub part_type: PartitionType,
+
+    /// The start address of the partition.
+    pub start_addr: u32,
+
+    /// The length of the partition.
+    pub length: u32,
+
+    /// The name of the key store or built-in key matching the hash key of the record.
+    pub hash_key_name: Option<String>,
+
+    /// The SHA-256 digest of the hash key of the record.
+    pub hash_key_digest: Option<String>,
+}
+
+/// A summary of all public values of a provisioning bundle.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProvisionSummary {
+    /// The Ed25519 public key of the secure boot key.
+    pub secure_boot_pubkey: String,
+
+    /// The SHA-256 digest of the raw secure boot public key (tool-specific eFuse value).
+    pub secure_boot_pubkey_hash: String,
+
+    /// The key export operation of the partition table.
+    pub key_exp_op: KeyExportOp,
+
+    /// The X25519 encryption public key stored in the key block of the partition table.
+    pub enc_pubkey: String,
+
+    /// The hash of the partition table.
+    pub partition_table_hash: String,
+
+    /// All partition records.
+    pub records: Vec<RecordSummary>,
+
+    /// All eFuse values (secret values are replaced by their digest).
+    pub efuse: Vec<EfuseSummary>,
+}
+
+/// eFuse values and the matching signed partition table.
+///
+/// # Example
+/// ```
+/// use amebazii::conf::{PartitionItemCfg, PartitionTableCfg};
+/// use amebazii::keystore::KeyStore;
+/// use amebazii::provision::{ProvisionBundle, SECURE_BOOT_KEY_NAME};
+/// use amebazii::types::PartitionType;
+///
+/// let mut keys = KeyStore::new();
+/// keys.add_key(SECURE_BOOT_KEY_NAME, &[0x11; 32]);
+///
+/// let mut config = PartitionTableCfg::default();
+/// config.items.push(PartitionItemCfg {
+///     start_addr: 0xC000,
+///     length: 0xF8000,
+///     part_type: PartitionType::Fw1,
+///     debug_skip: false,
+///     hash_key: hex::encode(keys.get_fw_hash_key()),
+/// });
+///
+/// let bundle = ProvisionBundle::build(&keys, config).unwrap();
+/// let summary = bundle.summary(&keys).unwrap();
+/// assert_eq!(summary.efuse[0].name, "secure_boot_pubkey_hash");
+/// ```
+#[derive(Debug)]
+pub struct ProvisionBundle {
+    efuse: Vec<EfuseValue>,
+    partition_table: PartitionTableImage,
+    secure_boot_pubkey: [u8; 32],
+}
+
+impl ProvisionBundle {
+    /// Builds a provisioning bundle from the given keys and partition table configuration.
+    ///
+    /// The partition table is signed using the hash key (see [`KeyStore::get_hash_key`]) of the
+    /// key store. Its key block stores the public key of the encryption private key (see
+    /// [`KeyStore::get_enc_priv_key`]).
+    ///
+    /// # Arguments:
+    /// - `keys`: The key store providing all keys.
+    /// - `config`: The partition table configuration.
+    ///
+    /// # Returns:
+    /// - `Ok(ProvisionBundle)` containing the eFuse values and the signed partition table.
+    /// - `Err(Error)` if the secure boot key is missing or invalid, or the partition table
+    ///   could not be built.
+    pub fn build(keys: &KeyStore, config: PartitionTableCfg) -> Result<Self, Error> {
+        let secure_boot_key = match keys.get_key(SECURE_BOOT_KEY_NAME) {
+            Some(key) => key,
+            None => {
+                return Err(Error::InvalidState(format!(
+                    "Key store does not contain a secure boot key ({})",
+                    SECURE_BOOT_KEY_NAME
+                )))
+            }
+        };
+
+        let pt: PartTab = config.try_into()?;
+        let key_exp_op = pt.key_exp_op;
+
+        let mut partition_table = PartitionTableImage::default();
+        partition_table.pt = EncryptedOr::Plain(pt);
+        set_default_segment_size(&mut partition_table);
+
+        // the hash covers the key block, so it must be set up first
+        partition_table
+            .keyblock
+            .get_enc_pubkey_mut()
+            .copy_from_slice(&x25519_public_key(keys.get_enc_priv_key())?);
+        set_signature_from(&mut partition_table, keys, PartitionType::PartTab)?;
+
+        let secure_boot_pubkey = ed25519_public_key(secure_boot_key)?;
+        let pubkey_hash = sha256(&secure_boot_pubkey)?;
+        let mut efuse = vec![
+            EfuseValue::new("secure_boot_pubkey_hash", &pubkey_hash, false),
+            EfuseValue::new("key_exp_op", &[key_exp_op as u8], false),
+        ];
+        for name in [SECURE_KEY_NAME, SUPER_SECURE_KEY_NAME] {
+            if let Some(key) = keys.get_key(name) {
+                if key.len() != 32 {
+                    return Err(Error::InvalidState(format!(
+                        "Key {:?} must be 32 bytes long",
+                        name
+                    )));
+                }
+                efuse.push(EfuseValue::new(name, key, true));
+            }
+        }
+
+        Ok(ProvisionBundle {
+            efuse,
+            partition_table,
+            secure_boot_pubkey,
+        })
+    }
+
+    /// Returns all eFuse values of this bundle.
+    pub fn get_efuse_values(&self) -> &[EfuseValue] {
+        &self.efuse
+    }
+
+    /// Returns the signed partition table.
+    pub fn get_partition_table(&self) -> &PartitionTableImage {
+        &self.partition_table
+    }
+
+    /// Returns the Ed25519 public key of the secure boot key.
+    pub fn get_secure_boot_pubkey(&self) -> &[u8; 32] {
+        &self.secure_boot_pubkey
+    }
+
+    /// Builds a summary of all public values of this bundle.
+    ///
+    /// # Arguments:
+    /// - `keys`: The key store used to name the hash keys of all partition records.
+    ///
+    /// # Returns:
+    /// - `Ok(ProvisionSummary)` containing all public values.
+    /// - `Err(Error)` if a digest could not be computed.
+    pub fn summary(&self, keys: &KeyStore) -> Result<ProvisionSummary, Error> {
+        let keyblock = &self.partition_table.keyblock;
+        let pt: &PartTab = self.partition_table.pt.as_ref();
+        let known_keys = keys.known_keys();
+
+        let mut records = Vec::new();
+        for record in pt.get_records() {
+            let hash_key = record.get_hash_key();
+            records.push(RecordSummary {
+                part_type: record.part_type,
+                start_addr: record.start_addr,
+                length: record.length,
+                hash_key_name: hash_key.and_then(|hash_key| {
+                    known_keys
+                        .iter()
+                        .find(|key| key.get_key() == hash_key)
+                        .map(|key| key.get_name().to_string())
+                }),
+                hash_key_digest: match hash_key {
+                    Some(hash_key) => Some(hex::encode(sha256(hash_key)?)),
+                    None => None,
+                },
+            });
+        }
+
+        let mut efuse = Vec::new();
+        for value in &self.efuse {
+            efuse.push(if value.secret {
+                EfuseSummary {
+                    name: value.name.clone(),
+                    value: None,
+                    digest: Some(hex::encode(sha256(&value.value)?)),
+                }
+            } else {
+                EfuseSummary {
+                    name: value.name.clone(),
+                    value: Some(hex::encode(&value.value)),
+                    digest: None,
+                }
+            });
+        }
+
+        Ok(ProvisionSummary {
+            secure_boot_pubkey: hex::encode(self.secure_boot_pubkey),
+            secure_boot_pubkey_hash: hex::encode(sha256(&self.secure_boot_pubkey)?),
+            key_exp_op: pt.key_exp_op,
+            enc_pubkey: hex::encode(keyblock.get_enc_pubkey()),
+            partition_table_hash: hex::encode(self.partition_table.get_hash()),
+            records,
+            efuse,
+        })
+    }
+}
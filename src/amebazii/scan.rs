@@ -0,0 +1,556 @@
+//! Scanner for embedded secrets and certificates.
+//!
+//! Firmware images often contain certificates, private keys or other key material. The
+//! [`SecretScanner`] searches raw data, OTA sections, NVDM items and flash partitions for:
+//!
+//! - PEM blocks (`-----BEGIN <label>-----`),
+//! - ASN.1 DER encoded X.509 certificates, private keys and public keys,
+//! - high-entropy 16 or 32 byte key candidates, which are surrounded by low-entropy data.
+//!
+//! Key candidates are a heuristic and may contain false positives (e.g. compressed data
+//! or constant tables).
+
+use serde::Serialize;
+use std::{
+    fmt,
+    io::{self, Read},
+};
+
+use crate::{
+    error::Error,
+    types::{enums::PartitionType, EncryptedOr, Flash, OTAImage, Partition, NVDM},
+};
+
+/// The type of a secret found by the scanner.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+pub enum SecretKind {
+    /// A PEM block, such as a certificate or private key.
+    PemBlock,
+
+    /// An ASN.1 DER encoded X.509 certificate.
+    DerCertificate,
+
+    /// An ASN.1 DER encoded private key (PKCS#1, PKCS#8 or SEC1).
+    DerPrivateKey,
+
+    /// An ASN.1 DER encoded public key (SubjectPublicKeyInfo).
+    DerPublicKey,
+
+    /// High-entropy data that may be a symmetric key.
+    KeyCandidate,
+}
+
+impl fmt::Display for SecretKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretKind::PemBlock => write!(f, "PEM"),
+            SecretKind::DerCertificate => write!(f, "DER certificate"),
+            SecretKind::DerPrivateKey => write!(f, "DER private key"),
+            SecretKind::DerPublicKey => write!(f, "DER public key"),
+            SecretKind::KeyCandidate => write!(f, "Key candidate"),
+        }
+    }
+}
+
+/// A single secret found by the scanner.
+#[derive(Debug, Clone, Serialize)]
+pub struct SecretFinding {
+    /// The type of the secret.
+    pub kind: SecretKind,
+
+    /// A short description (e.g. the PEM label or the entropy of a key candidate).
+    pub label: String,
+
+    /// Where the secret was found (e.g. `subimage 0/section 1` or `sys/wifi_ssid`).
+    pub location: String,
+
+    /// The offset of the secret within the scanned data.
+    pub offset: usize,
+
+    /// The (virtual) address of the secret, if the scanned data has an address.
+    pub address: Option<u32>,
+
+    /// The length of the secret in bytes.
+    pub length: usize,
+
+    /// The raw data of the secret.
+    #[serde(with = "hex")]
+    pub data: Vec<u8>,
+}
+
+/// Searches data for embedded secrets and certificates.
+///
+/// # Example
+/// ```
+/// use amebazii::scan::{SecretKind, SecretScanner};
+///
+/// let mut data = vec![0u8; 64];
+/// data.extend_from_slice(b"-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----\n");
+///
+/// let findings = SecretScanner::default().scan(&data, "data", Some(0x1000));
+/// assert_eq!(findings[0].kind, SecretKind::PemBlock);
+/// assert_eq!(findings[0].label, "CERTIFICATE");
+/// assert_eq!(findings[0].address, Some(0x1040));
+///
+/// // key candidates are surrounded by low-entropy data, lookup tables are ignored
+/// let mut data = vec![0u8; 64];
+/// data.extend_from_slice(amebazii::keys::HASH_KEY);
+/// data.extend_from_slice(&[0u8; 64]);
+/// data.extend((0..32u8).map(|i| i * 7 + (i % 3)));
+/// data.extend_from_slice(&[0u8; 64]);
+///
+/// let findings = SecretScanner::default().scan(&data, "data", None);
+/// assert_eq!(findings.len(), 1);
+/// assert_eq!(findings[0].kind, SecretKind::KeyCandidate);
+/// assert_eq!((findings[0].offset, findings[0].length), (64, 32));
+/// ```
+#[derive(Debug, Clone)]
+pub struct SecretScanner {
+    key_candidates: bool,
+    min_entropy: f64,
+}
+
+impl Default for SecretScanner {
+    /// Creates a scanner that reports all kinds of secrets, including key candidates.
+    fn default() -> Self {
+        SecretScanner {
+            key_candidates: true,
+            min_entropy: 0.95,
+        }
+    }
+}
+
+/// DER prefixes (after the outer SEQUENCE header) and the kind of structure they identify.
+const DER_PREFIXES: &[(&[u8], SecretKind, &str)] = &[
+    // tbsCertificate SEQUENCE with version [0]
+    (
+        &[0x30, 0x82],
+        SecretKind::DerCertificate,
+        "X.509 certificate",
+    ),
+    (
+        &[0x30, 0x81],
+        SecretKind::DerCertificate,
+        "X.509 certificate",
+    ),
+    // version 0, modulus
+    (
+        &[0x02, 0x01, 0x00, 0x02, 0x82],
+        SecretKind::DerPrivateKey,
+        "RSA private key (PKCS#1)",
+    ),
+    (
+        &[0x02, 0x01, 0x00, 0x02, 0x81],
+        SecretKind::DerPrivateKey,
+        "RSA private key (PKCS#1)",
+    ),
+    // version 0, AlgorithmIdentifier
+    (
+        &[0x02, 0x01, 0x00, 0x30],
+        SecretKind::DerPrivateKey,
+        "private key (PKCS#8)",
+    ),
+    // version 1, privateKey OCTET STRING
+    (
+        &[0x02, 0x01, 0x01, 0x04],
+        SecretKind::DerPrivateKey,
+        "EC private key (SEC1)",
+    ),
+    // AlgorithmIdentifier with rsaEncryption or id-ecPublicKey
+    (
+        &[
+            0x30, 0x0d, 0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01,
+        ],
+        SecretKind::DerPublicKey,
+        "RSA public key",
+    ),
+    (
+        &[
+            0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01,
+        ],
+        SecretKind::DerPublicKey,
+        "EC public key",
+    ),
+];
+
+impl SecretScanner {
+    /// Enables or disables the detection of high-entropy key candidates.
+    pub fn set_key_candidates(&mut self, enabled: bool) {
+        self.key_candidates = enabled;
+    }
+
+    /// Sets the minimum entropy of a key candidate relative to the maximum entropy of its
+    /// size (`0.0` to `1.0`, defaults to `0.95`).
+    pub fn set_min_entropy(&mut self, min_entropy: f64) {
+        self.min_entropy = min_entropy;
+    }
+
+    /// Scans raw data for secrets.
+    ///
+    /// # Arguments:
+    /// - `data`: The data to scan.
+    /// - `location`: The name of the data, used in all findings.
+    /// - `base_address`: The address of the first byte of `data` (if any).
+    ///
+    /// # Returns:
+    /// - All findings ordered by their offset.
+    pub fn scan(
+        &self,
+        data: &[u8],
+        location: &str,
+        base_address: Option<u32>,
+    ) -> Vec<SecretFinding> {
+        let mut findings = Vec::new();
+        let mut covered = Vec::new();
+        let mut add = |kind: SecretKind, label: &str, offset: usize, length: usize| {
+            findings.push(SecretFinding {
+                kind,
+                label: label.to_string(),
+                location: location.to_string(),
+                offset,
+                address: base_address.map(|address| address.wrapping_add(offset as u32)),
+                length,
+                data: data[offset..offset + length].to_vec(),
+            });
+        };
+
+        for (offset, length, label) in find_pem_blocks(data) {
+            add(SecretKind::PemBlock, &label, offset, length);
+            covered.push(offset..offset + length);
+        }
+
+        for (offset, length, kind, label) in find_der_structures(data) {
+            if !covered.iter().any(|range| range.contains(&offset)) {
+                add(kind, label, offset, length);
+                covered.push(offset..offset + length);
+            }
+        }
+
+        if self.key_candidates {
+            for (offset, length, entropy) in self.find_key_candidates(data) {
+                if !covered
+                    .iter()
+                    .any(|range| range.start < offset + length && offset < range.end)
+                {
+                    add(
+                        SecretKind::KeyCandidate,
+                        &format!("entropy {:.2}", entropy),
+                        offset,
+                        length,
+                    );
+                    covered.push(offset..offset + length);
+                }
+            }
+        }
+
+        findings.sort_by_key(|finding| finding.offset);
+        findings
+    }
+
+    /// Scans all sections of an OTA image.
+    ///
+    /// Encrypted subimages are skipped and SCE-encrypted XIP sections are scanned as they are
+    /// stored. The address of each finding is based on the load address of its section.
+    ///
+    /// # Arguments:
+    /// - `image`: The OTA image to scan.
+    /// - `location`: A prefix for the location of all findings (e.g. `Fw1`), may be empty.
+    ///
+    /// # Returns:
+    /// - All findings ordered by subimage and section.
+    pub fn scan_ota(&self, image: &OTAImage, location: &str) -> Vec<SecretFinding> {
+        let mut findings = Vec::new();
+        for (idx, subimage) in image.get_subimages().iter().enumerate() {
+            if subimage.is_encrypted() {
+                continue;
+            }
+
+            for (sect_idx, section) in subimage.get_sections().iter().enumerate() {
+                let name = format!("subimage {}/section {}", idx, sect_idx);
+                findings.extend(self.scan(
+                    section.get_data(),
+                    &join_location(location, &name),
+                    Some(section.entry_header.load_address),
+                ));
+            }
+        }
+        findings
+    }
+
+    /// Scans the values of all NVDM data items (including deleted ones).
+    ///
+    /// Each finding is located by `<group>/<name>` of its data item and has no address.
+    ///
+    /// # Arguments:
+    /// - `nvdm`: The parsed NVDM storage.
+    ///
+    /// # Returns:
+    /// - All findings ordered by data item.
+    pub fn scan_nvdm(&self, nvdm: &NVDM) -> Vec<SecretFinding> {
+        nvdm.get_items()
+            .iter()
+            .flat_map(|item| {
+                self.scan(
+                    item.data(),
+                    &format!("{}/{}", item.group(), item.name()),
+                    None,
+                )
+            })
+            .collect()
+    }
+
+    /// Scans a flash image.
+    ///
+    /// The sections of both firmware images and the text of the boot image are scanned using
+    /// their load addresses. The system data and all other partitions (except the partition
+    /// table) are scanned as raw data using their flash offsets.
+    ///
+    /// # Arguments:
+    /// - `flash`: The parsed flash image.
+    /// - `reader`: The stream the flash image was read from.
+    ///
+    /// # Returns:
+    /// - `Ok(Vec<SecretFinding>)` containing all findings.
+    /// - `Err(Error)` if there is no (decrypted) partition table or the data could not be read.
+    pub fn scan_flash<R>(&self, flash: &Flash, reader: &mut R) -> Result<Vec<SecretFinding>, Error>
+    where
+        R: io::Read + io::Seek,
+    {
+        let pt = match flash.get_partition(PartitionType::PartTab) {
+            Some(Partition::PartitionTable(pt_image)) => match &pt_image.pt {
+                EncryptedOr::Plain(pt) => pt,
+                EncryptedOr::Encrypted(_) => {
+                    return Err(Error::InvalidState(
+                        "Partition table is encrypted".to_string(),
+                    ))
+                }
+            },
+            _ => {
+                return Err(Error::InvalidState(
+                    "Flash has no partition table".to_string(),
+                ))
+            }
+        };
+
+        // the system data is usually not part of the partition table
+        let mut findings = Vec::new();
+        if pt.get_record(PartitionType::Sys).is_none() {
+            let data = read_data(reader, 0x1000, 0x1000)?;
+            findings.extend(self.scan(&data, "Sys", Some(0x1000)));
+        }
+        for record in pt.get_records() {
+            let location = format!("{:?}", record.part_type);
+            match flash.get_partition(record.part_type) {
+                Some(Partition::Fw1(image)) | Some(Partition::Fw2(image)) => {
+                    findings.extend(self.scan_ota(image, &location));
+                }
+                Some(Partition::Bootloader(boot_image)) if !boot_image.is_encrypted() => {
+                    findings.extend(self.scan(
+                        boot_image.get_text(),
+                        &location,
+                        Some(boot_image.entry.load_address),
+                    ));
+                }
+                _ => {
+                    let data = read_data(reader, record.start_addr, record.length)?;
+                    findings.extend(self.scan(&data, &location, Some(record.start_addr)));
+                }
+            }
+        }
+        Ok(findings)
+    }
+
+    /// Finds 16 and 32 byte windows (4-byte aligned) with a high entropy, whose neighbouring
+    /// 16 bytes have a low entropy. Windows that look like text, counting sequences or
+    /// (nearly) monotonic lookup tables are ignored.
+    fn find_key_candidates(&self, data: &[u8]) -> Vec<(usize, usize, f64)> {
+        let flank_threshold = 16f64.log2() * 0.6;
+        let is_low_entropy = |range: std::ops::Range<usize>| {
+            range.end > data.len() || shannon_entropy(&data[range]) < flank_threshold
+        };
+
+        let mut candidates: Vec<(usize, usize, f64)> = Vec::new();
+        for size in [32, 16] {
+            let threshold = (size as f64).log2() * self.min_entropy;
+            let mut offset = 0;
+            while offset + size <= data.len() {
+                let window = &data[offset..offset + size];
+                let entropy = shannon_entropy(window);
+                if entropy >= threshold
+                    && !looks_structured(window)
+                    && (offset < 16 || is_low_entropy(offset - 16..offset))
+                    && is_low_entropy(offset + size..offset + size + 16)
+                    && !candidates
+                        .iter()
+                        .any(|(start, length, _)| *start < offset + size && offset < start + length)
+                {
+                    candidates.push((offset, size, entropy));
+                    offset += size;
+                } else {
+                    offset += 4;
+                }
+            }
+        }
+        candidates.sort_by_key(|(offset, _, _)| *offset);
+        candidates
+    }
+}
+
+/// Returns `true` if the given data is mostly printable text, contains counting sequences or
+/// is (nearly) monotonic, such as lookup tables.
+fn looks_structured(data: &[u8]) -> bool {
+    let printable = data
+        .iter()
+        .filter(|byte| byte.is_ascii_graphic() || **byte == b' ')
+        .count();
+    let sequential = data
+        .windows(2)
+        .filter(|pair| pair[1] == pair[0].wrapping_add(1))
+        .count();
+    // random data ascends (or descends) for about half of all byte pairs
+    let pairs = data.len().saturating_sub(1);
+    let ascending = data.windows(2).filter(|pair| pair[1] > pair[0]).count();
+    let descending = data.windows(2).filter(|pair| pair[1] < pair[0]).count();
+    printable * 4 >= data.len() * 3
+        || sequential * 4 >= data.len()
+        || ascending * 4 >= pairs * 3
+        || descending * 4 >= pairs * 3
+}
+
+fn join_location(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", prefix, name)
+    }
+}
+
+fn read_data<R>(reader: &mut R, offset: u32, length: u32) -> Result<Vec<u8>, Error>
+where
+    R: io::Read + io::Seek,
+{
+    let mut buffer = Vec::with_capacity(length as usize);
+    reader.seek(io::SeekFrom::Start(offset as u64))?;
+    reader
+        .by_ref()
+        .take(length as u64)
+        .read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Returns the Shannon entropy of the given data in bits per byte.
+fn shannon_entropy(data: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for byte in data {
+        counts[*byte as usize] += 1;
+    }
+
+    let len = data.len() as f64;
+    counts
+        .iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let p = *count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Returns the offset, length and label of all PEM blocks. Blocks without an end marker
+/// extend to the end of the printable data.
+fn find_pem_blocks(data: &[u8]) -> Vec<(usize, usize, String)> {
+    const BEGIN: &[u8] = b"-----BEGIN ";
+    let mut blocks = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = find(&data[offset..], BEGIN).map(|pos| offset + pos) {
+        let label_start = start + BEGIN.len();
+        let label_end = match find(&data[label_start..], b"-----") {
+            Some(pos) if pos <= 64 => label_start + pos,
+            _ => {
+                offset = label_start;
+                continue;
+            }
+        };
+
+        let label = String::from_utf8_lossy(&data[label_start..label_end]).to_string();
+        let end_marker = format!("-----END {}-----", label);
+        let end = match find(&data[label_end..], end_marker.as_bytes()) {
+            Some(pos) => label_end + pos + end_marker.len(),
+            None => {
+                label_end
+                    + data[label_end..]
+                        .iter()
+                        .position(|byte| !byte.is_ascii_graphic() && !byte.is_ascii_whitespace())
+                        .unwrap_or(data.len() - label_end)
+            }
+        };
+        blocks.push((start, end - start, label));
+        offset = end;
+    }
+    blocks
+}
+
+/// Returns the offset, length, kind and label of all DER structures starting with a
+/// SEQUENCE, whose content matches one of [`DER_PREFIXES`].
+fn find_der_structures(data: &[u8]) -> Vec<(usize, usize, SecretKind, &'static str)> {
+    let mut structures = Vec::new();
+    let mut offset = 0;
+    while offset + 4 < data.len() {
+        if data[offset] != 0x30 {
+            offset += 1;
+            continue;
+        }
+
+        let (header_len, content_len) = match data[offset + 1] {
+            length @ 0x10..=0x7f => (2, length as usize),
+            0x81 => (3, data[offset + 2] as usize),
+            0x82 => (
+                4,
+                ((data[offset + 2] as usize) << 8) | data[offset + 3] as usize,
+            ),
+            _ => {
+                offset += 1;
+                continue;
+            }
+        };
+
+        let content_start = offset + header_len;
+        let end = content_start + content_len;
+        let content = &data[content_start..end.min(data.len())];
+        let matched = DER_PREFIXES
+            .iter()
+            .find(|(prefix, _, _)| content.starts_with(prefix));
+
+        match matched {
+            // the tbsCertificate must be followed by its version [0]
+            Some((_, SecretKind::DerCertificate, _)) if !is_certificate_content(content) => {
+                offset += 1
+            }
+            Some((_, kind, label)) if end <= data.len() && content_len >= 16 => {
+                structures.push((offset, end - offset, *kind, *label));
+                offset = end;
+            }
+            _ => offset += 1,
+        }
+    }
+    structures
+}
+
+/// Returns `true` if the content of a SEQUENCE starts with a tbsCertificate (including
+/// its version).
+fn is_certificate_content(content: &[u8]) -> bool {
+    let header_len = match content.get(1) {
+        Some(0x81) => 3,
+        Some(0x82) => 4,
+        _ => return false,
+    };
+    content
+        .get(header_len..header_len + 4)
+        .is_some_and(|version| version == [0xa0, 0x03, 0x02, 0x01])
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
@@ -0,0 +1,377 @@
+//! Signers used to compute the hashes (signatures) of images.
+//!
+//! By default, all hashes are computed in-process using a raw key ([`KeySigner`]). To keep
+//! private keys on a dedicated signing host, the exact byte buffer to be hashed can also be
+//! passed to an external command ([`CommandSigner`]) or a local socket service
+//! ([`SocketSigner`]), which return the signature.
+//!
+//! ## External Command Protocol
+//!
+//! The command receives the data on `stdin` and must write the signature to `stdout`, either
+//! as raw bytes or as hex string. The hash algorithm (`sha256` or `md5`) and the name of the
+//! requested key are passed in the environment variables `AMEBAZII_HASH_ALGO` and
+//! `AMEBAZII_KEY_ID`. A non-zero exit status aborts the operation.
+//!
+//! ## Socket Protocol
+//!
+//! Each request opens a new connection (TCP `host:port` or Unix domain socket path) and sends a
+//! single JSON line. The service responds with a single JSON line containing either the
+//! signature or an error message:
+//!
+//! ```text
+//! > {"algo":"sha256","key":"fw_hash_key","data":"<hex>"}
+//! < {"signature":"<hex>"}
+//! < {"error":"unknown key"}
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{self, BufRead, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    process::{Command, Stdio},
+};
+
+use crate::{error::Error, types::HashAlgo};
+
+/// Environment variable storing the hash algorithm passed to external commands.
+pub const ENV_HASH_ALGO: &str = "AMEBAZII_HASH_ALGO";
+
+/// Environment variable storing the key name passed to external commands.
+pub const ENV_KEY_ID: &str = "AMEBAZII_KEY_ID";
+
+/// Computes the hash of the data covered by an image signature.
+pub trait Signer {
+    /// Computes the hash (usually a HMAC) of the given data.
+    ///
+    /// # Arguments:
+    /// - `algo`: The hash algorithm to use.
+    /// - `data`: The exact data to be hashed.
+    ///
+    /// # Returns:
+    /// - `Ok(Vec<u8>)` containing the signature.
+    /// - `Err(Error)` if the signature could not be computed.
+    fn sign(&self, algo: HashAlgo, data: &[u8]) -> Result<Vec<u8>, Error>;
+}
+
+/// Computes all hashes in-process using a raw key (or no key at all).
+///
+/// # Example
+/// ```
+/// use amebazii::keys::HASH_KEY;
+/// use amebazii::signer::{KeySigner, Signer};
+/// use amebazii::types::HashAlgo;
+///
+/// let signer = KeySigner::new(Some(HASH_KEY));
+/// let signature = signer.sign(HashAlgo::Sha256, b"data").unwrap();
+/// assert_eq!(signature, HashAlgo::Sha256.compute_hash(b"data", Some(HASH_KEY)).unwrap());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct KeySigner {
+    key: Option<Vec<u8>>,
+}
+
+impl KeySigner {
+    /// Creates a new signer using the given key.
+    ///
+    /// # Arguments:
+    /// - `key`: The HMAC key, or `None` to compute plain hashes.
+    pub fn new(key: Option<&[u8]>) -> Self {
+        KeySigner {
+            key: key.map(<[u8]>::to_vec),
+        }
+    }
+}
+
+impl Signer for KeySigner {
+    fn sign(&self, algo: HashAlgo, data: &[u8]) -> Result<Vec<u8>, Error> {
+        algo.compute_hash(data, self.key.as_deref())
+    }
+}
+
+/// Delegates all hashes to an external command.
+///
+/// # Example
+/// ```
+/// use amebazii::signer::{CommandSigner, KeySigner, Signer};
+/// use amebazii::types::HashAlgo;
+///
+/// // a stand-in for a signing host computing plain SHA-256 hashes
+/// let signer = CommandSigner::with_args(
+///     "sh",
+///     &["-c", "test \"$AMEBAZII_KEY_ID\" = fw_hash_key && sha256sum | cut -c1-64"],
+///     "fw_hash_key",
+/// );
+/// let signature = signer.sign(HashAlgo::Sha256, b"data").unwrap();
+/// assert_eq!(signature, KeySigner::new(None).sign(HashAlgo::Sha256, b"data").unwrap());
+///
+/// // the exit status and error output are part of the error, even if the command
+/// // exits before reading all data
+/// let signer = CommandSigner::with_args("sh", &["-c", "echo unknown key >&2; exit 3"], "x");
+/// let error = signer.sign(HashAlgo::Sha256, &[0u8; 0x100000]).unwrap_err();
+/// assert!(error.to_string().contains("exit status: 3"));
+/// assert!(error.to_string().contains("unknown key"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct CommandSigner {
+    program: String,
+    args: Vec<String>,
+    key_id: String,
+}
+
+impl CommandSigner {
+    /// Creates a new signer calling the given command.
+    ///
+    /// # Arguments:
+    /// - `command`: The program and its arguments, separated by whitespace.
+    /// - `key_id`: The name of the key the command should use (e.g. `fw_hash_key`).
+    ///
+    /// # Returns:
+    /// - `Ok(CommandSigner)` if the command is not empty.
+    /// - `Err(Error::InvalidState)` otherwise.
+    pub fn new(command: &str, key_id: &str) -> Result<Self, Error> {
+        let mut parts = command.split_whitespace().map(str::to_string);
+        match parts.next() {
+            Some(program) => Ok(CommandSigner {
+                program,
+                args: parts.collect(),
+                key_id: key_id.to_string(),
+            }),
+            None => Err(Error::InvalidState(
+                "Signer command must not be empty".to_string(),
+            )),
+        }
+    }
+
+    /// Creates a new signer calling the given program with the given arguments, which may
+    /// contain whitespace.
+    ///
+    /// # Arguments:
+    /// - `program`: The program to call.
+    /// - `args`: The arguments of the program.
+    /// - `key_id`: The name of the key the command should use (e.g. `fw_hash_key`).
+    pub fn with_args(program: &str, args: &[&str], key_id: &str) -> Self {
+        CommandSigner {
+            program: program.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            key_id: key_id.to_string(),
+        }
+    }
+}
+
+impl Signer for CommandSigner {
+    fn sign(&self, algo: HashAlgo, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .env(ENV_HASH_ALGO, algo_name(algo)?)
+            .env(ENV_KEY_ID, &self.key_id)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        // The data is written in a separate thread while the output is collected, so that
+        // neither side blocks. stdin is closed after the data has been written.
+        let stdin = child.stdin.take();
+        let (written, output) = std::thread::scope(|scope| {
+            let writer = scope.spawn(move || match stdin {
+                Some(mut stdin) => stdin.write_all(data),
+                None => Ok(()),
+            });
+            let output = child.wait_with_output();
+            (writer.join(), output)
+        });
+        let output = output?;
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() {
+            return Err(Error::InvalidState(format!(
+                "Signer command {:?} failed ({}): {}",
+                self.program,
+                output.status,
+                stderr.trim()
+            )));
+        }
+        match written {
+            Ok(Ok(())) => parse_signature(algo, &output.stdout),
+            Ok(Err(e)) => Err(Error::InvalidState(format!(
+                "Signer command {:?} did not read all data ({}, {}): {}",
+                self.program,
+                e,
+                output.status,
+                stderr.trim()
+            ))),
+            Err(_) => Err(Error::InvalidState(format!(
+                "Signer command {:?}: failed to write data",
+                self.program
+            ))),
+        }
+    }
+}
+
+/// Delegates all hashes to a local socket service (TCP or Unix domain socket).
+///
+/// # Example
+/// ```
+/// use amebazii::signer::{KeySigner, Signer, SocketSigner};
+/// use amebazii::types::HashAlgo;
+/// use std::io::{BufRead, BufReader, Write};
+/// use std::net::TcpListener;
+///
+/// // a stand-in for a signing service computing plain SHA-256 hashes
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let port = listener.local_addr().unwrap().port();
+/// let service = std::thread::spawn(move || {
+///     for stream in listener.incoming().take(2) {
+///         let mut stream = stream.unwrap();
+///         let mut line = String::new();
+///         BufReader::new(&stream).read_line(&mut line).unwrap();
+///
+///         let request: serde_json::Value = serde_json::from_str(&line).unwrap();
+///         let response = if request["key"] == "fw_hash_key" {
+///             let data = hex::decode(request["data"].as_str().unwrap()).unwrap();
+///             let signature = KeySigner::new(None).sign(HashAlgo::Sha256, &data).unwrap();
+///             serde_json::json!({ "signature": hex::encode(signature) })
+///         } else {
+///             serde_json::json!({ "error": "unknown key" })
+///         };
+///         writeln!(stream, "{}", response).unwrap();
+///     }
+/// });
+///
+/// let address = format!("localhost:{}", port);
+/// let signer = SocketSigner::new(&address, "fw_hash_key");
+/// let signature = signer.sign(HashAlgo::Sha256, b"data").unwrap();
+/// assert_eq!(signature, KeySigner::new(None).sign(HashAlgo::Sha256, b"data").unwrap());
+///
+/// let signer = SocketSigner::new(&address, "hash_key");
+/// let error = signer.sign(HashAlgo::Sha256, b"data").unwrap_err();
+/// assert!(error.to_string().contains("unknown key"));
+/// service.join().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct SocketSigner {
+    address: String,
+    key_id: String,
+}
+
+#[derive(Serialize)]
+struct SignRequest<'a> {
+    algo: &'a str,
+    key: &'a str,
+    #[serde(with = "hex")]
+    data: &'a [u8],
+}
+
+#[derive(Deserialize)]
+struct SignResponse {
+    signature: Option<String>,
+    error: Option<String>,
+}
+
+impl SocketSigner {
+    /// Creates a new signer connecting to the given address.
+    ///
+    /// # Arguments:
+    /// - `address`: A TCP address (`host:port`) or the path of a Unix domain socket.
+    /// - `key_id`: The name of the key the service should use (e.g. `fw_hash_key`).
+    pub fn new(address: &str, key_id: &str) -> Self {
+        SocketSigner {
+            address: address.to_string(),
+            key_id: key_id.to_string(),
+        }
+    }
+
+    fn request<S: io::Read + io::Write>(
+        &self,
+        mut stream: S,
+        request: &[u8],
+    ) -> Result<String, Error> {
+        stream.write_all(request)?;
+        stream.flush()?;
+
+        let mut line = String::new();
+        io::BufReader::new(stream).read_line(&mut line)?;
+        Ok(line)
+    }
+
+    #[cfg(unix)]
+    fn request_unix(&self, request: &[u8]) -> Result<String, Error> {
+        self.request(
+            std::os::unix::net::UnixStream::connect(&self.address)?,
+            request,
+        )
+    }
+
+    #[cfg(not(unix))]
+    fn request_unix(&self, _request: &[u8]) -> Result<String, Error> {
+        Err(Error::NotImplemented(
+            "Unix domain sockets are not supported on this platform".to_string(),
+        ))
+    }
+}
+
+impl Signer for SocketSigner {
+    fn sign(&self, algo: HashAlgo, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut request = serde_json::to_vec(&SignRequest {
+            algo: algo_name(algo)?,
+            key: &self.key_id,
+            data,
+        })?;
+        request.push(b'\n');
+
+        // host names are resolved first, anything else is treated as socket path
+        let line = match self.address.to_socket_addrs() {
+            Ok(addrs) => {
+                let addrs: Vec<SocketAddr> = addrs.collect();
+                self.request(TcpStream::connect(&addrs[..])?, &request)?
+            }
+            Err(_) => self.request_unix(&request)?,
+        };
+
+        let response: SignResponse = serde_json::from_str(&line)?;
+        match (response.signature, response.error) {
+            (Some(signature), None) => parse_signature(algo, signature.as_bytes()),
+            (_, Some(error)) => Err(Error::InvalidState(format!(
+                "Signer service at {} failed: {}",
+                self.address, error
+            ))),
+            _ => Err(Error::InvalidState(format!(
+                "Signer service at {} returned no signature",
+                self.address
+            ))),
+        }
+    }
+}
+
+/// Returns the name of the given hash algorithm used by external signers.
+fn algo_name(algo: HashAlgo) -> Result<&'static str, Error> {
+    match algo {
+        HashAlgo::Sha256 => Ok("sha256"),
+        HashAlgo::Md5 => Ok("md5"),
+        HashAlgo::Other => Err(Error::UnsupportedHashAlgo(algo as u8)),
+    }
+}
+
+/// Parses the signature returned by an external signer (raw bytes or hex string).
+fn parse_signature(algo: HashAlgo, output: &[u8]) -> Result<Vec<u8>, Error> {
+    let size = match algo {
+        HashAlgo::Sha256 => 32,
+        _ => 16,
+    };
+
+    let text = output.trim_ascii();
+    if text.len() == size * 2 {
+        if let Ok(signature) = hex::decode(text) {
+            return Ok(signature);
+        }
+    }
+
+    if output.len() == size {
+        return Ok(output.to_vec());
+    }
+    Err(Error::InvalidState(format!(
+        "External signer returned {} bytes, expected a {}-byte signature",
+        output.len(),
+        size
+    )))
+}
@@ -0,0 +1,559 @@
+//! Symbolizer for AmebaZ2 crash logs.
+//!
+//! When the CPU hard-faults, the fault handler prints the stacked and current registers
+//! (`PC = 0x9b0083bc`), followed by a stack dump and (depending on the SDK) a backtrace.
+//! The [`Symbolizer`] extracts all addresses from such a console log and maps them to:
+//!
+//! - their memory region (see [`crate::map::REGIONS`]),
+//! - the sub-image and section of an OTA image storing the address
+//!   (see [`crate::map::AddressTranslator`]),
+//! - the nearest symbol of an ELF file or an SDK ROM symbol file (see [`SymbolTable`]).
+//!
+//! # Example
+//! ```
+//! use amebazii::symbolize::{Symbolizer, SymbolTable};
+//!
+//! let symbols =
+//!     SymbolTable::from_ld_script("hal_uart_init = 0x00008a11;\nhal_uart_deinit = 0x00008b25;")
+//!         .unwrap();
+//! let mut symbolizer = Symbolizer::new();
+//! symbolizer.add_symbols(symbols);
+//!
+//! let addresses = symbolizer.symbolize_log("LR  = 0x00008a3b\nPC  = 0x9b0083bc\n");
+//! assert_eq!(addresses[0].label.as_deref(), Some("LR"));
+//! assert_eq!(addresses[0].symbol.as_deref(), Some("hal_uart_init+0x2a"));
+//! assert_eq!(addresses[1].region, Some("XIP_FLASH_C"));
+//! ```
+
+use object::{Object, ObjectSection, ObjectSymbol};
+use serde::Serialize;
+use std::fmt;
+
+use crate::{
+    error::Error,
+    map::{region_name, AddressTranslator, ImageLocation},
+    types::OTAImage,
+};
+
+/// The type of a symbol.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+pub enum SymbolKind {
+    /// A function, the Thumb bit of its address is cleared.
+    Function,
+
+    /// A data object.
+    Object,
+
+    /// Any other named address, such as a section label.
+    Label,
+}
+
+impl fmt::Display for SymbolKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SymbolKind::Function => write!(f, "FUNC"),
+            SymbolKind::Object => write!(f, "OBJECT"),
+            SymbolKind::Label => write!(f, "LABEL"),
+        }
+    }
+}
+
+/// A named address.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Symbol {
+    /// The name of the symbol.
+    pub name: String,
+
+    /// The address of the symbol.
+    pub address: u64,
+
+    /// The size of the symbol in bytes, `0` if unknown.
+    pub size: u64,
+
+    /// The type of the symbol.
+    pub kind: SymbolKind,
+}
+
+/// A table of symbols, sorted by their address.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    /// Creates a new, empty symbol table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads all named function, object and label symbols of an ELF file.
+    ///
+    /// Undefined symbols and ARM mapping symbols (`$t`, `$d`, ...) are skipped. Section
+    /// symbols without a size (e.g. the labels of a relinked image) cover their section.
+    ///
+    /// # Arguments:
+    /// - `elf_data`: The raw data of the ELF file.
+    ///
+    /// # Returns:
+    /// - `Ok(SymbolTable)` containing all symbols.
+    /// - `Err(Error)` if the ELF file could not be parsed.
+    pub fn from_elf(elf_data: &[u8]) -> Result<Self, Error> {
+        let file = object::File::parse(elf_data)
+            .map_err(|e| Error::InvalidState(format!("Failed to parse ELF file: {}", e)))?;
+
+        let mut table = Self::new();
+        for symbol in file.symbols() {
+            let name = match symbol.name() {
+                Ok(name) if !name.is_empty() && !name.starts_with('$') => name,
+                _ => continue,
+            };
+            if symbol.is_undefined() {
+                continue;
+            }
+
+            let kind = match symbol.kind() {
+                object::SymbolKind::Text => SymbolKind::Function,
+                object::SymbolKind::Data => SymbolKind::Object,
+                object::SymbolKind::Section
+                | object::SymbolKind::Label
+                | object::SymbolKind::Unknown => SymbolKind::Label,
+                _ => continue,
+            };
+            // section labels (e.g. of relinked images) cover their whole section
+            let size = match (symbol.kind(), symbol.section_index()) {
+                (object::SymbolKind::Section, Some(index)) if symbol.size() == 0 => file
+                    .section_by_index(index)
+                    .map_or(0, |section| section.size()),
+                _ => symbol.size(),
+            };
+            table.add(Symbol {
+                name: name.to_string(),
+                address: symbol.address(),
+                size,
+                kind,
+            });
+        }
+        Ok(table)
+    }
+
+    /// Parses a GNU-ld style symbol script, such as the ROM symbol files of the SDK
+    /// (`rtl8710c_rom_symbol_*.ld`).
+    ///
+    /// Every assignment of a constant (`name = 0x...;` or `PROVIDE(name = 0x...);`) is
+    /// added as symbol, all other statements and comments are ignored. The script does
+    /// not store the type of a symbol, so it is derived from the Thumb bit of its address:
+    /// odd addresses are treated as functions, all others (e.g. ROM data tables) as objects
+    /// at their unmodified address.
+    ///
+    /// # Arguments:
+    /// - `script`: The content of the linker script.
+    ///
+    /// # Returns:
+    /// - `Ok(SymbolTable)` containing all symbols.
+    /// - `Err(Error)` if an assigned constant is not a valid number.
+    ///
+    /// # Example
+    /// ```
+    /// use amebazii::symbolize::{SymbolKind, SymbolTable};
+    ///
+    /// let table = SymbolTable::from_ld_script(
+    ///     "/* ROM */\nSECTIONS\n{\n  PROVIDE(hal_uart_init = 0x00008a11);\n  \
+    ///      hal_uart_baud_table = 0x0000c000;\n  __rom_bss_start__ = 0x10000000;\n  \
+    ///      . = ALIGN(4);\n}",
+    /// )
+    /// .unwrap();
+    /// let symbols = table.get_symbols();
+    /// assert_eq!(symbols.len(), 3);
+    /// assert_eq!((symbols[0].address, symbols[0].kind), (0x8a10, SymbolKind::Function));
+    /// assert_eq!((symbols[1].address, symbols[1].kind), (0xc000, SymbolKind::Object));
+    /// assert_eq!(symbols[2].name, "__rom_bss_start__");
+    /// ```
+    pub fn from_ld_script(script: &str) -> Result<Self, Error> {
+        let mut table = Self::new();
+        for statement in strip_comments(script).split(';') {
+            let Some((name, value)) = statement.split_once('=') else {
+                continue;
+            };
+
+            // only the last token of the left-hand side is the symbol name
+            let name = name
+                .split(['{', '}', '('])
+                .next_back()
+                .unwrap_or_default()
+                .trim();
+            let name = name.split_whitespace().next_back().unwrap_or_default();
+            if name.is_empty()
+                || name == "."
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$'))
+            {
+                continue;
+            }
+
+            let value = value.trim().trim_end_matches(')').trim();
+            if !value.starts_with(|c: char| c.is_ascii_digit()) {
+                // expressions (e.g. ALIGN(4) or other symbols) are not supported
+                continue;
+            }
+            let address = parse_number(value).ok_or_else(|| {
+                Error::InvalidState(format!("Invalid address of symbol {}: {}", name, value))
+            })?;
+
+            let kind = if address & 1 == 1 {
+                SymbolKind::Function
+            } else {
+                SymbolKind::Object
+            };
+            table.add(Symbol {
+                name: name.to_string(),
+                address,
+                size: 0,
+                kind,
+            });
+        }
+        Ok(table)
+    }
+
+    /// Adds a symbol to this table.
+    ///
+    /// The Thumb bit of function addresses is cleared.
+    pub fn add(&mut self, mut symbol: Symbol) {
+        if symbol.kind == SymbolKind::Function {
+            symbol.address &= !1;
+        }
+        let idx = self
+            .symbols
+            .partition_point(|other| other.address <= symbol.address);
+        self.symbols.insert(idx, symbol);
+    }
+
+    /// Adds all symbols of another table to this table.
+    pub fn extend(&mut self, other: SymbolTable) {
+        for symbol in other.symbols {
+            self.add(symbol);
+        }
+    }
+
+    /// Returns all symbols, sorted by their address.
+    pub fn get_symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    /// Returns the number of symbols in this table.
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// Checks whether this table is empty.
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Returns the symbol containing the given address.
+    ///
+    /// Symbols with a known size only contain addresses within their size. Symbols without
+    /// a size extend to the next symbol with a greater address (the last symbol only
+    /// matches its own address).
+    ///
+    /// # Arguments:
+    /// - `address`: The address to look up.
+    ///
+    /// # Returns:
+    /// - `Some((&Symbol, u64))`: The symbol and the offset of the address relative to it.
+    /// - `None` if no symbol contains the address.
+    pub fn lookup(&self, address: u64) -> Option<(&Symbol, u64)> {
+        let end = self
+            .symbols
+            .partition_point(|symbol| symbol.address <= address);
+        let nearest = self.symbols[..end].last()?.address;
+
+        for symbol in self.symbols[..end].iter().rev() {
+            let target = match symbol.kind {
+                SymbolKind::Function => address & !1,
+                _ => address,
+            };
+            let offset = target.saturating_sub(symbol.address);
+            let contained = if symbol.size > 0 {
+                offset < symbol.size
+            } else {
+                symbol.address == nearest && (end < self.symbols.len() || offset == 0)
+            };
+            if contained {
+                return Some((symbol, offset));
+            }
+        }
+        None
+    }
+}
+
+/// An address extracted from a console log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LogAddress {
+    /// The line number (starting with 1).
+    pub line: usize,
+
+    /// The label of the value, e.g. the register name (`PC`) or the address of a
+    /// stack dump line.
+    pub label: Option<String>,
+
+    /// The extracted address.
+    pub address: u64,
+}
+
+/// Extracts all addresses from a console log.
+///
+/// Addresses are either prefixed with `0x` or consist of exactly eight hex digits. Register
+/// lines (`PC = 0x9b0083bc`, `LR: 0x9b0083b7`) use the register name as label and dump lines
+/// (`[0x1003ef98] 0x9b0083b7 ...` or `1003EF98: 9B0083B7 ...`) use the dumped address.
+///
+/// # Example
+/// ```
+/// use amebazii::symbolize::parse_log;
+///
+/// let addresses = parse_log("Hard Fault Error!!!!\n PC  = 0x9b0083bc\n[1003ef98] 9b0083b7 00000000");
+/// assert_eq!(addresses.len(), 3);
+/// assert_eq!(addresses[0].label.as_deref(), Some("PC"));
+/// assert_eq!(addresses[1].label.as_deref(), Some("0x1003ef98"));
+/// assert_eq!(addresses[1].address, 0x9b0083b7);
+/// ```
+pub fn parse_log(log: &str) -> Vec<LogAddress> {
+    let mut addresses = Vec::new();
+    for (idx, line) in log.lines().enumerate() {
+        let line = line.trim();
+        let mut label = None;
+        let mut values = line;
+
+        // dump lines start with the dumped address
+        if let Some(rest) = line.strip_prefix('[') {
+            if let Some((address, rest)) = rest.split_once(']') {
+                if let Some(address) = parse_address(address.trim()) {
+                    label = Some(format!("0x{:08x}", address));
+                    values = rest;
+                }
+            }
+        } else if let Some((address, rest)) = line.split_once(':') {
+            if let Some(address) = parse_address(address.trim()) {
+                label = Some(format!("0x{:08x}", address));
+                values = rest;
+            }
+        }
+
+        for (pos, token) in tokenize(values) {
+            let Some(address) = parse_address(token) else {
+                continue;
+            };
+            let label = label.clone().or_else(|| {
+                // register lines: "<NAME> = <value>" or "<NAME>: <value>"
+                let prefix = values[..pos].trim_end();
+                let name = prefix.strip_suffix(['=', ':'])?.trim();
+                (!name.is_empty()).then(|| name.to_string())
+            });
+            addresses.push(LogAddress {
+                line: idx + 1,
+                label,
+                address,
+            });
+        }
+    }
+    addresses
+}
+
+/// An address of a console log mapped to its region, image location and symbol.
+#[derive(Debug, Clone, Serialize)]
+pub struct SymbolizedAddress {
+    /// The line number (starting with 1).
+    pub line: usize,
+
+    /// The label of the value (see [`LogAddress::label`]).
+    pub label: Option<String>,
+
+    /// The address.
+    pub address: u64,
+
+    /// The name of the memory region containing the address.
+    pub region: Option<&'static str>,
+
+    /// The location of the address in the OTA image.
+    pub location: Option<ImageLocation>,
+
+    /// The symbol containing the address, including the offset (`name+0x10`).
+    pub symbol: Option<String>,
+}
+
+impl SymbolizedAddress {
+    /// Checks whether the address could be mapped to a region, image location or symbol.
+    pub fn is_resolved(&self) -> bool {
+        self.region.is_some() || self.location.is_some() || self.symbol.is_some()
+    }
+}
+
+/// Maps addresses of AmebaZ2 console logs to memory regions, OTA image locations and
+/// symbols.
+#[derive(Debug, Clone, Default)]
+pub struct Symbolizer {
+    translator: Option<AddressTranslator>,
+    symbols: SymbolTable,
+}
+
+impl Symbolizer {
+    /// Creates a new symbolizer without image and symbols.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the OTA image used to resolve image locations.
+    pub fn set_image(&mut self, image: &OTAImage) {
+        self.translator = Some(AddressTranslator::new(image));
+    }
+
+    /// Sets the address translator used to resolve image (and flash) locations.
+    pub fn set_translator(&mut self, translator: AddressTranslator) {
+        self.translator = Some(translator);
+    }
+
+    /// Adds all symbols of the given table.
+    pub fn add_symbols(&mut self, symbols: SymbolTable) {
+        self.symbols.extend(symbols);
+    }
+
+    /// Returns all known symbols.
+    pub fn get_symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    /// Maps a single address to its region, image location and symbol.
+    pub fn symbolize(&self, address: u64) -> SymbolizedAddress {
+        let code_address = address & !1;
+        let location = self.translator.as_ref().and_then(|translator| {
+            translator
+                .resolve_address(address)
+                .or_else(|| translator.resolve_address(code_address))
+        });
+        let symbol = self.symbols.lookup(address).map(|(symbol, offset)| {
+            if offset == 0 {
+                symbol.name.clone()
+            } else {
+                format!("{}+0x{:x}", symbol.name, offset)
+            }
+        });
+
+        SymbolizedAddress {
+            line: 0,
+            label: None,
+            address,
+            region: region_name(address),
+            location,
+            symbol,
+        }
+    }
+
+    /// Extracts all addresses of a console log (see [`parse_log`]) and maps them to their
+    /// region, image location and symbol.
+    ///
+    /// # Returns:
+    /// - All addresses that could be mapped (see [`SymbolizedAddress::is_resolved`]).
+    ///
+    /// # Example
+    /// ```
+    /// use amebazii::{
+    ///     symbolize::{SymbolTable, Symbolizer},
+    ///     types::{from_stream, OTAImage},
+    /// };
+    /// use std::io::Cursor;
+    ///
+    /// let mut reader = Cursor::new(include_bytes!("../../assets/fw1.bin"));
+    /// let image: OTAImage = from_stream(&mut reader).unwrap();
+    /// let mut symbolizer = Symbolizer::new();
+    /// symbolizer.set_image(&image);
+    /// symbolizer.add_symbols(
+    ///     SymbolTable::from_ld_script(include_str!("../../assets/rom_symbols_example.ld")).unwrap(),
+    /// );
+    ///
+    /// let addresses = symbolizer.symbolize_log(include_str!("../../assets/hardfault.log"));
+    /// let find = |label: &str| {
+    ///     addresses
+    ///         .iter()
+    ///         .find(|address| address.label.as_deref() == Some(label))
+    ///         .unwrap()
+    /// };
+    /// for (label, file_offset) in [("PC", 0x42b4), ("LR", 0x42a7)] {
+    ///     let address = find(label);
+    ///     assert_eq!(address.region, Some("XIP_FLASH_C"));
+    ///     let location = address.location.as_ref().unwrap();
+    ///     assert_eq!((location.subimage, location.section), (1, 0));
+    ///     assert_eq!(location.file_offset, file_offset);
+    /// }
+    ///
+    /// let rom = addresses.iter().find(|address| address.address == 0x8a3b).unwrap();
+    /// assert_eq!(rom.symbol.as_deref(), Some("hal_uart_init+0x2a"));
+    /// ```
+    pub fn symbolize_log(&self, log: &str) -> Vec<SymbolizedAddress> {
+        parse_log(log)
+            .into_iter()
+            .map(|entry| SymbolizedAddress {
+                line: entry.line,
+                label: entry.label,
+                ..self.symbolize(entry.address)
+            })
+            .filter(SymbolizedAddress::is_resolved)
+            .collect()
+    }
+}
+
+/// Splits a line into alphanumeric tokens, including their position.
+fn tokenize(line: &str) -> Vec<(usize, &str)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (pos, c) in line
+        .char_indices()
+        .chain(std::iter::once((line.len(), ' ')))
+    {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            start.get_or_insert(pos);
+        } else if let Some(begin) = start.take() {
+            tokens.push((begin, &line[begin..pos]));
+        }
+    }
+    tokens
+}
+
+/// Parses an address prefixed with `0x` or consisting of exactly eight hex digits.
+fn parse_address(token: &str) -> Option<u64> {
+    let digits = match token
+        .strip_prefix("0x")
+        .or_else(|| token.strip_prefix("0X"))
+    {
+        Some(digits) if !digits.is_empty() && digits.len() <= 8 => digits,
+        Some(_) => return None,
+        None if token.len() == 8 => token,
+        None => return None,
+    };
+    u64::from_str_radix(digits, 16).ok()
+}
+
+/// Parses a hexadecimal (`0x` prefix) or decimal number.
+fn parse_number(value: &str) -> Option<u64> {
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(digits) => u64::from_str_radix(digits, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+/// Removes all C-style comments (`/* ... */`) from a linker script.
+fn strip_comments(script: &str) -> String {
+    let mut result = String::with_capacity(script.len());
+    let mut rest = script;
+    while let Some(start) = rest.find("/*") {
+        result.push_str(&rest[..start]);
+        rest = match rest[start + 2..].find("*/") {
+            Some(end) => &rest[start + 2 + end + 2..],
+            None => "",
+        };
+    }
+    result.push_str(rest);
+    result
+}
@@ -1,8 +1,10 @@
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
 use crate::{
     error::Error,
-    util::{hmac_md5, hmac_sha256, md5, sha256},
+    keys::DEFAULT_IV,
+    util::{aes_decrypt, aes_encrypt, hmac_md5, hmac_sha256, md5, sha256},
 };
 
 /// Enum representing different image types.
@@ -276,6 +278,58 @@ impl TryFrom<u16> for EncryptionAlgo {
     }
 }
 
+impl EncryptionAlgo {
+    /// Decrypts the provided buffer using the specified algorithm.
+    ///
+    /// The AES variant is selected based on the key length. In CBC mode, the given IV is
+    /// used, or `DEFAULT_IV` if none is provided. The IV is ignored in ECB mode.
+    ///
+    /// # Parameters
+    /// - `buffer`: A byte slice containing the encrypted data (multiple of 16 bytes).
+    /// - `key`: The AES key (16, 24 or 32 bytes).
+    /// - `iv`: An optional 16-byte IV for CBC mode.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<u8>)`: The decrypted data.
+    /// - `Err(Error::UnsupportedEncryptionAlgo)`: An error if an unsupported algorithm is chosen.
+    ///
+    /// # Example
+    /// ```
+    /// use amebazii::types::enums::EncryptionAlgo;
+    ///
+    /// let key = [0x11; 32];
+    /// let plain = EncryptionAlgo::Cbc.decrypt(&[0u8; 16], &key, None).unwrap();
+    /// assert_eq!(plain.len(), 16);
+    /// ```
+    pub fn decrypt(&self, buffer: &[u8], key: &[u8], iv: Option<&[u8]>) -> Result<Vec<u8>, Error> {
+        match self {
+            EncryptionAlgo::Ecb => aes_decrypt(key, None, buffer),
+            EncryptionAlgo::Cbc => aes_decrypt(key, Some(iv.unwrap_or(DEFAULT_IV)), buffer),
+            _ => Err(Error::UnsupportedEncryptionAlgo(*self as u8)),
+        }
+    }
+
+    /// Encrypts the provided buffer using the specified algorithm.
+    ///
+    /// The key and IV are handled the same way as in [`EncryptionAlgo::decrypt`].
+    ///
+    /// # Parameters
+    /// - `buffer`: A byte slice containing the plaintext data (multiple of 16 bytes).
+    /// - `key`: The AES key (16, 24 or 32 bytes).
+    /// - `iv`: An optional 16-byte IV for CBC mode.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<u8>)`: The encrypted data.
+    /// - `Err(Error::UnsupportedEncryptionAlgo)`: An error if an unsupported algorithm is chosen.
+    pub fn encrypt(&self, buffer: &[u8], key: &[u8], iv: Option<&[u8]>) -> Result<Vec<u8>, Error> {
+        match self {
+            EncryptionAlgo::Ecb => aes_encrypt(key, None, buffer),
+            EncryptionAlgo::Cbc => aes_encrypt(key, Some(iv.unwrap_or(DEFAULT_IV)), buffer),
+            _ => Err(Error::UnsupportedEncryptionAlgo(*self as u8)),
+        }
+    }
+}
+
 // --- Hash Algorithms ---
 
 // Defined in parse_json_config
@@ -377,6 +431,30 @@ impl HashAlgo {
     }
 }
 
+impl FromStr for HashAlgo {
+    type Err = Error;
+
+    /// Parses a hash algorithm from its name (`md5` or `sha256`, case-insensitive).
+    ///
+    /// # Example
+    /// ```
+    /// use amebazii::types::enums::HashAlgo;
+    /// use std::str::FromStr;
+    ///
+    /// assert_eq!(HashAlgo::from_str("SHA256").unwrap(), HashAlgo::Sha256);
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "md5" => Ok(HashAlgo::Md5),
+            "sha256" => Ok(HashAlgo::Sha256),
+            _ => Err(Error::InvalidEnumValue(format!(
+                "Invalid hash algorithm: {}",
+                s
+            ))),
+        }
+    }
+}
+
 /// Enum representing all different types of partitions. (as per _convert_pt_type)
 ///
 /// # Variants
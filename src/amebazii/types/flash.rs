@@ -29,13 +29,22 @@
 //    │                  │
 //    └──────────────────┘
 
-use std::{collections::HashMap, io};
+use std::{
+    collections::HashMap,
+    io::{self, Cursor, Read},
+};
 
 use crate::{
     error::Error,
+    keystore::KeyStore,
     read_padding,
-    types::{image::EncryptedOr, PartTab},
-    util::write_fill,
+    signer::{KeySigner, Signer},
+    types::{
+        image::{set_signature_with, KeySource},
+        verify::{KeyIdentification, VerifyReport},
+        EncryptedOr, PartTab,
+    },
+    util::{write_fill, x25519_public_key},
     write_aligned, write_padding,
 };
 
@@ -195,6 +204,17 @@ impl Flash {
         self.partitions.get(&part_type)
     }
 
+    /// Retrieves a mutable reference to a partition by its type.
+    ///
+    /// # Parameters:
+    /// - `part_type`: The partition type to search for in the flash.
+    ///
+    /// # Returns:
+    /// - `Some(&mut Partition)` if the partition exists, otherwise `None`.
+    pub fn get_partition_mut(&mut self, part_type: PartitionType) -> Option<&mut Partition> {
+        self.partitions.get_mut(&part_type)
+    }
+
     /// Checks whether a partition of the specified type exists.
     ///
     /// # Parameters:
@@ -268,35 +288,405 @@ impl Flash {
     }
 }
 
-impl FromStream for Flash {
-    /// Reads the flash image from the provided reader.
+impl Flash {
+    /// Reads the flash image from the provided reader, decrypting the partition table if
+    /// necessary.
     ///
-    /// This function reads the entire flash image, including the calibration pattern, partitions,
-    /// and partition records. It populates the `Flash` struct with the data read from the stream.
+    /// All partitions are loaded from the records of the (decrypted) partition table.
     ///
     /// # Parameters:
     /// - `reader`: The input stream from which the flash image is read.
+    /// - `source`: Specifies where to take the key material for an encrypted partition table from.
     ///
     /// # Returns:
     /// - `Ok(())` if the flash image was successfully read and parsed.
-    /// - `Err(Error)` if there was an issue reading the flash image.
-    fn read_from<R>(&mut self, reader: &mut R) -> Result<(), Error>
+    /// - `Err(Error)` if there was an issue reading the flash image or the partition table
+    ///   could not be decrypted.
+    pub fn read_with_key<R>(&mut self, reader: &mut R, source: &KeySource) -> Result<(), Error>
     where
         R: io::Read + io::Seek,
     {
         reader.read_exact(&mut self.calibration_pattern)?;
         read_padding!(reader, 16);
 
-        let pt_image: pt::PartitionTableImage = from_stream(reader)?;
+        let mut pt_image: pt::PartitionTableImage = from_stream(reader)?;
+        pt_image.decrypt(source)?;
+
+        let pt: &PartTab = pt_image.pt.as_ref();
+        for record in pt.get_records() {
+            reader.seek(io::SeekFrom::Start(record.start_addr as u64))?;
+            self.set_partition(record.part_type, Partition::from_record(record, reader)?);
+        }
+        self.set_partition(PartitionType::PartTab, Partition::PartitionTable(pt_image));
+        Ok(())
+    }
+}
+
+impl Flash {
+    /// Verifies the integrity of all images stored in this flash image.
+    ///
+    /// The hash of the partition table is verified using the hash key of the given key store.
+    /// The boot image and both firmware images are verified using the hash key of their
+    /// partition record, falling back to the hash key (boot image) or firmware hash key
+    /// (firmware images) of the key store. All other partitions are not signed and therefore
+    /// not part of the result.
+    ///
+    /// # Parameters:
+    /// - `reader`: The stream this flash image was read from.
+    /// - `keys`: The key store used to resolve all hash keys.
+    ///
+    /// # Returns:
+    /// - `Ok(Vec<(PartitionType, VerifyReport)>)` containing one report per verified partition.
+    /// - `Err(Error)` if there is no partition table or the image data could not be read.
+    pub fn verify<R>(
+        &self,
+        reader: &mut R,
+        keys: &KeyStore,
+    ) -> Result<Vec<(PartitionType, VerifyReport)>, Error>
+    where
+        R: io::Read + io::Seek,
+    {
+        let pt_image = self.get_partition_table()?;
+        let mut reports = Vec::new();
+        reader.seek(io::SeekFrom::Start(0x20))?;
+        reports.push((
+            PartitionType::PartTab,
+            pt_image.verify(reader, keys.get_hash_key())?,
+        ));
+
         if let EncryptedOr::Plain(pt) = &pt_image.pt {
+            let mut keys = keys.clone();
+            keys.add_partition_keys(pt);
             for record in pt.get_records() {
+                let hash_key = keys.get_partition_hash_key(record.part_type);
                 reader.seek(io::SeekFrom::Start(record.start_addr as u64))?;
-                self.set_partition(record.part_type, Partition::from_record(record, reader)?);
+
+                let report = match self.get_partition(record.part_type) {
+                    Some(Partition::Bootloader(boot_image)) => {
+                        boot_image.verify(reader, hash_key)?
+                    }
+                    Some(Partition::Fw1(ota_image)) | Some(Partition::Fw2(ota_image)) => {
+                        let buffer = Flash::read_record_data(reader, record)?;
+                        ota_image.verify_with_key(&mut Cursor::new(buffer), Some(hash_key))?
+                    }
+                    _ => continue,
+                };
+                reports.push((record.part_type, report));
+            }
+        }
+        Ok(reports)
+    }
+
+    /// Identifies the keys and hash algorithms used to create the hashes of the partition table,
+    /// the boot image and both firmware images.
+    ///
+    /// All keys of the given key store, all built-in keys and the hash keys of all partition
+    /// records are tried (in this order).
+    ///
+    /// # Parameters:
+    /// - `reader`: The stream this flash image was read from.
+    /// - `keys`: The key store providing additional keys to try.
+    ///
+    /// # Returns:
+    /// - `Ok(Vec<(PartitionType, Vec<KeyIdentification>)>)` containing the results per partition.
+    /// - `Err(Error)` if there is no partition table or the image data could not be read.
+    pub fn identify_keys<R>(
+        &self,
+        reader: &mut R,
+        keys: &KeyStore,
+    ) -> Result<Vec<(PartitionType, Vec<KeyIdentification>)>, Error>
+    where
+        R: io::Read + io::Seek,
+    {
+        let pt_image = self.get_partition_table()?;
+        let mut keys = keys.known_keys();
+        if let EncryptedOr::Plain(pt) = &pt_image.pt {
+            let mut record_keys = KeyStore::new();
+            record_keys.add_partition_keys(pt);
+            keys.extend_from_slice(record_keys.get_keys());
+        }
+
+        let mut results = Vec::new();
+        reader.seek(io::SeekFrom::Start(0x20))?;
+        results.push((
+            PartitionType::PartTab,
+            vec![pt_image.identify_key(reader, &keys)?],
+        ));
+
+        if let EncryptedOr::Plain(pt) = &pt_image.pt {
+            for record in pt.get_records() {
+                reader.seek(io::SeekFrom::Start(record.start_addr as u64))?;
+                let result = match self.get_partition(record.part_type) {
+                    Some(Partition::Bootloader(boot_image)) => {
+                        vec![boot_image.identify_key(reader, &keys)?]
+                    }
+                    Some(Partition::Fw1(ota_image)) | Some(Partition::Fw2(ota_image)) => {
+                        let buffer = Flash::read_record_data(reader, record)?;
+                        ota_image.identify_keys(&mut Cursor::new(buffer), &keys)?
+                    }
+                    _ => continue,
+                };
+                results.push((record.part_type, result));
+            }
+        }
+        Ok(results)
+    }
+
+    /// Recomputes the hashes of the partition table, the boot image and both firmware images.
+    ///
+    /// All other partitions are not signed and therefore left unchanged.
+    ///
+    /// # Parameters:
+    /// - `signer_for`: Returns the signer used for the partition of the given type.
+    ///
+    /// # Returns:
+    /// - `Ok(())` if all hashes were updated.
+    /// - `Err(Error)` if a signer could not be created or a hash could not be computed.
+    pub fn update_signatures_with<F>(&mut self, signer_for: F) -> Result<(), Error>
+    where
+        F: Fn(PartitionType) -> Result<Box<dyn Signer>, Error>,
+    {
+        for (part_type, partition) in self.partitions.iter_mut() {
+            match partition {
+                Partition::PartitionTable(pt_image) => {
+                    set_signature_with(pt_image, signer_for(*part_type)?.as_ref())?;
+                }
+                Partition::Bootloader(boot_image) => {
+                    set_signature_with(boot_image, signer_for(*part_type)?.as_ref())?;
+                }
+                Partition::Fw1(ota_image) | Partition::Fw2(ota_image) => {
+                    ota_image.update_signatures_with(signer_for(*part_type)?.as_ref())?;
+                }
+                _ => {}
             }
         }
-        self.set_partition(PartitionType::PartTab, Partition::PartitionTable(pt_image));
         Ok(())
     }
+
+    /// Replaces all keys of this flash image and re-signs all images.
+    ///
+    /// The hash key of every partition record that stores one is replaced by the partition hash
+    /// key of `new_keys` (see [`KeyStore::get_partition_hash_key`]). All public keys in the key
+    /// blocks of the partition table, boot image and firmware images derived from the encryption
+    /// or hash private key of `old_keys` are replaced by the public keys of the corresponding
+    /// private keys of `new_keys`. Finally, all hashes, signatures and checksums are recomputed
+    /// using the new keys.
+    ///
+    /// Encryption (user) keys are not changed.
+    ///
+    /// # Parameters:
+    /// - `old_keys`: The keys currently used by this flash image.
+    /// - `new_keys`: The keys to use from now on.
+    ///
+    /// # Returns:
+    /// - `Ok(())` if all keys were replaced.
+    /// - `Err(Error)` if there is no (decrypted) partition table, a new hash key is not 32 bytes
+    ///   long or an image could not be signed.
+    ///
+    /// # Example
+    /// ```
+    /// use amebazii::keystore::KeyStore;
+    /// use amebazii::types::{from_stream, to_bytes, Flash, Partition, PartitionType};
+    /// use std::io::Cursor;
+    ///
+    /// let pt_data = &include_bytes!("../../../assets/partition.bin")[0x20..];
+    /// let fw1_data = include_bytes!("../../../assets/fw1.bin");
+    /// let mut flash = Flash::default();
+    /// flash.set_partition_table(from_stream(&mut Cursor::new(pt_data)).unwrap());
+    /// flash.set_fw1(from_stream(&mut Cursor::new(fw1_data)).unwrap());
+    ///
+    /// let mut new_keys = KeyStore::new();
+    /// new_keys.add_key("hash_key", &[0x42; 32]);
+    /// new_keys.add_key("fw_hash_key", &[0x24; 32]);
+    /// flash.rekey(&KeyStore::new(), &new_keys).unwrap();
+    ///
+    /// let Some(Partition::Fw1(fw1)) = flash.get_partition(PartitionType::Fw1) else {
+    ///     panic!("missing firmware image");
+    /// };
+    /// let data = to_bytes(fw1).unwrap();
+    /// assert!(fw1.verify(&mut Cursor::new(&data), &new_keys).unwrap().is_ok());
+    /// assert!(!fw1.verify(&mut Cursor::new(&data), &KeyStore::new()).unwrap().is_ok());
+    ///
+    /// let Some(Partition::PartitionTable(pt_image)) = flash.get_partition(PartitionType::PartTab)
+    /// else {
+    ///     panic!("missing partition table");
+    /// };
+    /// let data = to_bytes(pt_image).unwrap();
+    /// let report = pt_image.verify(&mut Cursor::new(&data), &[0x42; 32]).unwrap();
+    /// assert!(report.is_ok());
+    /// ```
+    pub fn rekey(&mut self, old_keys: &KeyStore, new_keys: &KeyStore) -> Result<(), Error> {
+        let mut keys = new_keys.clone();
+        match self.partitions.get_mut(&PartitionType::PartTab) {
+            Some(Partition::PartitionTable(pt_image)) => match &mut pt_image.pt {
+                EncryptedOr::Plain(pt) => {
+                    Flash::rekey_records(pt, new_keys)?;
+                    keys.add_partition_keys(pt);
+                }
+                EncryptedOr::Encrypted(_) => {
+                    return Err(Error::InvalidState(
+                        "Partition table must be decrypted before rekeying".to_string(),
+                    ))
+                }
+            },
+            _ => {
+                return Err(Error::InvalidState(
+                    "Flash image does not contain a partition table".to_string(),
+                ))
+            }
+        }
+
+        let pubkeys = [
+            (old_keys.get_enc_priv_key(), new_keys.get_enc_priv_key()),
+            (old_keys.get_hash_priv_key(), new_keys.get_hash_priv_key()),
+        ];
+        for (old_key, new_key) in pubkeys {
+            let old_pubkey = x25519_public_key(old_key)?;
+            let new_pubkey = x25519_public_key(new_key)?;
+            for partition in self.partitions.values_mut() {
+                let keyblock = match partition {
+                    Partition::PartitionTable(pt_image) => &mut pt_image.keyblock,
+                    Partition::Bootloader(boot_image) => &mut boot_image.keyblock,
+                    Partition::Fw1(ota_image) | Partition::Fw2(ota_image) => {
+                        &mut ota_image.keyblock
+                    }
+                    _ => continue,
+                };
+                keyblock.replace_pubkey(&old_pubkey, &new_pubkey);
+            }
+        }
+
+        self.update_signatures_with(|part_type| {
+            Ok(Box::new(KeySigner::new(Some(
+                keys.get_partition_hash_key(part_type),
+            ))))
+        })
+    }
+
+    /// Writes the partition table, the boot image and both firmware images into an existing
+    /// flash image.
+    ///
+    /// In contrast to [`ToStream::write_to`], all other data (system data, calibration data and
+    /// user data) of the existing flash image is kept as it is.
+    ///
+    /// # Parameters:
+    /// - `writer`: The stream storing the existing flash image.
+    ///
+    /// # Returns:
+    /// - `Ok(())` if all partitions were written.
+    /// - `Err(Error)` if there is no (decrypted) partition table, a partition does not fit into
+    ///   its record or the data could not be written.
+    pub fn patch_to<W>(&self, writer: &mut W) -> Result<(), Error>
+    where
+        W: io::Write + io::Seek,
+    {
+        let pt_image = self.get_partition_table()?;
+        if pt_image.is_encrypted() {
+            return Err(Error::InvalidState(
+                "Partition table must be decrypted before writing".to_string(),
+            ));
+        }
+
+        writer.seek(io::SeekFrom::Start(0x20))?;
+        pt_image.write_to(writer)?;
+
+        let pt: &PartTab = pt_image.pt.as_ref();
+        for record in pt.get_records() {
+            let partition = match self.get_partition(record.part_type) {
+                Some(partition @ Partition::Bootloader(_))
+                | Some(partition @ Partition::Fw1(_))
+                | Some(partition @ Partition::Fw2(_)) => partition,
+                _ => continue,
+            };
+
+            let mut buffer = Cursor::new(Vec::with_capacity(record.length as usize));
+            partition.write_to(&mut buffer)?;
+            let buffer = buffer.into_inner();
+            if buffer.len() > record.length as usize {
+                return Err(Error::InvalidState(format!(
+                    "Partition {:?} ({} bytes) does not fit into its record ({} bytes)",
+                    record.part_type,
+                    buffer.len(),
+                    record.length
+                )));
+            }
+            writer.seek(io::SeekFrom::Start(record.start_addr as u64))?;
+            writer.write_all(&buffer)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the hash keys of all partition records that store one.
+    fn rekey_records(pt: &mut PartTab, new_keys: &KeyStore) -> Result<(), Error> {
+        let part_types: Vec<PartitionType> = pt
+            .get_records()
+            .iter()
+            .filter(|record| record.get_hash_key().is_some())
+            .map(|record| record.part_type)
+            .collect();
+
+        for part_type in part_types {
+            let new_key: [u8; 32] = new_keys
+                .get_partition_hash_key(part_type)
+                .try_into()
+                .map_err(|_| {
+                    Error::InvalidState(format!(
+                        "Hash key of partition {:?} must be 32 bytes long",
+                        part_type
+                    ))
+                })?;
+            if let Some(record) = pt.get_record_mut(part_type) {
+                record.set_hash_key(Some(new_key));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the partition table image of this flash image.
+    fn get_partition_table(&self) -> Result<&pt::PartitionTableImage, Error> {
+        match self.get_partition(PartitionType::PartTab) {
+            Some(Partition::PartitionTable(pt_image)) => Ok(pt_image),
+            _ => Err(Error::InvalidState(
+                "Flash image does not contain a partition table".to_string(),
+            )),
+        }
+    }
+
+    /// Reads the data of the given partition record, so that it can be processed on its own.
+    fn read_record_data<R>(reader: &mut R, record: &Record) -> Result<Vec<u8>, Error>
+    where
+        R: io::Read + io::Seek,
+    {
+        let mut buffer = Vec::with_capacity(record.length as usize);
+        reader
+            .by_ref()
+            .take(record.length as u64)
+            .read_to_end(&mut buffer)?;
+        Ok(buffer)
+    }
+}
+
+impl FromStream for Flash {
+    /// Reads the flash image from the provided reader.
+    ///
+    /// This function reads the entire flash image, including the calibration pattern, partitions,
+    /// and partition records. It populates the `Flash` struct with the data read from the stream.
+    /// An encrypted partition table is decrypted using the user key from its image header (see
+    /// [`Flash::read_with_key`] to use another key).
+    ///
+    /// # Parameters:
+    /// - `reader`: The input stream from which the flash image is read.
+    ///
+    /// # Returns:
+    /// - `Ok(())` if the flash image was successfully read and parsed.
+    /// - `Err(Error)` if there was an issue reading the flash image or the partition table
+    ///   could not be decrypted.
+    fn read_from<R>(&mut self, reader: &mut R) -> Result<(), Error>
+    where
+        R: io::Read + io::Seek,
+    {
+        self.read_with_key(reader, &KeySource::default())
+    }
 }
 
 impl ToStream for Flash {
@@ -315,18 +705,19 @@ impl ToStream for Flash {
     where
         W: io::Write + io::Seek,
     {
-        writer.write_all(&self.calibration_pattern)?;
-        write_padding!(writer, 16);
-
-        let pt_image = self.partitions.get(&PartitionType::PartTab);
-        if pt_image.is_none() {
-            return Err(Error::InvalidState("Partition table not found".to_string()));
+        let pt_image = match self.partitions.get(&PartitionType::PartTab) {
+            Some(Partition::PartitionTable(pt_image)) => pt_image,
+            _ => return Err(Error::InvalidState("Partition table not found".to_string())),
+        };
+
+        // the partition table is written encrypted, but we need the plaintext records to
+        // populate the flash image
+        if pt_image.is_encrypted() {
+            return Err(Error::InvalidState(
+                "Partition table must be decrypted before writing".to_string(),
+            ));
         }
 
-        let pt_image = pt_image.unwrap();
-        pt_image.write_to(writer)?;
-        write_aligned!(writer, 0x1000);
-
         // system partition is mandatory
         let system = self.partitions.get(&PartitionType::Sys);
         if system.is_none() {
@@ -334,6 +725,13 @@ impl ToStream for Flash {
                 "System partition not found".to_string(),
             ));
         }
+
+        writer.write_all(&self.calibration_pattern)?;
+        write_padding!(writer, 16);
+
+        pt_image.write_to(writer)?;
+        write_aligned!(writer, 0x1000);
+
         system.unwrap().write_to(writer)?;
         // we don't have to align here, because the system partition already fills up
         // the space
@@ -344,21 +742,12 @@ impl ToStream for Flash {
 
         // even though the next sections are mandatory, we use the records within the
         // partition table to populate the flash image
-        if let Partition::PartitionTable(pt_image) = pt_image {
-            let pt = &pt_image.pt;
-            if pt.is_encrypted() {
-                return Err(Error::NotImplemented(
-                    "Encrypted partition table is not supported".to_string(),
-                ));
-            }
-
-            // the order must be preserved (BUT it is not checked here)
-            let pt: &PartTab = pt.as_ref();
-            self.write_partition(writer, pt, PartitionType::Boot)?;
-            self.write_partition(writer, pt, PartitionType::Fw1)?;
-            self.write_partition(writer, pt, PartitionType::Fw2)?;
-            self.write_partition(writer, pt, PartitionType::User)?;
-        }
+        // the order must be preserved (BUT it is not checked here)
+        let pt: &PartTab = pt_image.pt.as_ref();
+        self.write_partition(writer, pt, PartitionType::Boot)?;
+        self.write_partition(writer, pt, PartitionType::Fw1)?;
+        self.write_partition(writer, pt, PartitionType::Fw2)?;
+        self.write_partition(writer, pt, PartitionType::User)?;
         Ok(())
     }
 }
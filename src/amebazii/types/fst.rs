@@ -31,9 +31,9 @@ use crate::{
 /// ```
 /// - Size = 0x60 = 96 bytes
 ///
-/// **Note:** Encryption and cipher-related fields are placeholders, as encryption is not
-/// currently supported. These fields are implemented as `Option<T>` to allow future extension
-/// if encryption support is added later.
+/// **Note:** The encryption algorithm and cipher key/IV only apply to encrypted sub-images
+/// (see `SubImage::decrypt` and `SubImage::enable_encryption`). If the cipher key and IV are
+/// not set, the user key from the image header will be used to encrypt the sections.
 ///
 /// # Example:
 /// ```rust
@@ -47,9 +47,14 @@ use crate::{
 /// ```
 #[derive(Debug)]
 pub struct FST {
-    /// encryption algorithm (not supported)
+    /// The encryption algorithm used for the sections of an encrypted sub-image. `None`
+    /// if encryption is disabled.
     pub enc_algo: Option<EncryptionAlgo>,
 
+    /// The raw encryption algorithm written if encryption is disabled, so that an FST is
+    /// written back unchanged.
+    disabled_enc_algo: u16,
+
     /// The hash algorithm used for hashing. Default is `Sha256`.
     pub hash_algo: Option<HashAlgo>,
 
@@ -63,7 +68,8 @@ pub struct FST {
 impl Default for FST {
     fn default() -> FST {
         return FST {
-            enc_algo: None, // currently encryption is not supported
+            enc_algo: None, // encryption is disabled by default
+            disabled_enc_algo: EncryptionAlgo::default() as u16,
             hash_algo: Some(HashAlgo::Sha256),
             partition_size: 0, // default is zero
             valid_pattern: DEFAULT_VALID_PATTERN.clone(),
@@ -160,9 +166,8 @@ impl FromStream for FST {
     {
         // Read the encryption algorithm (u16 to EncryptionAlgo), even though it may
         // be unset later on
-        self.enc_algo = Some(EncryptionAlgo::try_from(
-            reader.read_u16::<LittleEndian>()?,
-        )?);
+        self.disabled_enc_algo = reader.read_u16::<LittleEndian>()?;
+        self.enc_algo = Some(EncryptionAlgo::try_from(self.disabled_enc_algo)?);
 
         self.hash_algo = Some(HashAlgo::try_from(reader.read_u16::<LittleEndian>()?)?);
         self.partition_size = reader.read_u32::<LittleEndian>()?;
@@ -212,8 +217,12 @@ impl ToStream for FST {
     where
         W: std::io::Write,
     {
-        // Write the encryption algorithm and hash algorithm (u16) or a default value
-        writer.write_u16::<LittleEndian>(self.enc_algo.unwrap_or_default() as u16)?;
+        // Write the encryption algorithm (or the value read if encryption is disabled) and
+        // the hash algorithm (u16) or a default value
+        writer.write_u16::<LittleEndian>(match self.enc_algo {
+            Some(enc_algo) => enc_algo as u16,
+            None => self.disabled_enc_algo,
+        })?;
         writer.write_u16::<LittleEndian>(self.hash_algo.unwrap_or_default() as u16)?;
         writer.write_u32::<LittleEndian>(self.partition_size)?;
         writer.write_all(&self.valid_pattern)?; // 8 bytes
@@ -224,7 +233,9 @@ impl ToStream for FST {
         let flags = if self.enc_algo.is_some() { 0b01 } else { 0 }
             | if self.hash_algo.is_some() { 0b10 } else { 0 };
         writer.write_u8(flags & 0b11)?; // 2 bits
-        writer.write_u8(self.is_cipher_key_iv_valid() as u8)?;
+
+        // only bit 0 is relevant, all other bits are set (as in erased flash)
+        writer.write_u8(0xFE | self.is_cipher_key_iv_valid() as u8)?;
 
         // padding
         write_padding!(writer, 10);
@@ -15,6 +15,9 @@ use super::{
 /// A struct representing the key block with two public keys:
 /// * an encryption public key and
 /// * a hash public key.
+///
+/// In OTA images, the key block is made up of the OTA signature and the first public key
+/// (see [`crate::types::OTAImage`]).
 #[derive(Debug)]
 pub struct KeyBlock {
     enc_pubkey: [u8; 32],  // Encryption public key (32 bytes)
@@ -137,6 +140,25 @@ impl KeyBlock {
     pub fn get_hash_pubkey_mut(&mut self) -> &mut [u8; 32] {
         &mut self.hash_pubkey
     }
+
+    /// Replaces all public keys of this key block that are equal to `old_key` with `new_key`.
+    ///
+    /// # Arguments:
+    /// - `old_key`: The public key to replace.
+    /// - `new_key`: The new public key.
+    ///
+    /// # Returns:
+    /// - `true` if at least one public key has been replaced.
+    pub fn replace_pubkey(&mut self, old_key: &[u8; 32], new_key: &[u8; 32]) -> bool {
+        let mut replaced = false;
+        for pubkey in [&mut self.enc_pubkey, &mut self.hash_pubkey] {
+            if pubkey == old_key {
+                pubkey.copy_from_slice(new_key);
+                replaced = true;
+            }
+        }
+        replaced
+    }
 }
 
 // --- Generic Header ---
@@ -571,7 +593,8 @@ impl ToStream for SectionHeader {
 
         write_padding!(writer, 4);
         writer.write_all(&self.valid_pattern)?;
-        writer.write_u8(self.xip_key_iv_valid() as u8)?;
+        // only bit 0 is relevant, all other bits are set (as in erased flash)
+        writer.write_u8(0xFE | self.xip_key_iv_valid() as u8)?;
         write_padding!(writer, 7);
 
         write_data!(writer, self.xip_key, 16);
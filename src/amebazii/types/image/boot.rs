@@ -1,15 +1,20 @@
-use std::io::{Cursor, Seek, Write};
+use std::io::{self, Cursor, Write};
 
 use crate::{
+    error::Error,
+    keys::{KnownKey, BOOT_DEFAULT_USER_KEY1},
+    signer::Signer,
     types::{
+        enums::HashAlgo,
+        from_stream,
         header::{EntryHeader, ImageHeader, KeyBlock},
+        verify::{identify_key, CheckKind, CheckValue, KeyIdentification, VerifyReport},
         BinarySize, FromStream, ToStream,
     },
-    util::{hmac_sha256, skip_aligned, write_fill},
-    write_aligned,
+    util::{hmac_sha256, skip_aligned},
 };
 
-use super::AsImage;
+use super::{AsImage, ImageCipher, KeySource};
 
 /// Represents a boot image, including encryption public keys, hash, and segment data.
 ///
@@ -31,6 +36,13 @@ pub struct BootImage {
     /// The hash of the boot image.
     /// This is a 32-byte hash used to verify the integrity of the boot image.
     hash: [u8; 32],
+
+    /// The raw segment data (entry header and text) of an encrypted boot image that has
+    /// not been decrypted yet.
+    encrypted: Option<Vec<u8>>,
+
+    /// The key source used to encrypt the boot image when it is written.
+    key_source: Option<KeySource>,
 }
 
 impl Default for BootImage {
@@ -47,6 +59,8 @@ impl Default for BootImage {
             entry: EntryHeader::default(),
             text: Vec::new(),
             hash: [0xFF; 32],
+            encrypted: None,
+            key_source: None,
         }
     }
 }
@@ -102,6 +116,191 @@ impl BootImage {
     }
 }
 
+// cryptographic ops
+impl BootImage {
+    /// Returns `true` if the entry header and text of this boot image are still encrypted.
+    pub fn is_encrypted(&self) -> bool {
+        self.encrypted.is_some()
+    }
+
+    /// Returns the key source used to encrypt the boot image when it is written.
+    ///
+    /// # Returns:
+    /// - `Some(&KeySource)` if the boot image will be encrypted on write, `None` otherwise.
+    pub fn get_key_source(&self) -> Option<&KeySource> {
+        self.key_source.as_ref()
+    }
+
+    /// Enables encryption of the boot image.
+    ///
+    /// The entry header and text are encrypted when the image is written (and when its
+    /// signature is built), so the hash will be calculated over the encrypted image.
+    ///
+    /// # Arguments:
+    /// - `source`: Specifies where to take the key material from.
+    pub fn enable_encryption(&mut self, source: KeySource) {
+        self.header.is_encrypt = true;
+        self.key_source = Some(source);
+    }
+
+    /// Disables encryption of the boot image, which will be written as plaintext afterward.
+    ///
+    /// This method has no effect on a boot image that has not been decrypted yet.
+    pub fn disable_encryption(&mut self) {
+        self.header.is_encrypt = false;
+        self.key_source = None;
+    }
+
+    /// Returns the encryption algorithm, key and IV used to encrypt or decrypt the boot image.
+    ///
+    /// Unless a custom key is given, the user key of the image header (`user_key1`) is used,
+    /// falling back to `BOOT_DEFAULT_USER_KEY1`.
+    fn get_cipher<'a>(&'a self, source: &'a KeySource) -> Result<ImageCipher<'a>, Error> {
+        source.image_cipher(Some(
+            self.header
+                .get_user_key1()
+                .unwrap_or(BOOT_DEFAULT_USER_KEY1),
+        ))
+    }
+
+    /// Decrypts the entry header and text of this boot image.
+    ///
+    /// The given key source is kept, so the boot image will be encrypted again the same way
+    /// when it is written. Use `disable_encryption` to write the plaintext image instead.
+    /// Boot images that are not encrypted are not modified.
+    ///
+    /// Note that a wrong key can only be detected by verifying the hash of the image, which
+    /// is calculated over the encrypted data.
+    ///
+    /// # Arguments:
+    /// - `source`: Specifies where to take the key material from.
+    ///
+    /// # Returns:
+    /// - `Ok(())` if the boot image was decrypted successfully.
+    /// - `Err(Error)` if the data could not be decrypted or parsed.
+    ///
+    /// # Example
+    /// ```
+    /// use amebazii::types::{from_stream, set_default_segment_size, to_bytes, BootImage, KeySource};
+    /// use std::io::Cursor;
+    ///
+    /// let text: Vec<u8> = (0..0x100).map(|i| i as u8).collect();
+    /// let mut image = BootImage::default();
+    /// image.set_text(text.clone());
+    /// set_default_segment_size(&mut image);
+    /// image.enable_encryption(KeySource::default());
+    /// let encrypted = to_bytes(&image).unwrap();
+    /// assert!(!encrypted.windows(text.len()).any(|window| window == &text[..]));
+    ///
+    /// let mut image: BootImage = from_stream(&mut Cursor::new(&encrypted)).unwrap();
+    /// assert!(image.is_encrypted());
+    /// image.decrypt(&KeySource::default()).unwrap();
+    /// assert!(!image.is_encrypted());
+    /// assert_eq!(&image.get_text()[..text.len()], &text[..]);
+    /// assert_eq!(to_bytes(&image).unwrap(), encrypted);
+    /// ```
+    pub fn decrypt(&mut self, source: &KeySource) -> Result<(), Error> {
+        if let Some(data) = &self.encrypted {
+            let (algo, key, iv) = self.get_cipher(source)?;
+            let plain = algo.decrypt(data, key, iv)?;
+
+            let mut reader = Cursor::new(&plain);
+            self.entry = from_stream(&mut reader)?;
+            self.text = plain[EntryHeader::binary_size()..].to_vec();
+            self.encrypted = None;
+            self.key_source = Some(source.clone());
+        }
+        Ok(())
+    }
+
+    /// Verifies the hash of this boot image against its raw binary data.
+    ///
+    /// # Arguments:
+    /// - `reader`: The stream this image was read from, positioned at the start of the image.
+    /// - `key`: The key used to compute the HMAC SHA-256 hash (usually `HASH_KEY`).
+    ///
+    /// # Returns:
+    /// - `Ok(VerifyReport)` containing the expected and actual hash.
+    /// - `Err(Error)` if the image data could not be read.
+    pub fn verify<R>(&self, reader: &mut R, key: &[u8]) -> Result<VerifyReport, Error>
+    where
+        R: io::Read + io::Seek,
+    {
+        let buffer = self.read_hashed_data(reader)?;
+
+        let mut report = VerifyReport::default();
+        report.add_check(
+            CheckKind::ImageHash,
+            CheckValue::Data(hmac_sha256(key, &buffer)?.to_vec()),
+            CheckValue::Data(self.hash.to_vec()),
+        );
+        Ok(report)
+    }
+
+    /// Identifies the key and hash algorithm used to create the hash of this boot image.
+    ///
+    /// # Arguments:
+    /// - `reader`: The stream this image was read from, positioned at the start of the image.
+    /// - `keys`: The keys to try (see [`KnownKey::defaults`]).
+    ///
+    /// # Returns:
+    /// - `Ok(KeyIdentification)` containing the matching key and algorithm (if any).
+    /// - `Err(Error)` if the image data could not be read.
+    pub fn identify_key<R>(
+        &self,
+        reader: &mut R,
+        keys: &[KnownKey],
+    ) -> Result<KeyIdentification, Error>
+    where
+        R: io::Read + io::Seek,
+    {
+        let buffer = self.read_hashed_data(reader)?;
+        identify_key(CheckKind::ImageHash, &buffer, &self.hash, keys)
+    }
+
+    /// Reads the raw data covered by the hash, which includes the key block, header and
+    /// the (possibly encrypted) segment.
+    fn read_hashed_data<R>(&self, reader: &mut R) -> Result<Vec<u8>, Error>
+    where
+        R: io::Read + io::Seek,
+    {
+        let mut buffer = vec![
+            0x00;
+            KeyBlock::binary_size()
+                + ImageHeader::binary_size()
+                + self.header.segment_size as usize
+        ];
+        reader.read_exact(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Writes the entry header and text padded to the segment size, encrypting them if
+    /// encryption is enabled for this boot image.
+    fn write_payload<W>(&self, writer: &mut W) -> Result<(), Error>
+    where
+        W: std::io::Write + std::io::Seek,
+    {
+        if let Some(data) = &self.encrypted {
+            writer.write_all(data)?;
+            return Ok(());
+        }
+
+        // Pad the text to the segment size (multiple of 0x20 bytes)
+        let mut buffer = vec![0x00; self.header.segment_size as usize];
+        let mut buf_writer = Cursor::new(&mut buffer);
+        self.entry.write_to(&mut buf_writer)?;
+        buf_writer.write_all(&self.text)?;
+
+        match &self.key_source {
+            Some(source) if self.header.is_encrypt => {
+                let (algo, key, iv) = self.get_cipher(source)?;
+                writer.write_all(&algo.encrypt(&buffer, key, iv)?)?;
+            }
+            _ => writer.write_all(&buffer)?,
+        }
+        Ok(())
+    }
+}
 
 impl FromStream for BootImage {
     /// Reads a `BootImage` from a binary stream.
@@ -118,15 +317,21 @@ impl FromStream for BootImage {
         self.keyblock.read_from(reader)?;
         self.header.read_from(reader)?;
 
-        // TODO: add support for encrypted boot images
-        self.entry.read_from(reader)?;
+        if self.header.is_encrypt {
+            // The entry header and text will be parsed when the image is decrypted
+            let mut data = vec![0x00; self.header.segment_size as usize];
+            reader.read_exact(&mut data)?;
+            self.encrypted = Some(data);
+        } else {
+            self.entry.read_from(reader)?;
 
-        // Resize the `text` field to match the segment size in the header, then read it
-        self.text.resize(
-            self.header.segment_size as usize - EntryHeader::binary_size(),
-            0x00,
-        );
-        reader.read_exact(&mut self.text)?;
+            // Resize the `text` field to match the segment size in the header, then read it
+            self.text.resize(
+                self.header.segment_size as usize - EntryHeader::binary_size(),
+                0x00,
+            );
+            reader.read_exact(&mut self.text)?;
+        }
 
         // Skip any padding (aligned to 0x20 bytes)
         skip_aligned(reader, 0x20)?;
@@ -145,6 +350,10 @@ impl AsImage for BootImage {
     /// # Returns:
     /// - `u32`: The computed segment size.
     fn build_segment_size(&self) -> u32 {
+        if let Some(data) = &self.encrypted {
+            return data.len() as u32;
+        }
+
         // Segment size is the sum of the header size, entry size, text size, and hash size.
         // You can adjust this formula if your BootImage structure needs additional fields.
         let new_size = self.text.len() as u32 + EntryHeader::binary_size() as u32;
@@ -164,31 +373,16 @@ impl AsImage for BootImage {
 
     /// Computes the signature for the BootImage.
     ///
-    /// This method computes the signature (e.g., HMAC or checksum) for the `BootImage` using the provided key.
+    /// This method computes the signature (e.g., HMAC or checksum) for the `BootImage` using the provided signer.
     ///
     /// # Arguments:
-    /// - `key`: The key used to compute the signature.
+    /// - `signer`: The signer used to compute the signature.
     ///
     /// # Returns:
     /// - `Result<Vec<u8>, crate::error::Error>`: The computed signature as a vector of bytes.
-    fn build_signature(&self, key: Option<&[u8]>) -> Result<Vec<u8>, crate::error::Error> {
-        let mut buffer = vec![
-            0x00;
-            KeyBlock::binary_size()
-                + ImageHeader::binary_size()
-                + self.build_segment_size() as usize
-        ];
-        let mut writer = Cursor::new(&mut buffer);
-
-        // Serialize the components of the BootImage into a buffer
-        self.keyblock.write_to(&mut writer)?;
-        self.header.write_to(&mut writer)?;
-        self.entry.write_to(&mut writer)?;
-        writer.write_all(&self.text)?;
-        write_aligned!(&mut writer, 0x20, 0x00, optional);
-
+    fn build_signature_with(&self, signer: &dyn Signer) -> Result<Vec<u8>, crate::error::Error> {
         // The signature is generated using HMAC or any other algorithm.
-        Ok(hmac_sha256(key.unwrap(), &buffer)?.to_vec())
+        signer.sign(HashAlgo::Sha256, &self.build_signed_data()?)
     }
 
     /// Sets the signature for the BootImage.
@@ -202,6 +396,26 @@ impl AsImage for BootImage {
     }
 }
 
+impl BootImage {
+    /// Builds the data covered by the boot image signature, which includes the key block,
+    /// the header, the entry header and the text (including padding).
+    fn build_signed_data(&self) -> Result<Vec<u8>, Error> {
+        let mut buffer = Vec::with_capacity(
+            KeyBlock::binary_size()
+                + ImageHeader::binary_size()
+                + self.header.segment_size as usize,
+        );
+        let mut writer = Cursor::new(&mut buffer);
+
+        // Serialize the components of the BootImage into a buffer (the payload is padded
+        // to the segment size of the header)
+        self.keyblock.write_to(&mut writer)?;
+        self.header.write_to(&mut writer)?;
+        self.write_payload(&mut writer)?;
+        Ok(buffer)
+    }
+}
+
 impl ToStream for BootImage {
     /// Writes a `BootImage` to a binary stream.
     ///
@@ -216,18 +430,8 @@ impl ToStream for BootImage {
     {
         self.keyblock.write_to(writer)?;
         self.header.write_to(writer)?;
-        self.entry.write_to(writer)?;
-        writer.write_all(&self.text)?;
-
-        // Pad the text to a multiple of 0x20 bytes
-        let text_len = self.text.len();
-        if self.header.segment_size > text_len as u32 {
-            writer.write_all(&vec![
-                0x00;
-                (self.header.segment_size - text_len as u32) as usize
-            ])?;
-        }
-
+        // The entry header and text (with padding applied) will be encrypted if enabled
+        self.write_payload(writer)?;
         writer.write_all(&self.hash)?;
         Ok(())
     }
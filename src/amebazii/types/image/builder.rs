@@ -0,0 +1,326 @@
+//! Fluent builders for OTA images.
+//!
+//! The binary layout of an OTA image contains many fields that are derived from the
+//! stored data (lengths, offsets, segment sizes, hashes and the checksum). These builders
+//! only take the actual content of an image and compute all derived fields when the image
+//! is finalized (see [`OTAImageBuilder::finalize`]).
+//!
+//! # Example
+//! ```
+//! use amebazii::{
+//!     keystore::KeyStore,
+//!     types::{ImageType, OTAImageBuilder, SectionBuilder, SectionType, SubImageBuilder},
+//! };
+//!
+//! let keys = KeyStore::new();
+//! let image = OTAImageBuilder::new()
+//!     .subimage(
+//!         SubImageBuilder::new(ImageType::FHWSS).serial(1).section(
+//!             SectionBuilder::new(SectionType::SRAM)
+//!                 .load_address(0x1000_0480)
+//!                 .data(vec![0x00; 0x100]),
+//!         ),
+//!     )
+//!     .subimage(
+//!         SubImageBuilder::new(ImageType::Xip).section(
+//!             SectionBuilder::new(SectionType::XIP)
+//!                 .load_address(0x9B00_0140)
+//!                 .data(vec![0x00; 0x100]),
+//!         ),
+//!     )
+//!     .finalize(&keys)
+//!     .unwrap();
+//!
+//! // the second sub-image starts at a 0x4000 byte boundary
+//! assert_eq!(image.get_subimage(0).unwrap().header.next_offset, Some(0x4000 - 224));
+//! assert!(image.checksum.is_some());
+//! ```
+
+use crate::{
+    error::Error,
+    keys::{XIP_IV, XIP_KEY},
+    keystore::KeyStore,
+    signer::{KeySigner, Signer},
+    types::{
+        enums::{HashAlgo, ImageType, SectionType, XipPageRemapSize},
+        fst::FST,
+        header::{EntryHeader, ImageHeader},
+        section::Section,
+        BinarySize, DataType,
+    },
+    util::x25519_public_key,
+};
+
+use super::{set_default_segment_size, EncryptedOr, OTAImage, SubImage};
+
+/// Builder for a single section of a sub-image.
+///
+/// The section length, the entry header length and the offset to the next section are
+/// computed when the sub-image is built.
+#[derive(Debug)]
+pub struct SectionBuilder {
+    section: Section,
+}
+
+impl SectionBuilder {
+    /// Creates a new builder for a section of the given type.
+    ///
+    /// `XIP` sections store the default XIP key and IV (see [`crate::keys::XIP_KEY`]) and
+    /// `SRAM` sections use their load address as entry address unless another one is set.
+    ///
+    /// # Arguments:
+    /// - `sect_type`: The type of the section.
+    pub fn new(sect_type: SectionType) -> Self {
+        let mut section = Section::default();
+        section.header.sect_type = sect_type;
+        if sect_type == SectionType::XIP {
+            section.header.set_xip_key(Some(*XIP_KEY));
+            section.header.set_xip_iv(Some(*XIP_IV));
+        }
+        Self { section }
+    }
+
+    /// Sets the address the section data is loaded to.
+    pub fn load_address(mut self, address: u32) -> Self {
+        self.section.entry_header.load_address = address;
+        self
+    }
+
+    /// Sets the entry address of the section.
+    pub fn entry_address(mut self, address: Option<u32>) -> Self {
+        self.section.entry_header.entry_address = address;
+        self
+    }
+
+    /// Sets the XIP page remap size.
+    pub fn xip_page_size(mut self, size: XipPageRemapSize) -> Self {
+        self.section.header.xip_page_size = size;
+        self
+    }
+
+    /// Sets the XIP block size.
+    pub fn xip_block_size(mut self, size: u8) -> Self {
+        self.section.header.xip_block_size = size;
+        self
+    }
+
+    /// Sets the XIP key and IV stored in the section header.
+    pub fn xip_key_iv(mut self, key: DataType<16>, iv: DataType<16>) -> Self {
+        self.section.header.set_xip_key(key);
+        self.section.header.set_xip_iv(iv);
+        self
+    }
+
+    /// Sets the raw data of the section.
+    pub fn data(mut self, data: Vec<u8>) -> Self {
+        self.section.set_data(data);
+        self
+    }
+
+    /// Builds the section and computes its lengths.
+    ///
+    /// The offset to the next section is not set, because it depends on the sub-image
+    /// this section belongs to.
+    pub fn build(mut self) -> Section {
+        let length = self.section.get_data().len();
+        self.section.header.length = (EntryHeader::binary_size() + length) as u32;
+        self.section.entry_header.length = length as u32;
+        if self.section.header.sect_type == SectionType::SRAM
+            && self.section.entry_header.entry_address.is_none()
+        {
+            // RAM code starts at the RAM function table
+            self.section.entry_header.entry_address = Some(self.section.entry_header.load_address);
+        }
+        self.section
+    }
+}
+
+/// Builder for a sub-image of an OTA image.
+///
+/// The segment size, the offset to the next sub-image and the hash are computed when the
+/// OTA image is finalized.
+#[derive(Debug)]
+pub struct SubImageBuilder {
+    header: ImageHeader,
+    fst: FST,
+    sections: Vec<SectionBuilder>,
+}
+
+impl SubImageBuilder {
+    /// Creates a new builder for a sub-image of the given type with serial `0` and an
+    /// unencrypted FST using SHA-256.
+    ///
+    /// # Arguments:
+    /// - `img_type`: The type of the sub-image.
+    pub fn new(img_type: ImageType) -> Self {
+        Self {
+            header: ImageHeader {
+                img_type,
+                serial: 0,
+                ..ImageHeader::default()
+            },
+            fst: FST::default(),
+            sections: Vec::new(),
+        }
+    }
+
+    /// Replaces the image header. The segment size and next offset will be overwritten
+    /// when the OTA image is finalized.
+    pub fn header(mut self, header: ImageHeader) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Sets the serial number (version) of the sub-image.
+    pub fn serial(mut self, serial: u32) -> Self {
+        self.header.serial = serial;
+        self
+    }
+
+    /// Sets the first user key of the sub-image.
+    pub fn user_key1(mut self, key: DataType<32>) -> Self {
+        self.header.set_user_key1(key);
+        self
+    }
+
+    /// Sets the second user key of the sub-image.
+    pub fn user_key2(mut self, key: DataType<32>) -> Self {
+        self.header.set_user_key2(key);
+        self
+    }
+
+    /// Replaces the firmware security table (FST) of the sub-image.
+    pub fn fst(mut self, fst: FST) -> Self {
+        self.fst = fst;
+        self
+    }
+
+    /// Appends a section to the sub-image.
+    pub fn section(mut self, section: SectionBuilder) -> Self {
+        self.sections.push(section);
+        self
+    }
+
+    /// Builds the sub-image, links all sections and computes the segment size.
+    ///
+    /// The offset to the next sub-image and the hash are left unset.
+    ///
+    /// # Returns:
+    /// - `Ok(SubImage)`: The unsigned sub-image.
+    /// - `Err(Error)`: If the sub-image does not contain any section.
+    pub fn build(self) -> Result<SubImage, Error> {
+        if self.sections.is_empty() {
+            return Err(Error::InvalidState(format!(
+                "SubImageBuilder: {:?} sub-image does not contain any section",
+                self.header.img_type
+            )));
+        }
+
+        let mut subimage = SubImage::default();
+        subimage.header = self.header;
+        subimage.header.next_offset = None;
+        subimage.fst = EncryptedOr::Plain(self.fst);
+
+        let count = self.sections.len();
+        for (idx, builder) in self.sections.into_iter().enumerate() {
+            let mut section = builder.build();
+            section.header.next_offset = if idx + 1 < count {
+                Some(section.build_aligned_size())
+            } else {
+                None
+            };
+            subimage.add_section(section);
+        }
+        set_default_segment_size(&mut subimage);
+        Ok(subimage)
+    }
+}
+
+/// Builder for a signed OTA image.
+#[derive(Debug, Default)]
+pub struct OTAImageBuilder {
+    subimages: Vec<SubImageBuilder>,
+    public_keys: [DataType<32>; 5],
+}
+
+impl OTAImageBuilder {
+    /// Creates a new, empty builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a sub-image to the OTA image.
+    pub fn subimage(mut self, subimage: SubImageBuilder) -> Self {
+        self.subimages.push(subimage);
+        self
+    }
+
+    /// Sets the public key at the given index (0-4).
+    pub fn public_key(mut self, index: u8, key: DataType<32>) -> Self {
+        self.public_keys[index as usize] = key;
+        self
+    }
+
+    /// Builds and signs the OTA image using the firmware hash key of the given key store.
+    ///
+    /// See [`OTAImageBuilder::finalize_with`] for details.
+    pub fn finalize(self, keys: &KeyStore) -> Result<OTAImage, Error> {
+        let signer = KeySigner::new(Some(keys.get_fw_hash_key()));
+        self.finalize_with(keys, &signer)
+    }
+
+    /// Builds the OTA image, computes all derived fields and signs it using the given signer.
+    ///
+    /// The following fields are computed:
+    /// - section lengths and offsets (see [`SubImageBuilder::build`]),
+    /// - the offset to the next sub-image, so that each sub-image except the last one is
+    ///   aligned to 0x4000 bytes,
+    /// - the key block, storing the public key of the encryption private key
+    ///   (see [`KeyStore::get_enc_priv_key`]),
+    /// - the OTA signature, the hash of each sub-image (the hash of the first sub-image
+    ///   also covers the OTA signature and all public keys) and the checksum.
+    ///
+    /// # Arguments:
+    /// - `keys`: The key store.
+    /// - `signer`: The signer used to compute all hashes, which should use the firmware
+    ///   hash key (see [`KeyStore::get_fw_hash_key`]).
+    ///
+    /// # Returns:
+    /// - `Ok(OTAImage)`: The signed OTA image (see [`OTAImage::normalize_with`]).
+    /// - `Err(Error)`: If the image contains no sub-image, a sub-image contains no section,
+    ///   a hash algorithm other than SHA-256 is used or the image could not be signed.
+    pub fn finalize_with(self, keys: &KeyStore, signer: &dyn Signer) -> Result<OTAImage, Error> {
+        if self.subimages.is_empty() {
+            return Err(Error::InvalidState(
+                "OTAImageBuilder: image does not contain any sub-image".to_string(),
+            ));
+        }
+        // OTA signature and sub-image hashes are 32 bytes long
+        if self
+            .subimages
+            .iter()
+            .any(|subimage| subimage.fst.hash_algo != Some(HashAlgo::Sha256))
+        {
+            return Err(Error::NotImplemented(
+                "OTAImageBuilder: only SHA-256 is supported as hash algorithm".to_string(),
+            ));
+        }
+
+        let mut image = OTAImage::default();
+        image
+            .keyblock
+            .get_hash_pubkey_mut()
+            .copy_from_slice(&x25519_public_key(keys.get_enc_priv_key())?);
+        for (index, key) in self.public_keys.iter().enumerate() {
+            image.set_public_key(index as u8, *key);
+        }
+
+        for builder in self.subimages {
+            image.add_subimage(builder.build()?);
+        }
+
+        // offsets between sub-images and all hashes
+        image.normalize_with(signer)?;
+        Ok(image)
+    }
+}
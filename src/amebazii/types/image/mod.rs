@@ -1,10 +1,20 @@
-use super::{FromStream, ToStream};
-use crate::error::Error;
+use super::{
+    enums::{EncryptionAlgo, PartitionType},
+    FromStream, ToStream,
+};
+use crate::{
+    error::Error,
+    keystore::KeyStore,
+    signer::{KeySigner, Signer},
+};
 use std::io;
 
 pub mod boot;
 pub use boot::BootImage;
 
+pub mod builder;
+pub use builder::{OTAImageBuilder, SectionBuilder, SubImageBuilder};
+
 pub mod ota;
 pub use ota::{OTAImage, SubImage};
 
@@ -13,6 +23,60 @@ pub use pt::{PartTab, PartitionTableImage, Record, TrapConfig};
 
 pub type RawImage = Vec<u8>;
 
+/// The encryption algorithm, key and optional IV used to encrypt or decrypt image data.
+pub(crate) type ImageCipher<'a> = (EncryptionAlgo, &'a [u8], Option<&'a [u8]>);
+
+/// Describes where the key material used to decrypt an encrypted image is taken from.
+///
+/// Encrypted sub-images store the FST and all sections as ciphertext. The FST is always
+/// decrypted first, because it defines the encryption algorithm (and possibly the cipher
+/// key and IV) of the following sections.
+#[derive(Debug, Clone, Default)]
+pub enum KeySource {
+    /// The FST is decrypted using the user key from the image header, the sections are
+    /// decrypted using the cipher key and IV stored in the FST (falls back to the user
+    /// key if the FST does not define a valid cipher key).
+    #[default]
+    Fst,
+
+    /// The user key from the image header is used for both, the FST and all sections.
+    UserKey,
+
+    /// A caller-supplied AES key (16, 24 or 32 bytes) and optional IV is used for the FST
+    /// and all sections.
+    Custom(Vec<u8>, Option<Vec<u8>>),
+}
+
+impl KeySource {
+    /// Returns the encryption algorithm, key and IV for images that are encrypted as a whole,
+    /// such as the partition table and boot image.
+    ///
+    /// `KeySource::Fst` and `KeySource::UserKey` both select the given user key in AES-ECB
+    /// mode. A custom key is used in AES-CBC mode if an IV is given, in AES-ECB mode otherwise.
+    ///
+    /// # Arguments:
+    /// - `user_key`: The user key of the image (if any).
+    ///
+    /// # Returns:
+    /// - `Ok(ImageCipher)` containing the algorithm, key and optional IV.
+    /// - `Err(Error::InvalidState)` if no user key is available.
+    pub(crate) fn image_cipher<'a>(
+        &'a self,
+        user_key: Option<&'a [u8; 32]>,
+    ) -> Result<ImageCipher<'a>, Error> {
+        match self {
+            KeySource::Custom(key, Some(iv)) => Ok((EncryptionAlgo::Cbc, key, Some(iv))),
+            KeySource::Custom(key, None) => Ok((EncryptionAlgo::Ecb, key, None)),
+            _ => match user_key {
+                Some(key) => Ok((EncryptionAlgo::Ecb, key, None)),
+                None => Err(Error::InvalidState(
+                    "No valid user key in image header".to_string(),
+                )),
+            },
+        }
+    }
+}
+
 /// A generic enum representing either encrypted or plain data.
 ///
 /// The `EncryptedOr` enum is used to differentiate between encrypted data and unencrypted (plain) data.
@@ -213,7 +277,38 @@ pub trait AsImage {
     /// ```rust
     /// let signature = image.build_signature(&key);
     /// ```
-    fn build_signature(&self, key: Option<&[u8]>) -> Result<Vec<u8>, crate::error::Error>;
+    fn build_signature(&self, key: Option<&[u8]>) -> Result<Vec<u8>, crate::error::Error> {
+        self.build_signature_with(&KeySigner::new(key))
+    }
+
+    /// Computes the signature for the image using the hash key of the given partition from
+    /// the key store (see [`KeyStore::get_partition_hash_key`]).
+    ///
+    /// # Arguments:
+    /// - `keys`: The key store providing the hash key.
+    /// - `part_type`: The partition this image is stored in (e.g. `PartitionType::Boot`).
+    ///
+    /// # Returns:
+    /// - `Result<Vec<u8>, crate::error::Error>`: The signature as a `Vec<u8>`, or an error.
+    fn build_signature_from(
+        &self,
+        keys: &KeyStore,
+        part_type: PartitionType,
+    ) -> Result<Vec<u8>, crate::error::Error> {
+        self.build_signature_with(&keys.get_signer(part_type))
+    }
+
+    /// Computes the signature for the image using the provided signer.
+    ///
+    /// The signer receives the exact data covered by the signature, which allows the
+    /// signature to be computed outside this process (see [`crate::signer`]).
+    ///
+    /// # Arguments:
+    /// - `signer`: The signer used to compute the signature.
+    ///
+    /// # Returns:
+    /// - `Result<Vec<u8>, crate::error::Error>`: The signature as a `Vec<u8>`, or an error.
+    fn build_signature_with(&self, signer: &dyn Signer) -> Result<Vec<u8>, crate::error::Error>;
 
     /// Sets the signature for the image.
     ///
@@ -274,6 +369,44 @@ where
     Ok(())
 }
 
+/// Sets the signature for a given image using the provided signer.
+///
+/// # Arguments:
+/// - `image`: The image-like object that implements `AsImage`.
+/// - `signer`: The signer used to compute the signature.
+///
+/// # Returns:
+/// - `Result<(), crate::error::Error>`: An empty result on success, or an error.
+pub fn set_signature_with<I>(image: &mut I, signer: &dyn Signer) -> Result<(), crate::error::Error>
+where
+    I: AsImage,
+{
+    let signature = image.build_signature_with(signer)?;
+    image.set_signature(&signature);
+    Ok(())
+}
+
+/// Sets the signature for a given image using the hash key of the given partition from the
+/// key store (see [`AsImage::build_signature_from`]).
+///
+/// # Arguments:
+/// - `image`: The image-like object that implements `AsImage`.
+/// - `keys`: The key store providing the hash key.
+/// - `part_type`: The partition this image is stored in.
+///
+/// # Returns:
+/// - `Result<(), crate::error::Error>`: An empty result on success, or an error.
+pub fn set_signature_from<I>(
+    image: &mut I,
+    keys: &KeyStore,
+    part_type: PartitionType,
+) -> Result<(), crate::error::Error>
+where
+    I: AsImage,
+{
+    set_signature_with(image, &keys.get_signer(part_type))
+}
+
 /// Builds the segment size for a given image.
 ///
 /// # Arguments:
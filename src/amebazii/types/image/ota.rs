@@ -39,25 +39,32 @@
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use std::{
     io::{self, Cursor, Write},
+    ops::Range,
     vec,
 };
 
 use crate::{
+    conf::PatchCfg,
     error::Error,
     is_valid_data,
+    keys::{KnownKey, DEFAULT_VALID_PATTERN},
+    keystore::KeyStore,
+    signer::{KeySigner, Signer},
     types::{
-        enums::HashAlgo,
+        enums::{EncryptionAlgo, HashAlgo},
         from_stream,
         fst::FST,
-        header::{ImageHeader, KeyBlock},
+        header::{EntryHeader, ImageHeader, KeyBlock},
         section::Section,
+        to_bytes,
+        verify::{identify_key, CheckKind, CheckValue, KeyIdentification, VerifyReport},
         BinarySize, DataRefType, DataType, FromStream, ToStream,
     },
     util::{skip_aligned, write_fill},
     write_aligned, write_data, write_padding,
 };
 
-use super::{AsImage, EncryptedOr};
+use super::{AsImage, EncryptedOr, ImageCipher, KeySource};
 
 /// Represents a sub-image, including a header, FST (Firmware Security Table), sections, and hash for integrity verification.
 ///
@@ -67,7 +74,6 @@ pub struct SubImage {
     /// The header of the sub-image containing general information about the sub-image.
     pub header: ImageHeader,
 
-    // REVISIT: this struct does not cover the use-case of an encrypted sub-image!!
     /// The Firmware Security Table (FST) associated with the sub-image.
     pub fst: EncryptedOr<FST>,
 
@@ -76,6 +82,12 @@ pub struct SubImage {
 
     /// The hash of the sub-image used for integrity verification.
     hash: [u8; 32],
+
+    /// The key source used to encrypt the sub-image when it is written.
+    key_source: Option<KeySource>,
+
+    /// Indicates that the FST is stored as plaintext even though the image is encrypted.
+    plain_fst: bool,
 }
 
 impl Default for SubImage {
@@ -86,12 +98,15 @@ impl Default for SubImage {
     /// - The `fst` is initialized with the default value of `FST`.
     /// - The `sections` is an empty vector.
     /// - The `hash` is set to an array of 32 `0xFF` bytes (indicating an uninitialized or invalid hash).
+    /// - Encryption is disabled.
     fn default() -> Self {
         SubImage {
             header: ImageHeader::default(),
             fst: EncryptedOr::Plain(FST::default()),
             sections: EncryptedOr::Plain(Vec::new()),
             hash: [0xFF; 32],
+            key_source: None,
+            plain_fst: false,
         }
     }
 }
@@ -229,6 +244,209 @@ impl SubImage {
     }
 }
 
+// cryptographic ops
+impl SubImage {
+    /// Returns `true` if the FST or the sections of this sub-image are still encrypted.
+    pub fn is_encrypted(&self) -> bool {
+        self.fst.is_encrypted() || self.sections.is_encrypted()
+    }
+
+    /// Returns the key source used to encrypt this sub-image when it is written.
+    ///
+    /// # Returns:
+    /// - `Some(&KeySource)` if the sub-image will be encrypted on write, `None` otherwise.
+    pub fn get_key_source(&self) -> Option<&KeySource> {
+        self.key_source.as_ref()
+    }
+
+    /// Enables encryption of this sub-image.
+    ///
+    /// The FST and all sections are encrypted when the sub-image is written (and when its
+    /// signature is built), so the hash will be calculated over the encrypted image. This
+    /// method updates the encryption flags of the image header and the FST.
+    ///
+    /// # Arguments:
+    /// - `algo`: The encryption algorithm to use for all sections.
+    /// - `source`: Specifies where to take the key material from.
+    pub fn enable_encryption(&mut self, algo: EncryptionAlgo, source: KeySource) {
+        self.header.is_encrypt = true;
+        if let EncryptedOr::Plain(fst) = &mut self.fst {
+            fst.enc_algo = Some(algo);
+        }
+        self.key_source = Some(source);
+    }
+
+    /// Disables encryption of this sub-image, which will be written as plaintext afterward.
+    ///
+    /// This method only updates the encryption flags of the image header and the FST. It has
+    /// no effect on the content of a sub-image that has not been decrypted yet.
+    pub fn disable_encryption(&mut self) {
+        self.header.is_encrypt = false;
+        if let EncryptedOr::Plain(fst) = &mut self.fst {
+            fst.enc_algo = None;
+        }
+        self.key_source = None;
+        self.plain_fst = false;
+    }
+
+    /// Returns the user key of the image header that is used for encryption.
+    ///
+    /// Application images store their key in `user_key2`, whereas `user_key1` is used
+    /// as a fallback. The ROM-loaded images (boot image and partition table) prefer
+    /// `user_key1` instead (see `PartitionTableImage::get_cipher`).
+    fn get_user_key(&self) -> Result<&[u8; 32], Error> {
+        self.header
+            .get_user_key2()
+            .or(self.header.get_user_key1())
+            .ok_or(Error::InvalidState(
+                "SubImage: no valid user key in image header".to_string(),
+            ))
+    }
+
+    /// Returns the key used to encrypt or decrypt the FST.
+    fn get_fst_key<'a>(&'a self, source: &'a KeySource) -> Result<&'a [u8], Error> {
+        match source {
+            KeySource::Custom(key, _) => Ok(key),
+            _ => Ok(self.get_user_key()?),
+        }
+    }
+
+    /// Returns the encryption algorithm, key and IV used to encrypt or decrypt all sections.
+    fn get_section_cipher<'a>(&'a self, source: &'a KeySource) -> Result<ImageCipher<'a>, Error> {
+        let fst: &FST = self.fst.as_ref();
+        let algo = fst.enc_algo.ok_or(Error::InvalidState(
+            "SubImage: FST does not define an encryption algorithm".to_string(),
+        ))?;
+
+        let (key, iv): (&[u8], Option<&[u8]>) = match source {
+            KeySource::Custom(key, iv) => (key, iv.as_deref()),
+            KeySource::Fst if fst.is_cipher_key_iv_valid() => (
+                fst.get_cipher_key().unwrap(),
+                fst.get_cipher_iv().map(|iv| iv.as_slice()),
+            ),
+            _ => (
+                self.get_user_key()?,
+                fst.get_cipher_iv().map(|iv| iv.as_slice()),
+            ),
+        };
+        Ok((algo, key, iv))
+    }
+
+    /// Decrypts the FST and all sections of this sub-image.
+    ///
+    /// The FST is decrypted first (AES-ECB) using either the user key from the image header
+    /// or the caller-supplied key. Some images store a plain FST even though encryption is
+    /// enabled, which is detected by checking the FST validation pattern. Afterward, the
+    /// section stream is decrypted with the `EncryptionAlgo` defined in the FST and parsed
+    /// into individual sections.
+    ///
+    /// The given key source is kept, so the sub-image will be encrypted again the same way
+    /// when it is written. Use `disable_encryption` to write the plaintext image instead.
+    /// Sub-images that are not encrypted are not modified.
+    ///
+    /// # Arguments:
+    /// - `source`: Specifies where to take the key material from.
+    ///
+    /// # Returns:
+    /// - `Ok(())` if the sub-image was decrypted successfully.
+    /// - `Err(Error)` if no key is available, the FST does not define an encryption
+    ///   algorithm or the decrypted data could not be parsed (e.g. wrong key).
+    ///
+    /// # Example
+    /// ```
+    /// use amebazii::types::{from_stream, to_bytes, EncryptionAlgo, KeySource, OTAImage};
+    /// use std::io::Cursor;
+    ///
+    /// let plain = include_bytes!("../../../../assets/fw1.bin");
+    /// let source = KeySource::Custom(vec![0x42; 32], Some(vec![0x24; 16]));
+    ///
+    /// let mut image: OTAImage = from_stream(&mut Cursor::new(plain)).unwrap();
+    /// for subimage in image.get_subimages_mut() {
+    ///     subimage.enable_encryption(EncryptionAlgo::Cbc, source.clone());
+    /// }
+    /// let encrypted = to_bytes(&image).unwrap();
+    /// assert_ne!(&encrypted[..], &plain[..]);
+    ///
+    /// // parse -> decrypt -> re-encrypt yields the same bytes
+    /// let mut image: OTAImage = from_stream(&mut Cursor::new(&encrypted)).unwrap();
+    /// assert!(image.get_subimages().iter().all(|s| s.is_encrypted()));
+    /// for subimage in image.get_subimages_mut() {
+    ///     subimage.decrypt(&source).unwrap();
+    ///     assert!(!subimage.is_encrypted());
+    /// }
+    /// assert_eq!(to_bytes(&image).unwrap(), encrypted);
+    ///
+    /// // writing the decrypted image as plaintext restores the original
+    /// for subimage in image.get_subimages_mut() {
+    ///     subimage.disable_encryption();
+    /// }
+    /// assert_eq!(to_bytes(&image).unwrap(), plain);
+    /// ```
+    pub fn decrypt(&mut self, source: &KeySource) -> Result<(), Error> {
+        if !self.is_encrypted() {
+            return Ok(());
+        }
+
+        if let EncryptedOr::Encrypted(data) = &self.fst {
+            self.plain_fst = data.get(8..16) == Some(&DEFAULT_VALID_PATTERN[..]);
+            let plain = if self.plain_fst {
+                data.clone()
+            } else {
+                EncryptionAlgo::Ecb.decrypt(data, self.get_fst_key(source)?, None)?
+            };
+            self.fst = EncryptedOr::Plain(from_stream(&mut Cursor::new(plain))?);
+        }
+
+        if let EncryptedOr::Encrypted(data) = &self.sections {
+            let (algo, key, iv) = self.get_section_cipher(source)?;
+            let plain = algo.decrypt(data, key, iv)?;
+
+            let mut reader = Cursor::new(plain);
+            let mut sections = Vec::new();
+            loop {
+                let section: Section = from_stream(&mut reader)?;
+                let has_next = section.header.has_next();
+
+                sections.push(section);
+                if !has_next {
+                    break;
+                }
+            }
+            self.sections = EncryptedOr::Plain(sections);
+        }
+        self.key_source = Some(source.clone());
+        Ok(())
+    }
+
+    /// Writes the FST and all sections to the given stream, encrypting them if encryption
+    /// is enabled for this sub-image.
+    fn write_payload<W>(&self, writer: &mut W) -> Result<(), Error>
+    where
+        W: io::Write + io::Seek,
+    {
+        match &self.key_source {
+            Some(source) if self.header.is_encrypt && !self.is_encrypted() => {
+                let fst_data = to_bytes(&self.fst)?;
+                if self.plain_fst {
+                    writer.write_all(&fst_data)?;
+                } else {
+                    let key = self.get_fst_key(source)?;
+                    writer.write_all(&EncryptionAlgo::Ecb.encrypt(&fst_data, key, None)?)?;
+                }
+
+                let (algo, key, iv) = self.get_section_cipher(source)?;
+                let sections_data = to_bytes(&self.sections)?;
+                writer.write_all(&algo.encrypt(&sections_data, key, iv)?)?;
+            }
+            _ => {
+                self.fst.write_to(writer)?;
+                self.sections.write_to(writer)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 impl AsImage for SubImage {
     /// Set the signature for the SubImage.
     ///
@@ -273,13 +491,13 @@ impl AsImage for SubImage {
     /// including the header, firmware security table (FST), and sections.
     ///
     /// # Arguments:
-    /// - `key`: A byte slice containing the key used to generate the signature.
+    /// - `signer`: The signer used to generate the signature.
     ///
     /// # Returns:
     /// A `Result<Vec<u8>, crate::error::Error>` that contains:
     /// - `Ok(Vec<u8>)`: The signature as a byte vector.
     /// - `Err(Error)`: An error if signature calculation fails (e.g., unsupported hash algorithm).
-    fn build_signature(&self, key: Option<&[u8]>) -> Result<Vec<u8>, crate::error::Error> {
+    fn build_signature_with(&self, signer: &dyn Signer) -> Result<Vec<u8>, crate::error::Error> {
         let hash_algo = match &self.fst {
             // TODO: what should we use in case of an encrypted FST?
             EncryptedOr::Encrypted(_) => Some(HashAlgo::Sha256),
@@ -291,12 +509,11 @@ impl AsImage for SubImage {
 
         // Write the header, FST, and sections to the buffer.
         self.header.write_to(&mut writer)?;
-        self.fst.write_to(&mut writer)?;
-        self.sections.write_to(&mut writer)?;
+        self.write_payload(&mut writer)?;
 
         // Compute the hash using the FST's hash algorithm.
         match hash_algo {
-            Some(algo) => Ok(algo.compute_hash(&buffer, key)?.to_vec()),
+            Some(algo) => signer.sign(algo, &buffer),
             None => Err(Error::NotImplemented(
                 "SubImage::build_signature".to_string(),
             )),
@@ -354,8 +571,7 @@ impl ToStream for SubImage {
         W: io::Write + io::Seek,
     {
         self.header.write_to(writer)?;
-        self.fst.write_to(writer)?;
-        self.sections.write_to(writer)?;
+        self.write_payload(writer)?;
         writer.write_all(&self.hash)?;
 
         let align = if self.header.has_next() { 0x4000 } else { 0x40 };
@@ -477,6 +693,34 @@ impl OTAImage {
     pub fn get_public_keys(&self) -> &[DataType<32>; 5] {
         &self.public_keys
     }
+
+    /// Sets the public key at the given index (0-4).
+    ///
+    /// # Arguments:
+    /// - `index`: The index of the public key.
+    /// - `key`: The new public key or `None` to clear it.
+    pub fn set_public_key(&mut self, index: u8, key: DataType<32>) {
+        self.public_keys[index as usize] = key;
+    }
+
+    /// Decrypts all encrypted subimages of this OTA image.
+    ///
+    /// See [`SubImage::decrypt`] for details on how each subimage is decrypted.
+    ///
+    /// # Arguments:
+    /// - `source`: Specifies where to take the key material from.
+    ///
+    /// # Returns:
+    /// - `Ok(())` if all subimages are plain afterward, or an error if one of them could
+    ///   not be decrypted.
+    pub fn decrypt(&mut self, source: &KeySource) -> Result<(), Error> {
+        for subimage in self.subimages.iter_mut() {
+            if subimage.is_encrypted() {
+                subimage.decrypt(source)?;
+            }
+        }
+        Ok(())
+    }
 }
 
 // cryptographic ops
@@ -490,6 +734,37 @@ impl OTAImage {
     /// - `Result<Vec<u8>, crate::error::Error>`: The computed signature as a vector of bytes on success,
     ///   or an error if the computation cannot be completed (e.g., `fst.hash_algo` is `None`).
     pub fn build_ota_signature(&self, key: Option<&[u8]>) -> Result<Vec<u8>, crate::error::Error> {
+        self.build_ota_signature_with(&KeySigner::new(key))
+    }
+
+    /// Builds the OTA image signature using the firmware hash key of the given key store
+    /// (see [`KeyStore::get_fw_hash_key`]).
+    ///
+    /// # Arguments:
+    /// - `keys`: The key store providing the firmware hash key.
+    ///
+    /// # Returns:
+    /// - `Result<Vec<u8>, crate::error::Error>`: The computed signature on success, or an error
+    ///   if the computation cannot be completed.
+    pub fn build_ota_signature_from(
+        &self,
+        keys: &KeyStore,
+    ) -> Result<Vec<u8>, crate::error::Error> {
+        self.build_ota_signature_with(&KeySigner::new(Some(keys.get_fw_hash_key())))
+    }
+
+    /// Builds the OTA image signature using the provided signer (see [`OTAImage::build_ota_signature`]).
+    ///
+    /// # Arguments:
+    /// - `signer`: The signer used to compute the hash of the first SubImage's header.
+    ///
+    /// # Returns:
+    /// - `Result<Vec<u8>, crate::error::Error>`: The computed signature as a vector of bytes on success,
+    ///   or an error if the computation cannot be completed (e.g., `fst.hash_algo` is `None`).
+    pub fn build_ota_signature_with(
+        &self,
+        signer: &dyn Signer,
+    ) -> Result<Vec<u8>, crate::error::Error> {
         let mut buffer = Vec::with_capacity(ImageHeader::binary_size());
         // according to spec:
         // OTA signature: The hash result of the 1st Image header “Sub FW Image 0 Header”
@@ -504,7 +779,7 @@ impl OTAImage {
                 if let Some(algo) = &fst.hash_algo {
                     let mut writer = Cursor::new(&mut buffer);
                     subimage.header.write_to(&mut writer)?;
-                    return algo.compute_hash(&buffer, key);
+                    return signer.sign(*algo, &buffer);
                 } else {
                     return Err(Error::NotImplemented(
                         "OTAImage::build_ota_signature: subimage[0].fst.hash_algo is None"
@@ -522,14 +797,52 @@ impl OTAImage {
         ))
     }
 
+    /// Builds the hash of the first SubImage, which also covers the OTA signature and all
+    /// public keys (see [`OTAImage::build_first_signature_with`]).
+    ///
+    /// # Arguments:
+    /// - `key`: An optional key to be used in the hash calculation.
+    ///
+    /// # Returns:
+    /// - `Result<Vec<u8>, crate::error::Error>`: The computed hash of the first SubImage.
     pub fn build_first_signature(
         &self,
         key: Option<&[u8]>,
+    ) -> Result<Vec<u8>, crate::error::Error> {
+        self.build_first_signature_with(&KeySigner::new(key))
+    }
+
+    /// Builds the hash of the first SubImage using the firmware hash key of the given key
+    /// store (see [`KeyStore::get_fw_hash_key`]).
+    ///
+    /// # Arguments:
+    /// - `keys`: The key store providing the firmware hash key.
+    ///
+    /// # Returns:
+    /// - `Result<Vec<u8>, crate::error::Error>`: The computed hash of the first SubImage.
+    pub fn build_first_signature_from(
+        &self,
+        keys: &KeyStore,
+    ) -> Result<Vec<u8>, crate::error::Error> {
+        self.build_first_signature_with(&KeySigner::new(Some(keys.get_fw_hash_key())))
+    }
+
+    /// Builds the hash of the first SubImage, which also covers the OTA signature and all
+    /// public keys, using the provided signer.
+    ///
+    /// # Arguments:
+    /// - `signer`: The signer used to compute the OTA signature and the hash.
+    ///
+    /// # Returns:
+    /// - `Result<Vec<u8>, crate::error::Error>`: The computed hash of the first SubImage.
+    pub fn build_first_signature_with(
+        &self,
+        signer: &dyn Signer,
     ) -> Result<Vec<u8>, crate::error::Error> {
         // Hash: calculated with Encrypted FW image if image encryption is on
         // The 1st sub-image
         //  From OTA Signature to the last image section, including all padding bytes
-        let ota_signature = self.build_ota_signature(key)?;
+        let ota_signature = self.build_ota_signature_with(signer)?;
         if let Some(subimage) = self.get_subimage(0) {
             if let EncryptedOr::Plain(fst) = &subimage.fst {
                 if let Some(algo) = &fst.hash_algo {
@@ -553,10 +866,9 @@ impl OTAImage {
                     }
                     // signature does not include the hash itself
                     subimage.header.write_to(&mut writer)?;
-                    subimage.fst.write_to(&mut writer)?;
-                    subimage.sections.write_to(&mut writer)?;
+                    subimage.write_payload(&mut writer)?;
                     // build hash without existing subimage hash
-                    return algo.compute_hash(&buffer, key);
+                    return signer.sign(*algo, &buffer);
                 } else {
                     return Err(Error::NotImplemented(
                         "OTAImage::build_ota_signature: subimage[0].fst.hash_algo is None"
@@ -617,8 +929,9 @@ impl OTAImage {
 
     /// Computes the checksum for the OTA image by writing it to a buffer.
     ///
-    /// This method serializes the current `OTAImage` object into a byte buffer and then calculates
-    /// the checksum for the serialized data. The checksum is returned as a 32-bit unsigned integer.
+    /// This method serializes the current `OTAImage` object (without checksum)
+    /// into a byte buffer and then calculates the checksum for the serialized data. The checksum is
+    /// returned as a 32-bit unsigned integer.
     ///
     /// # Errors
     ///
@@ -627,7 +940,7 @@ impl OTAImage {
         let mut buffer = Vec::new();
         let mut cursor = std::io::Cursor::new(&mut buffer);
 
-        self.write_to(&mut cursor)?;
+        self.write_image(&mut cursor)?;
         Ok(OTAImage::checksum_from_buffer(&buffer))
     }
 
@@ -664,13 +977,343 @@ impl OTAImage {
     ///
     /// Returns an error if the signing process fails.
     pub fn update_ota_signature(&mut self, key: Option<&[u8]>) -> Result<(), Error> {
-        let new_signature = OTAImage::build_ota_signature(self, key)?;
+        self.update_ota_signature_with(&KeySigner::new(key))
+    }
+
+    /// Updates the OTA image signature using the provided signer.
+    ///
+    /// # Arguments
+    ///
+    /// * `signer` - The signer used to compute the new OTA signature.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the signing process fails.
+    pub fn update_ota_signature_with(&mut self, signer: &dyn Signer) -> Result<(), Error> {
+        let new_signature = self.build_ota_signature_with(signer)?;
         self.keyblock
             .get_enc_pubkey_mut()
             .copy_from_slice(&new_signature);
         Ok(())
     }
 
+    /// Recomputes all hashes of this OTA image using the firmware hash key of the given key
+    /// store (see [`OTAImage::update_signatures_with`]).
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - The key store providing the firmware hash key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if one of the hashes could not be computed.
+    pub fn update_signatures_from(&mut self, keys: &KeyStore) -> Result<(), Error> {
+        self.update_signatures_with(&KeySigner::new(Some(keys.get_fw_hash_key())))
+    }
+
+    /// Recomputes all hashes of this OTA image using the provided signer.
+    ///
+    /// This updates the OTA signature, the hash of each SubImage (the hash of the first SubImage
+    /// also covers the OTA signature and all public keys) and finally the checksum.
+    ///
+    /// # Arguments
+    ///
+    /// * `signer` - The signer used to compute all hashes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if one of the hashes could not be computed.
+    pub fn update_signatures_with(&mut self, signer: &dyn Signer) -> Result<(), Error> {
+        self.update_ota_signature_with(signer)?;
+        let first_signature = self.build_first_signature_with(signer)?;
+        for (idx, subimage) in self.subimages.iter_mut().enumerate() {
+            if idx == 0 {
+                subimage.set_signature(&first_signature);
+            } else {
+                super::set_signature_with(subimage, signer)?;
+            }
+        }
+        self.update_checksum()
+    }
+
+    /// Recomputes all structural fields of this OTA image and signs it again using the
+    /// firmware hash key of the given key store.
+    ///
+    /// See [`OTAImage::normalize_with`] for details.
+    pub fn normalize(&mut self, keys: &KeyStore) -> Result<VerifyReport, Error> {
+        self.normalize_with(&KeySigner::new(Some(keys.get_fw_hash_key())))
+    }
+
+    /// Recomputes all structural fields of this OTA image and signs it again using the
+    /// provided signer.
+    ///
+    /// This is required after the image has been modified, e.g. after section data has
+    /// been replaced or sections and subimages have been added or removed. The following
+    /// fields are recomputed:
+    /// - the length and next offset of each section (plain subimages only),
+    /// - the segment size of each subimage,
+    /// - the next offset of each subimage, so that all subimages except the last one are
+    ///   aligned to 0x4000 bytes (the padding is derived from it when the image is written),
+    /// - the OTA signature, all subimage hashes and the checksum.
+    ///
+    /// # Arguments:
+    /// - `signer`: The signer used to compute all hashes.
+    ///
+    /// # Returns:
+    /// - `Ok(VerifyReport)` containing the new (expected) and previous (actual) value of
+    ///   each field. Failed checks describe the fields that have been changed.
+    /// - `Err(Error)` if one of the hashes could not be computed.
+    ///
+    /// # Example
+    /// ```
+    /// use amebazii::keystore::KeyStore;
+    /// use amebazii::signer::KeySigner;
+    /// use amebazii::types::{from_stream, to_bytes, OTAImage};
+    /// use std::io::Cursor;
+    ///
+    /// let data = include_bytes!("../../../../assets/fw1.bin");
+    /// let mut image: OTAImage = from_stream(&mut Cursor::new(data)).unwrap();
+    ///
+    /// // a valid image is not changed
+    /// let signer = KeySigner::new(Some(KeyStore::new().get_fw_hash_key()));
+    /// let report = image.normalize_with(&signer).unwrap();
+    /// assert!(report.is_ok());
+    /// assert_eq!(to_bytes(&image).unwrap(), data);
+    /// ```
+    pub fn normalize_with(&mut self, signer: &dyn Signer) -> Result<VerifyReport, Error> {
+        let mut report = VerifyReport::default();
+        let count = self.subimages.len();
+        // key block and public keys
+        let mut offset = 224;
+        for (idx, subimage) in self.subimages.iter_mut().enumerate() {
+            if !subimage.is_encrypted() {
+                let sections = subimage.get_sections_mut();
+                let sect_count = sections.len();
+                for (sect_idx, section) in sections.iter_mut().enumerate() {
+                    let length = section.get_data().len() as u32;
+                    normalize_number(
+                        &mut report,
+                        CheckKind::SectionLength(idx, sect_idx),
+                        &mut section.header.length,
+                        EntryHeader::binary_size() as u32 + length,
+                    );
+                    normalize_number(
+                        &mut report,
+                        CheckKind::EntryLength(idx, sect_idx),
+                        &mut section.entry_header.length,
+                        length,
+                    );
+                    let next_offset = if sect_idx + 1 < sect_count {
+                        Some(section.build_aligned_size())
+                    } else {
+                        None
+                    };
+                    normalize_offset(
+                        &mut report,
+                        CheckKind::SectionNextOffset(idx, sect_idx),
+                        &mut section.header.next_offset,
+                        next_offset,
+                    );
+                }
+            }
+
+            let segment_size = subimage.build_segment_size();
+            normalize_number(
+                &mut report,
+                CheckKind::SegmentSize(idx),
+                &mut subimage.header.segment_size,
+                segment_size,
+            );
+
+            let next_offset = if idx + 1 < count {
+                // header, FST and sections followed by the hash
+                let size = ImageHeader::binary_size() + segment_size as usize + 32;
+                let next_offset = (offset + size).next_multiple_of(0x4000) - offset;
+                offset += next_offset;
+                Some(next_offset as u32)
+            } else {
+                None
+            };
+            normalize_offset(
+                &mut report,
+                CheckKind::NextOffset(idx),
+                &mut subimage.header.next_offset,
+                next_offset,
+            );
+        }
+
+        let old_ota_signature = self.get_ota_signature().to_vec();
+        let old_hashes: Vec<Vec<u8>> = self
+            .subimages
+            .iter()
+            .map(|subimage| subimage.get_hash().to_vec())
+            .collect();
+        let old_checksum = self.checksum;
+        self.update_signatures_with(signer)?;
+
+        report.add_check(
+            CheckKind::OtaSignature,
+            CheckValue::Data(self.get_ota_signature().to_vec()),
+            CheckValue::Data(old_ota_signature),
+        );
+        for (idx, old_hash) in old_hashes.into_iter().enumerate() {
+            report.add_check(
+                CheckKind::SubImageHash(idx),
+                CheckValue::Data(self.subimages[idx].get_hash().to_vec()),
+                CheckValue::Data(old_hash),
+            );
+        }
+        report.add_check(
+            CheckKind::Checksum,
+            CheckValue::Number(self.checksum.unwrap_or(0xFFFF_FFFF)),
+            CheckValue::Number(old_checksum.unwrap_or(0xFFFF_FFFF)),
+        );
+        Ok(report)
+    }
+
+    /// Resolves a virtual (runtime) address to the section storing it.
+    ///
+    /// Encrypted subimages are skipped.
+    ///
+    /// # Arguments:
+    /// - `address`: The virtual address.
+    /// - `length`: The number of bytes that must be stored in the same section.
+    ///
+    /// # Returns:
+    /// - `Some((subimage, section, offset))` containing the subimage index, the section index
+    ///   and the offset within the section data.
+    /// - `None` if no section contains the whole address range.
+    pub fn resolve_address(&self, address: u32, length: usize) -> Option<(usize, usize, usize)> {
+        let address = address as u64;
+        for (idx, subimage) in self.subimages.iter().enumerate() {
+            if subimage.is_encrypted() {
+                continue;
+            }
+            for (sect_idx, section) in subimage.get_sections().iter().enumerate() {
+                let start = section.entry_header.load_address as u64;
+                let end = start + section.get_data().len() as u64;
+                if start <= address && address + length as u64 <= end {
+                    return Some((idx, sect_idx, (address - start) as usize));
+                }
+            }
+        }
+        None
+    }
+
+    /// Applies all patches of the given patch file and signs the image again.
+    ///
+    /// Each patch is resolved through the load address of all sections (see
+    /// [`OTAImage::resolve_address`]). The original bytes are compared with the stored data
+    /// before they are replaced. SCE-encrypted XIP sections are not supported, because their
+    /// data is stored encrypted. Afterward, all hashes and the checksum are updated using the
+    /// provided signer.
+    ///
+    /// All patches are checked against the unmodified image before any data is replaced, so
+    /// the image is left unchanged if a patch fails.
+    ///
+    /// # Arguments:
+    /// - `patch`: The patches to apply.
+    /// - `signer`: The signer used to compute all hashes.
+    ///
+    /// # Returns:
+    /// - `Ok(Vec<(usize, usize, usize)>)` containing the resolved location of each patch.
+    /// - `Err(Error)` if a patch could not be resolved, targets an SCE-encrypted section, its
+    ///   original bytes do not match or the image could not be signed.
+    ///
+    /// # Example
+    /// ```
+    /// use amebazii::conf::{PatchCfg, PatchItemCfg};
+    /// use amebazii::keystore::KeyStore;
+    /// use amebazii::types::{from_stream, to_bytes, OTAImage, PartitionType};
+    /// use std::io::Cursor;
+    ///
+    /// let data = include_bytes!("../../../../assets/fw1.bin");
+    /// let mut image: OTAImage = from_stream(&mut Cursor::new(data)).unwrap();
+    /// let section = &image.get_subimages()[1].get_sections()[0];
+    /// let address = section.entry_header.load_address + 0x100;
+    /// let stored = |image: &OTAImage| {
+    ///     image.get_subimages()[1].get_sections()[0].get_data()[0x100..0x104].to_vec()
+    /// };
+    /// let original = stored(&image);
+    /// let replacement: Vec<u8> = original.iter().map(|byte| !byte).collect();
+    /// let item = |original: &[u8]| PatchItemCfg {
+    ///     address,
+    ///     original: original.to_vec(),
+    ///     replacement: replacement.clone(),
+    ///     description: None,
+    /// };
+    /// let keys = KeyStore::new();
+    /// let signer = keys.get_signer(PartitionType::Fw1);
+    ///
+    /// // a failing patch leaves the image unchanged
+    /// let patch = PatchCfg { patches: vec![item(&original), item(&[0x00; 4])] };
+    /// assert!(image.apply_patch(&patch, &signer).is_err());
+    /// assert_eq!(stored(&image), original);
+    ///
+    /// let patch = PatchCfg { patches: vec![item(&original)] };
+    /// assert_eq!(image.apply_patch(&patch, &signer).unwrap(), vec![(1, 0, 0x100)]);
+    /// assert_eq!(stored(&image), replacement);
+    ///
+    /// let patched = to_bytes(&image).unwrap();
+    /// assert_ne!(patched, data);
+    /// assert!(image.verify(&mut Cursor::new(&patched), &keys).unwrap().is_ok());
+    /// ```
+    pub fn apply_patch(
+        &mut self,
+        patch: &PatchCfg,
+        signer: &dyn Signer,
+    ) -> Result<Vec<(usize, usize, usize)>, Error> {
+        let mut locations = Vec::with_capacity(patch.patches.len());
+        for item in &patch.patches {
+            if item.original.len() != item.replacement.len() {
+                return Err(Error::InvalidState(format!(
+                    "Patch at 0x{:08x}: original and replacement must have the same length ({} != {})",
+                    item.address,
+                    item.original.len(),
+                    item.replacement.len()
+                )));
+            }
+
+            let Some((idx, sect_idx, offset)) =
+                self.resolve_address(item.address, item.original.len())
+            else {
+                return Err(Error::InvalidState(format!(
+                    "Patch at 0x{:08x}: address is not stored in any (decrypted) section",
+                    item.address
+                )));
+            };
+
+            let section = &self.subimages[idx].get_sections()[sect_idx];
+            if section.is_sce_enabled() {
+                // the stored data is encrypted
+                return Err(Error::NotImplemented(format!(
+                    "Patch at 0x{:08x}: patching SCE-encrypted sections is not supported",
+                    item.address
+                )));
+            }
+
+            let data = &section.get_data()[offset..offset + item.original.len()];
+            if data != item.original.as_slice() {
+                return Err(Error::InvalidState(format!(
+                    "Patch at 0x{:08x}: original bytes do not match (expected {}, found {})",
+                    item.address,
+                    hex::encode(&item.original),
+                    hex::encode(data)
+                )));
+            }
+            locations.push((idx, sect_idx, offset));
+        }
+
+        for (item, &(idx, sect_idx, offset)) in patch.patches.iter().zip(&locations) {
+            let section = &mut self.subimages[idx].get_sections_mut()[sect_idx];
+            section.get_data_mut()[offset..offset + item.replacement.len()]
+                .copy_from_slice(&item.replacement);
+        }
+
+        self.update_signatures_with(signer)?;
+        Ok(locations)
+    }
+
     /// Calculates a checksum from a byte buffer by summing all the byte values and applying a bitmask.
     ///
     /// # Arguments:
@@ -689,6 +1332,7 @@ impl OTAImage {
     ///
     /// # Returns:
     /// - `Result<i32, Error>`: The checksum computed from the stream as a 32-bit signed integer, or an error if the reading fails.
+    /// - `Err(Error::InvalidState)` if the stream is too short to contain a checksum.
     pub fn checksum_from_stream<R>(reader: &mut R) -> Result<u32, Error>
     where
         R: io::Read + io::Seek,
@@ -696,8 +1340,270 @@ impl OTAImage {
         let mut buffer = Vec::new();
         // we assume this reader is at pos 0
         reader.read_to_end(&mut buffer)?;
-        Ok(OTAImage::checksum_from_buffer(&buffer[..&buffer.len() - 4]))
+
+        if buffer.len() < 4 {
+            return Err(Error::InvalidState(
+                "OTA image is too short to contain a checksum".to_string(),
+            ));
+        }
+        Ok(OTAImage::checksum_from_buffer(&buffer[..buffer.len() - 4]))
+    }
+
+    /// Writes the key block, the public keys and all subimages (i.e. the image without
+    /// checksum) to the given stream.
+    fn write_image<W>(&self, writer: &mut W) -> Result<(), Error>
+    where
+        W: io::Write + io::Seek,
+    {
+        self.keyblock.write_to(writer)?;
+        for key in &self.public_keys {
+            write_data!(writer, key, 32);
+        }
+        for subimage in &self.subimages {
+            subimage.write_to(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the hash algorithm of the given subimage.
+    ///
+    /// Encrypted FSTs do not reveal their hash algorithm, so SHA-256 is assumed in this case.
+    fn get_hash_algo(subimage: &SubImage) -> HashAlgo {
+        match &subimage.fst {
+            EncryptedOr::Plain(fst) => fst.hash_algo.unwrap_or(HashAlgo::Sha256),
+            EncryptedOr::Encrypted(_) => HashAlgo::Sha256,
+        }
+    }
+
+    /// Computes the ranges of raw image data covered by the OTA signature and each subimage
+    /// hash, relative to the start of the image.
+    ///
+    /// # Returns:
+    /// - The hashed ranges, starting with the OTA signature followed by one range per subimage.
+    /// - The end of the last subimage, which is where the checksum is stored.
+    fn build_hashed_ranges(&self) -> (Vec<(CheckKind, Range<usize>)>, usize) {
+        let header_size = ImageHeader::binary_size();
+        let mut ranges = Vec::with_capacity(self.subimages.len() + 1);
+        let mut offset = 224;
+        for (idx, subimage) in self.subimages.iter().enumerate() {
+            let segment_size = subimage.header.segment_size as usize;
+            if idx == 0 {
+                // the OTA signature covers the header of the first subimage only and
+                // the hash of the first subimage starts at the OTA signature
+                ranges.push((CheckKind::OtaSignature, offset..offset + header_size));
+                ranges.push((
+                    CheckKind::SubImageHash(idx),
+                    0..offset + header_size + segment_size,
+                ));
+            } else {
+                ranges.push((
+                    CheckKind::SubImageHash(idx),
+                    offset..offset + header_size + segment_size,
+                ));
+            }
+
+            match subimage.header.next_offset {
+                Some(next_offset) => offset += next_offset as usize,
+                // the last subimage (including its hash) is aligned to 0x40 bytes
+                None => offset += (header_size + segment_size + 32).next_multiple_of(0x40),
+            }
+        }
+        (ranges, offset)
+    }
+
+    /// Returns the hash stored in this image for the given check.
+    fn get_stored_hash(&self, kind: &CheckKind) -> &[u8] {
+        match kind {
+            CheckKind::SubImageHash(idx) => self.subimages[*idx].get_hash(),
+            _ => self.get_ota_signature(),
+        }
+    }
+
+    /// Reads the raw image data from the given stream.
+    fn read_image_data<R>(reader: &mut R) -> Result<Vec<u8>, Error>
+    where
+        R: io::Read + io::Seek,
+    {
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Returns the given range of raw image data.
+    fn get_image_data(buffer: &[u8], range: Range<usize>) -> Result<&[u8], Error> {
+        buffer.get(range.clone()).ok_or_else(|| {
+            Error::InvalidState(format!(
+                "OTA image is truncated (expected data at 0x{:x}..0x{:x})",
+                range.start, range.end
+            ))
+        })
+    }
+
+    /// Verifies this OTA image against its raw binary data.
+    ///
+    /// All hashes and the checksum are computed from the raw data, so that the result does not
+    /// depend on how the parsed image would be serialized again. The report contains the OTA
+    /// signature, the hash and segment size of each subimage, the valid patterns of all plain
+    /// FSTs and section headers as well as the checksum (if present).
+    ///
+    /// # Arguments:
+    /// - `reader`: The stream this image was read from, positioned at the start of the image.
+    /// - `keys`: The key store providing the firmware hash key (see
+    ///   [`KeyStore::get_fw_hash_key`]).
+    ///
+    /// # Returns:
+    /// - `Ok(VerifyReport)` containing expected and actual values of each check.
+    /// - `Err(Error)` if the image data could not be read.
+    ///
+    /// # Example
+    /// ```
+    /// use amebazii::keystore::KeyStore;
+    /// use amebazii::types::{from_stream, OTAImage};
+    /// use std::io::{Cursor, Seek};
+    ///
+    /// let mut reader = Cursor::new(include_bytes!("../../../../assets/fw1.bin"));
+    /// let image: OTAImage = from_stream(&mut reader).unwrap();
+    ///
+    /// reader.rewind().unwrap();
+    /// let report = image.verify(&mut reader, &KeyStore::new()).unwrap();
+    /// assert!(report.is_ok());
+    /// ```
+    pub fn verify<R>(&self, reader: &mut R, keys: &KeyStore) -> Result<VerifyReport, Error>
+    where
+        R: io::Read + io::Seek,
+    {
+        self.verify_with_key(reader, Some(keys.get_fw_hash_key()))
+    }
+
+    /// Verifies this OTA image using the given hash key (see [`OTAImage::verify`]).
+    ///
+    /// # Arguments:
+    /// - `reader`: The stream this image was read from, positioned at the start of the image.
+    /// - `key`: The hash key used to compute the OTA signature and all subimage hashes.
+    ///
+    /// # Returns:
+    /// - `Ok(VerifyReport)` containing expected and actual values of each check.
+    /// - `Err(Error)` if the image data could not be read.
+    pub fn verify_with_key<R>(
+        &self,
+        reader: &mut R,
+        key: Option<&[u8]>,
+    ) -> Result<VerifyReport, Error>
+    where
+        R: io::Read + io::Seek,
+    {
+        let buffer = OTAImage::read_image_data(reader)?;
+        let (ranges, end) = self.build_hashed_ranges();
+        let first_algo = self
+            .get_subimage(0)
+            .map(OTAImage::get_hash_algo)
+            .unwrap_or(HashAlgo::Sha256);
+
+        let mut report = VerifyReport::default();
+        for (kind, range) in ranges {
+            let algo = match kind {
+                CheckKind::SubImageHash(idx) => OTAImage::get_hash_algo(&self.subimages[idx]),
+                _ => first_algo,
+            };
+            let hash = algo.compute_hash(OTAImage::get_image_data(&buffer, range)?, key)?;
+            let stored_hash = self.get_stored_hash(&kind).to_vec();
+            report.add_check(
+                kind.clone(),
+                CheckValue::Data(hash),
+                CheckValue::Data(stored_hash),
+            );
+
+            let CheckKind::SubImageHash(idx) = kind else {
+                continue;
+            };
+            let subimage = &self.subimages[idx];
+            report.add_check(
+                CheckKind::SegmentSize(idx),
+                CheckValue::Number(subimage.build_segment_size()),
+                CheckValue::Number(subimage.header.segment_size),
+            );
+
+            if let EncryptedOr::Plain(fst) = &subimage.fst {
+                report.add_check(
+                    CheckKind::FstValidPattern(idx),
+                    CheckValue::Data(DEFAULT_VALID_PATTERN.to_vec()),
+                    CheckValue::Data(fst.get_pattern().to_vec()),
+                );
+            }
+
+            if !subimage.is_encrypted() {
+                for (sect_idx, section) in subimage.get_sections().iter().enumerate() {
+                    report.add_check(
+                        CheckKind::SectionValidPattern(idx, sect_idx),
+                        CheckValue::Data(DEFAULT_VALID_PATTERN.to_vec()),
+                        CheckValue::Data(section.header.get_valid_pattern().to_vec()),
+                    );
+                }
+            }
+        }
+
+        // the checksum directly follows the last subimage
+        if let Some(checksum) = self.checksum {
+            let data = OTAImage::get_image_data(&buffer, 0..end)?;
+            report.add_check(
+                CheckKind::Checksum,
+                CheckValue::Number(OTAImage::checksum_from_buffer(data)),
+                CheckValue::Number(checksum),
+            );
+        }
+        Ok(report)
     }
+
+    /// Identifies the keys and hash algorithms used to create the OTA signature and all
+    /// subimage hashes.
+    ///
+    /// # Arguments:
+    /// - `reader`: The stream this image was read from, positioned at the start of the image.
+    /// - `keys`: The keys to try (see [`KnownKey::defaults`]).
+    ///
+    /// # Returns:
+    /// - `Ok(Vec<KeyIdentification>)` containing the result for each hash.
+    /// - `Err(Error)` if the image data could not be read.
+    pub fn identify_keys<R>(
+        &self,
+        reader: &mut R,
+        keys: &[KnownKey],
+    ) -> Result<Vec<KeyIdentification>, Error>
+    where
+        R: io::Read + io::Seek,
+    {
+        let buffer = OTAImage::read_image_data(reader)?;
+        let (ranges, _) = self.build_hashed_ranges();
+        ranges
+            .into_iter()
+            .map(|(kind, range)| {
+                let hash = self.get_stored_hash(&kind);
+                identify_key(kind, OTAImage::get_image_data(&buffer, range)?, hash, keys)
+            })
+            .collect()
+    }
+}
+
+/// Stores the given value in a numeric field and adds it to the report.
+fn normalize_number(report: &mut VerifyReport, kind: CheckKind, field: &mut u32, value: u32) {
+    report.add_check(kind, CheckValue::Number(value), CheckValue::Number(*field));
+    *field = value;
+}
+
+/// Stores the given offset in an offset field (`None` is stored as `0xFFFF_FFFF`) and adds
+/// it to the report.
+fn normalize_offset(
+    report: &mut VerifyReport,
+    kind: CheckKind,
+    field: &mut Option<u32>,
+    value: Option<u32>,
+) {
+    report.add_check(
+        kind,
+        CheckValue::Number(value.unwrap_or(0xFFFF_FFFF)),
+        CheckValue::Number(field.unwrap_or(0xFFFF_FFFF)),
+    );
+    *field = value;
 }
 
 impl FromStream for OTAImage {
@@ -756,13 +1662,7 @@ impl ToStream for OTAImage {
     where
         W: io::Write + io::Seek,
     {
-        self.keyblock.write_to(writer)?;
-        for key in &self.public_keys {
-            write_data!(writer, key, 32);
-        }
-        for subimage in &self.subimages {
-            subimage.write_to(writer)?;
-        }
+        self.write_image(writer)?;
         if let Some(checksum) = self.checksum {
             writer.write_u32::<LittleEndian>(checksum)?;
         }
@@ -5,18 +5,22 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     error::Error,
-    is_valid_data, read_padding, read_valid_data,
+    is_valid_data,
+    keys::KnownKey,
+    read_padding, read_valid_data,
+    signer::Signer,
     types::{
-        enums::{KeyExportOp, PartitionType},
+        enums::{HashAlgo, KeyExportOp, PartitionType},
         from_stream,
         header::{ImageHeader, KeyBlock},
+        verify::{identify_key, CheckKind, CheckValue, KeyIdentification, VerifyReport},
         BinarySize, DataRefType, DataType, FromStream, ToStream,
     },
     util::{hmac_sha256, write_fill},
     write_data, write_padding,
 };
 
-use super::{AsImage, EncryptedOr};
+use super::{AsImage, EncryptedOr, ImageCipher, KeySource};
 
 /// Represents the configuration of a hardware trap.
 ///
@@ -547,6 +551,9 @@ pub struct PartitionTableImage {
     pub header: ImageHeader,
     pub pt: EncryptedOr<PartTab>,
     hash: [u8; 32],
+
+    /// The key source used to encrypt the partition table when it is written.
+    key_source: Option<KeySource>,
 }
 
 impl FromStream for PartitionTableImage {
@@ -635,6 +642,174 @@ impl PartitionTableImage {
     }
 }
 
+// cryptographic ops
+impl PartitionTableImage {
+    /// Returns `true` if the partition table is still encrypted.
+    pub fn is_encrypted(&self) -> bool {
+        self.pt.is_encrypted()
+    }
+
+    /// Returns the key source used to encrypt the partition table when it is written.
+    ///
+    /// # Returns:
+    /// - `Some(&KeySource)` if the partition table will be encrypted on write, `None` otherwise.
+    pub fn get_key_source(&self) -> Option<&KeySource> {
+        self.key_source.as_ref()
+    }
+
+    /// Enables encryption of the partition table.
+    ///
+    /// The partition table is encrypted when the image is written (and when its signature
+    /// is built), so the HMAC will be calculated over the encrypted data.
+    ///
+    /// # Arguments:
+    /// - `source`: Specifies where to take the key material from.
+    pub fn enable_encryption(&mut self, source: KeySource) {
+        self.header.is_encrypt = true;
+        self.key_source = Some(source);
+    }
+
+    /// Disables encryption of the partition table, which will be written as plaintext afterward.
+    ///
+    /// This method has no effect on a partition table that has not been decrypted yet.
+    pub fn disable_encryption(&mut self) {
+        self.header.is_encrypt = false;
+        self.key_source = None;
+    }
+
+    /// Returns the encryption algorithm, key and IV used to encrypt or decrypt the partition
+    /// table.
+    ///
+    /// Unless a custom key is given, the user key of the image header (`user_key1`, falling
+    /// back to `user_key2`) is used. Like the boot image, the partition table is loaded by the
+    /// ROM and therefore prefers `user_key1`, whereas application images prefer `user_key2`
+    /// (see `SubImage::get_user_key`).
+    fn get_cipher<'a>(&'a self, source: &'a KeySource) -> Result<ImageCipher<'a>, Error> {
+        source.image_cipher(self.header.get_user_key1().or(self.header.get_user_key2()))
+    }
+
+    /// Decrypts the partition table and parses its records.
+    ///
+    /// The given key source is kept, so the partition table will be encrypted again the same
+    /// way when it is written. Use `disable_encryption` to write the plaintext table instead.
+    /// Partition tables that are not encrypted are not modified.
+    ///
+    /// # Arguments:
+    /// - `source`: Specifies where to take the key material from.
+    ///
+    /// # Returns:
+    /// - `Ok(())` if the partition table was decrypted successfully.
+    /// - `Err(Error)` if no key is available or the decrypted data could not be parsed
+    ///   (e.g. wrong key).
+    ///
+    /// # Example
+    /// ```
+    /// use amebazii::types::{from_stream, to_bytes, KeySource, PartitionTableImage};
+    /// use std::io::Cursor;
+    ///
+    /// // skip the calibration pattern
+    /// let data = &include_bytes!("../../../../assets/partition.bin")[0x20..];
+    /// let source = KeySource::Custom(vec![0x42; 32], None);
+    ///
+    /// let mut pt_image: PartitionTableImage = from_stream(&mut Cursor::new(data)).unwrap();
+    /// let plain = to_bytes(&pt_image).unwrap();
+    /// pt_image.enable_encryption(source.clone());
+    /// let encrypted = to_bytes(&pt_image).unwrap();
+    /// assert_ne!(encrypted, plain);
+    ///
+    /// let mut pt_image: PartitionTableImage = from_stream(&mut Cursor::new(&encrypted)).unwrap();
+    /// assert!(pt_image.is_encrypted());
+    /// pt_image.decrypt(&source).unwrap();
+    /// assert!(!pt_image.is_encrypted());
+    /// assert_eq!(to_bytes(&pt_image).unwrap(), encrypted);
+    ///
+    /// pt_image.disable_encryption();
+    /// assert_eq!(to_bytes(&pt_image).unwrap(), plain);
+    /// ```
+    pub fn decrypt(&mut self, source: &KeySource) -> Result<(), Error> {
+        if let EncryptedOr::Encrypted(data) = &self.pt {
+            let (algo, key, iv) = self.get_cipher(source)?;
+            let plain = algo.decrypt(data, key, iv)?;
+
+            let pt: PartTab = from_stream(&mut Cursor::new(plain)).map_err(|e| {
+                Error::MalfromedPartTab(format!(
+                    "Could not parse decrypted data (wrong key?): {}",
+                    e
+                ))
+            })?;
+            self.pt = EncryptedOr::Plain(pt);
+            self.key_source = Some(source.clone());
+        }
+        Ok(())
+    }
+
+    /// Verifies the hash of this partition table against its raw binary data.
+    ///
+    /// # Arguments:
+    /// - `reader`: The stream this image was read from, positioned at the start of the image.
+    /// - `key`: The key used to compute the HMAC SHA-256 hash (usually `HASH_KEY`).
+    ///
+    /// # Returns:
+    /// - `Ok(VerifyReport)` containing the expected and actual hash.
+    /// - `Err(Error)` if the image data could not be read.
+    pub fn verify<R>(&self, reader: &mut R, key: &[u8]) -> Result<VerifyReport, Error>
+    where
+        R: io::Read + io::Seek,
+    {
+        let mut report = VerifyReport::default();
+        report.add_check(
+            CheckKind::ImageHash,
+            CheckValue::Data(self.create_signature(reader, key)?),
+            CheckValue::Data(self.get_hash().to_vec()),
+        );
+        Ok(report)
+    }
+
+    /// Identifies the key and hash algorithm used to create the hash of this partition table.
+    ///
+    /// # Arguments:
+    /// - `reader`: The stream this image was read from, positioned at the start of the image.
+    /// - `keys`: The keys to try (see [`KnownKey::defaults`]).
+    ///
+    /// # Returns:
+    /// - `Ok(KeyIdentification)` containing the matching key and algorithm (if any).
+    /// - `Err(Error)` if the image data could not be read.
+    pub fn identify_key<R>(
+        &self,
+        reader: &mut R,
+        keys: &[KnownKey],
+    ) -> Result<KeyIdentification, Error>
+    where
+        R: io::Read + io::Seek,
+    {
+        // The hash covers the key block, header and the (possibly encrypted) segment
+        let mut buffer =
+            vec![0xFF; 64 + ImageHeader::binary_size() + self.header.segment_size as usize];
+        reader.read_exact(&mut buffer)?;
+        identify_key(CheckKind::ImageHash, &buffer, self.get_hash(), keys)
+    }
+
+    /// Writes the partition table padded to the segment size, encrypting it if encryption
+    /// is enabled for this image.
+    fn write_payload<W>(&self, writer: &mut W) -> Result<(), Error>
+    where
+        W: io::Write + io::Seek,
+    {
+        let mut pt_buffer = vec![0xFF; self.header.segment_size as usize];
+        let mut pt_writer = Cursor::new(&mut pt_buffer);
+        self.pt.write_to(&mut pt_writer)?;
+
+        match &self.key_source {
+            Some(source) if self.header.is_encrypt && !self.is_encrypted() => {
+                let (algo, key, iv) = self.get_cipher(source)?;
+                writer.write_all(&algo.encrypt(&pt_buffer, key, iv)?)?;
+            }
+            _ => writer.write_all(&pt_buffer)?,
+        }
+        Ok(())
+    }
+}
+
 impl AsImage for PartitionTableImage {
     /// Computes the segment size for the partition table image.
     ///
@@ -659,22 +834,15 @@ impl AsImage for PartitionTableImage {
 
     /// Computes the signature for the partition table image.
     ///
-    /// This method generates the HMAC SHA-256 signature for the image using the provided key.
+    /// This method generates the HMAC SHA-256 signature for the image using the provided signer.
     ///
     /// # Arguments:
-    /// - `key`: The key used to compute the HMAC SHA-256 signature.
+    /// - `signer`: The signer used to compute the HMAC SHA-256 signature.
     ///
     /// # Returns:
     /// - `Result<Vec<u8>, crate::error::Error>`: The computed signature as a vector of bytes.
-    fn build_signature(&self, key: Option<&[u8]>) -> Result<Vec<u8>, Error> {
-        let mut buffer =
-            vec![0xFF; 64 + ImageHeader::binary_size() + self.build_segment_size() as usize];
-        let mut writer = Cursor::new(&mut buffer);
-
-        self.keyblock.write_to(&mut writer)?;
-        self.header.write_to(&mut writer)?;
-        self.pt.write_to(&mut writer)?;
-        Ok(hmac_sha256(key.unwrap(), &buffer)?.to_vec())
+    fn build_signature_with(&self, signer: &dyn Signer) -> Result<Vec<u8>, Error> {
+        signer.sign(HashAlgo::Sha256, &self.build_signed_data()?)
     }
 
     /// Sets the signature for the partition table image.
@@ -698,6 +866,22 @@ impl AsImage for PartitionTableImage {
     }
 }
 
+impl PartitionTableImage {
+    /// Builds the data covered by the partition table signature, which includes the key block,
+    /// the header and the (encrypted) partition table.
+    fn build_signed_data(&self) -> Result<Vec<u8>, Error> {
+        let mut buffer =
+            Vec::with_capacity(64 + ImageHeader::binary_size() + self.header.segment_size as usize);
+        let mut writer = Cursor::new(&mut buffer);
+
+        // the payload is padded to the segment size of the header
+        self.keyblock.write_to(&mut writer)?;
+        self.header.write_to(&mut writer)?;
+        self.write_payload(&mut writer)?;
+        Ok(buffer)
+    }
+}
+
 impl Default for PartitionTableImage {
     /// Returns a default `PartitionTableImage` with default values for all fields.
     ///
@@ -717,6 +901,7 @@ impl Default for PartitionTableImage {
             header: ImageHeader::default(),
             pt: EncryptedOr::Plain(PartTab::default()),
             hash: [0xFF; 32],
+            key_source: None,
         }
     }
 }
@@ -742,12 +927,8 @@ impl ToStream for PartitionTableImage {
         self.keyblock.write_to(writer)?;
         self.header.write_to(writer)?;
 
-        // Create a buffer to hold the partition table (with padding applied)
-        let mut pt_buffer = vec![0xFF; self.header.segment_size as usize];
-        let mut pt_writer = Cursor::new(&mut pt_buffer);
-        self.pt.write_to(&mut pt_writer)?;
-
-        writer.write_all(&pt_buffer)?;
+        // The partition table (with padding applied) will be encrypted if enabled
+        self.write_payload(writer)?;
         writer.write_all(&self.hash)?;
         Ok(())
     }
@@ -26,6 +26,11 @@ pub use nvdm::*;
 pub mod sysctrl;
 pub use sysctrl::{FlashInfo, ForceOldImage, SpiConfig, SystemData};
 
+pub mod verify;
+pub use verify::{
+    identify_key, Check, CheckKind, CheckValue, KeyIdentification, MatchedKey, VerifyReport,
+};
+
 /// `DataType` is a type alias for an optional fixed-size array of `u8` bytes.
 ///
 /// This type represents an optional key where the key is an array of `u8` of a fixed size,
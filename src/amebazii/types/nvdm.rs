@@ -678,6 +678,14 @@ impl NVDM {
             .collect()
     }
 
+    /// Returns all data items, regardless of their status.
+    ///
+    /// # Returns
+    /// - A slice of all parsed `DataItem`s.
+    pub fn get_items(&self) -> &[DataItem] {
+        &self.items
+    }
+
     /// Returns a list of unique group names present in the stored data items.
     ///
     /// # Returns
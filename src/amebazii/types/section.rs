@@ -1,11 +1,17 @@
 use std::vec;
 
 use super::{
+    enums::SectionType,
     from_stream,
     header::{EntryHeader, SectionHeader},
     BinarySize, FromStream, ToStream,
 };
-use crate::{error::Error, util::skip_aligned, util::write_fill, write_aligned};
+use crate::{
+    error::Error,
+    keys::{XIP_IV, XIP_KEY},
+    util::{skip_aligned, write_fill},
+    write_aligned,
+};
 
 /// Represents a section in a sub-image.
 ///
@@ -81,6 +87,14 @@ impl Section {
         return &self.data;
     }
 
+    /// Returns a mutable reference to the section's data.
+    ///
+    /// # Returns:
+    /// A mutable slice of the section's data (`&mut [u8]`).
+    pub fn get_data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
     /// Computes the aligned length of the section data, ensuring it is padded to a 0x20-byte boundary.
     ///
     /// This function calculates the total length of the section (including the `EntryHeader` and the
@@ -114,6 +128,26 @@ impl Section {
     pub fn set_data(&mut self, data: Vec<u8>) {
         self.data = data;
     }
+
+    // ------------------------------------------------------------------------------------
+    // Secure Copy Engine (SCE)
+    // ------------------------------------------------------------------------------------
+
+    /// Returns `true` if this section is an XIP section with SCE (flash) encryption enabled.
+    pub fn is_sce_enabled(&self) -> bool {
+        self.header.sect_type == SectionType::XIP && self.header.sce_enabled
+    }
+
+    /// Returns the key and IV used by the Secure Copy Engine for this section.
+    ///
+    /// The key and IV from the section header are used if they are valid, otherwise the
+    /// default `XIP_KEY` and `XIP_IV` will be returned.
+    pub fn get_sce_key_iv(&self) -> (&[u8; 16], &[u8; 16]) {
+        match (self.header.get_xip_key(), self.header.get_xip_iv()) {
+            (Some(key), Some(iv)) if self.header.xip_key_iv_valid() => (key, iv),
+            _ => (XIP_KEY, XIP_IV),
+        }
+    }
 }
 
 impl FromStream for Section {
@@ -0,0 +1,216 @@
+use serde::Serialize;
+use std::fmt;
+
+use super::enums::HashAlgo;
+use crate::{error::Error, keys::KnownKey};
+
+/// Identifies a single check of a verification report.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum CheckKind {
+    /// The hash of an image that is signed as a whole (partition table or boot image).
+    ImageHash,
+
+    /// The OTA signature (hash of the first subimage header).
+    OtaSignature,
+
+    /// The trailing checksum of the OTA image.
+    Checksum,
+
+    /// The hash of the subimage at the given index.
+    SubImageHash(usize),
+
+    /// The segment size stored in the header of the subimage at the given index.
+    SegmentSize(usize),
+
+    /// The offset to the next subimage stored in the header of the subimage at the given index.
+    NextOffset(usize),
+
+    /// The length stored in a section header (subimage index, section index).
+    SectionLength(usize, usize),
+
+    /// The data length stored in the entry header of a section (subimage index, section index).
+    EntryLength(usize, usize),
+
+    /// The offset to the next section stored in a section header (subimage index, section index).
+    SectionNextOffset(usize, usize),
+
+    /// The valid pattern of the FST of the subimage at the given index.
+    FstValidPattern(usize),
+
+    /// The valid pattern of a section header (subimage index, section index).
+    SectionValidPattern(usize, usize),
+}
+
+impl fmt::Display for CheckKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckKind::ImageHash => write!(f, "Image hash"),
+            CheckKind::OtaSignature => write!(f, "OTA signature"),
+            CheckKind::Checksum => write!(f, "Checksum"),
+            CheckKind::SubImageHash(idx) => write!(f, "Subimage {} hash", idx),
+            CheckKind::SegmentSize(idx) => write!(f, "Subimage {} segment size", idx),
+            CheckKind::NextOffset(idx) => write!(f, "Subimage {} next offset", idx),
+            CheckKind::SectionLength(idx, sect_idx) => {
+                write!(f, "Subimage {} section {} length", idx, sect_idx)
+            }
+            CheckKind::EntryLength(idx, sect_idx) => {
+                write!(f, "Subimage {} section {} entry length", idx, sect_idx)
+            }
+            CheckKind::SectionNextOffset(idx, sect_idx) => {
+                write!(f, "Subimage {} section {} next offset", idx, sect_idx)
+            }
+            CheckKind::FstValidPattern(idx) => write!(f, "Subimage {} FST valid pattern", idx),
+            CheckKind::SectionValidPattern(idx, sect_idx) => {
+                write!(f, "Subimage {} section {} valid pattern", idx, sect_idx)
+            }
+        }
+    }
+}
+
+/// A value that has been compared during verification.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum CheckValue {
+    /// Raw data, such as a hash or valid pattern.
+    Data(#[serde(with = "hex")] Vec<u8>),
+
+    /// A numeric value, such as a size or checksum.
+    Number(u32),
+}
+
+impl fmt::Display for CheckValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckValue::Data(data) => write!(f, "{}", hex::encode(data)),
+            CheckValue::Number(value) => write!(f, "0x{:08x}", value),
+        }
+    }
+}
+
+/// The result of a single verification check.
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    /// What has been checked.
+    pub kind: CheckKind,
+
+    /// The value computed from the image data (or the value required by the format).
+    pub expected: CheckValue,
+
+    /// The value stored in the image.
+    pub actual: CheckValue,
+}
+
+impl Check {
+    /// Returns `true` if the stored value matches the expected one.
+    pub fn is_ok(&self) -> bool {
+        self.expected == self.actual
+    }
+}
+
+/// A structured report of all checks performed while verifying an image.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VerifyReport {
+    checks: Vec<Check>,
+}
+
+impl VerifyReport {
+    /// Adds a new check to this report.
+    ///
+    /// # Arguments:
+    /// - `kind`: What has been checked.
+    /// - `expected`: The computed (or required) value.
+    /// - `actual`: The value stored in the image.
+    pub fn add_check(&mut self, kind: CheckKind, expected: CheckValue, actual: CheckValue) {
+        self.checks.push(Check {
+            kind,
+            expected,
+            actual,
+        });
+    }
+
+    /// Returns all checks of this report.
+    pub fn get_checks(&self) -> &[Check] {
+        &self.checks
+    }
+
+    /// Returns an iterator over all failed checks.
+    pub fn get_failures(&self) -> impl Iterator<Item = &Check> {
+        self.checks.iter().filter(|check| !check.is_ok())
+    }
+
+    /// Returns `true` if all checks passed.
+    pub fn is_ok(&self) -> bool {
+        self.checks.iter().all(Check::is_ok)
+    }
+}
+
+/// The key and hash algorithm that reproduced a stored hash.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MatchedKey {
+    /// The name of the matching key, or `None` if the hash was computed without a key.
+    pub name: Option<String>,
+
+    /// The matching hash algorithm.
+    pub algo: HashAlgo,
+}
+
+impl fmt::Display for MatchedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{}, HMAC-{:?}", name, self.algo),
+            None => write!(f, "no key, {:?}", self.algo),
+        }
+    }
+}
+
+/// The result of identifying the key of a single hash.
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyIdentification {
+    /// The hash that has been checked.
+    pub kind: CheckKind,
+
+    /// The key and algorithm that reproduced the hash, if any.
+    pub matched: Option<MatchedKey>,
+}
+
+/// Tries all given keys with SHA-256 and MD5 (and finally without a key) to reproduce a
+/// stored hash.
+///
+/// MD5 hashes only occupy the first 16 bytes of a hash field, so only these bytes are compared.
+///
+/// # Arguments:
+/// - `kind`: The hash that is checked.
+/// - `data`: The raw data covered by the hash.
+/// - `hash`: The stored hash.
+/// - `keys`: The keys to try.
+///
+/// # Returns:
+/// - `Ok(KeyIdentification)` with the first matching key and algorithm (if any).
+pub fn identify_key(
+    kind: CheckKind,
+    data: &[u8],
+    hash: &[u8],
+    keys: &[KnownKey],
+) -> Result<KeyIdentification, Error> {
+    for algo in [HashAlgo::Sha256, HashAlgo::Md5] {
+        let candidates = keys
+            .iter()
+            .map(|key| (Some(key.get_name()), Some(key.get_key())))
+            .chain([(None, None)]);
+
+        for (name, key) in candidates {
+            if hash.starts_with(&algo.compute_hash(data, key)?) {
+                return Ok(KeyIdentification {
+                    kind,
+                    matched: Some(MatchedKey {
+                        name: name.map(str::to_string),
+                        algo,
+                    }),
+                });
+            }
+        }
+    }
+    Ok(KeyIdentification {
+        kind,
+        matched: None,
+    })
+}
@@ -1,8 +1,10 @@
-//! Utility functions for creating signatures and hashes.
+//! Utility functions for creating signatures, hashes and AES operations.
 
+use openssl::derive::Deriver;
 use openssl::md::Md;
 use openssl::md_ctx::MdCtx;
-use openssl::pkey::PKey;
+use openssl::pkey::{Id, PKey};
+use openssl::symm::{Cipher, Crypter, Mode};
 use std::io;
 
 use crate::error;
@@ -186,6 +188,173 @@ pub fn md5(data: &[u8]) -> Result<[u8; 16], error::Error> {
     return Ok(signature);
 }
 
+/// Selects the AES cipher matching the given key length and block mode.
+fn aes_cipher(key: &[u8], cbc: bool) -> Result<Cipher, error::Error> {
+    match (key.len(), cbc) {
+        (16, false) => Ok(Cipher::aes_128_ecb()),
+        (16, true) => Ok(Cipher::aes_128_cbc()),
+        (24, false) => Ok(Cipher::aes_192_ecb()),
+        (24, true) => Ok(Cipher::aes_192_cbc()),
+        (32, false) => Ok(Cipher::aes_256_ecb()),
+        (32, true) => Ok(Cipher::aes_256_cbc()),
+        (len, _) => Err(error::Error::InvalidState(format!(
+            "Invalid AES key length: {}",
+            len
+        ))),
+    }
+}
+
+/// Runs an AES operation in ECB or CBC mode without padding.
+fn aes_crypt(
+    mode: Mode,
+    key: &[u8],
+    iv: Option<&[u8]>,
+    data: &[u8],
+) -> Result<Vec<u8>, error::Error> {
+    if data.len() % 16 != 0 {
+        return Err(error::Error::InvalidState(format!(
+            "AES input length must be a multiple of 16 (got {})",
+            data.len()
+        )));
+    }
+
+    let cipher = aes_cipher(key, iv.is_some())?;
+    let mut crypter = Crypter::new(cipher, mode, key, iv)?;
+    crypter.pad(false);
+
+    let mut output = vec![0; data.len() + cipher.block_size()];
+    let mut count = crypter.update(data, &mut output)?;
+    count += crypter.finalize(&mut output[count..])?;
+    output.truncate(count);
+    Ok(output)
+}
+
+/// Decrypts the provided data using AES in ECB or CBC mode.
+///
+/// The AES variant (128, 192 or 256 bits) is derived from the length of the given key. If an
+/// IV is provided, CBC mode will be used, otherwise ECB. Padding is disabled, so the length of
+/// the input data must be a multiple of the AES block size (16 bytes).
+///
+/// # Parameters
+/// - `key`: A byte slice containing the AES key (16, 24 or 32 bytes).
+/// - `iv`: An optional 16-byte IV. If `None`, ECB mode is used.
+/// - `data`: A byte slice containing the encrypted data.
+///
+/// # Returns
+/// - `Ok(Vec<u8>)`: The decrypted data, which has the same length as the input.
+/// - `Err(error::Error)`: An error if the key or input length is invalid or decryption fails.
+///
+/// # Example
+/// ```
+/// use amebazii::util::aes_decrypt;
+///
+/// let key = [0x11; 32];
+/// let plain = aes_decrypt(&key, None, &[0u8; 32]).unwrap();
+/// assert_eq!(plain.len(), 32);
+/// ```
+pub fn aes_decrypt(key: &[u8], iv: Option<&[u8]>, data: &[u8]) -> Result<Vec<u8>, error::Error> {
+    aes_crypt(Mode::Decrypt, key, iv, data)
+}
+
+/// Encrypts the provided data using AES in ECB or CBC mode.
+///
+/// This is the counterpart to [`aes_decrypt`]. The AES variant is derived from the length of
+/// the given key and CBC mode is used if an IV is provided. The length of the input data must
+/// be a multiple of the AES block size (16 bytes).
+///
+/// # Parameters
+/// - `key`: A byte slice containing the AES key (16, 24 or 32 bytes).
+/// - `iv`: An optional 16-byte IV. If `None`, ECB mode is used.
+/// - `data`: A byte slice containing the plaintext data.
+///
+/// # Returns
+/// - `Ok(Vec<u8>)`: The encrypted data, which has the same length as the input.
+/// - `Err(error::Error)`: An error if the key or input length is invalid or encryption fails.
+///
+/// # Example
+/// ```
+/// use amebazii::util::{aes_decrypt, aes_encrypt};
+///
+/// let key = [0x11; 16];
+/// let iv = [0x22; 16];
+/// let cipher = aes_encrypt(&key, Some(&iv), b"0123456789abcdef").unwrap();
+/// assert_eq!(aes_decrypt(&key, Some(&iv), &cipher).unwrap(), b"0123456789abcdef");
+/// ```
+pub fn aes_encrypt(key: &[u8], iv: Option<&[u8]>, data: &[u8]) -> Result<Vec<u8>, error::Error> {
+    aes_crypt(Mode::Encrypt, key, iv, data)
+}
+
+/// Derives the Ed25519 public key from the provided private key.
+///
+/// # Parameters
+/// - `priv_key`: A byte slice containing the raw 32-byte Ed25519 private key.
+///
+/// # Returns
+/// - `Ok([u8; 32])`: The raw 32-byte Ed25519 public key.
+/// - `Err(error::Error)`: An error if the private key is invalid.
+///
+/// # Example
+/// ```
+/// use amebazii::util::ed25519_public_key;
+///
+/// let pub_key = ed25519_public_key(&[0x11; 32]).unwrap();
+/// assert_eq!(pub_key.len(), 32);
+/// ```
+pub fn ed25519_public_key(priv_key: &[u8]) -> Result<[u8; 32], error::Error> {
+    let pkey = PKey::private_key_from_raw_bytes(priv_key, Id::ED25519)?;
+    let mut pub_key = [0xFF; 32];
+    pub_key.copy_from_slice(&pkey.raw_public_key()?);
+    Ok(pub_key)
+}
+
+/// Derives the X25519 public key from the provided private key.
+///
+/// # Parameters
+/// - `priv_key`: A byte slice containing the raw 32-byte X25519 private key.
+///
+/// # Returns
+/// - `Ok([u8; 32])`: The raw 32-byte X25519 public key.
+/// - `Err(error::Error)`: An error if the private key is invalid.
+pub fn x25519_public_key(priv_key: &[u8]) -> Result<[u8; 32], error::Error> {
+    let pkey = PKey::private_key_from_raw_bytes(priv_key, Id::X25519)?;
+    let mut pub_key = [0xFF; 32];
+    pub_key.copy_from_slice(&pkey.raw_public_key()?);
+    Ok(pub_key)
+}
+
+/// Computes the X25519 (ECDH) shared secret of a private key and a peer public key.
+///
+/// # Parameters
+/// - `priv_key`: A byte slice containing the raw 32-byte X25519 private key.
+/// - `peer_pub_key`: A byte slice containing the raw 32-byte X25519 public key of the peer.
+///
+/// # Returns
+/// - `Ok([u8; 32])`: The 32-byte shared secret.
+/// - `Err(error::Error)`: An error if one of the keys is invalid.
+///
+/// # Example
+/// ```
+/// use amebazii::util::{x25519, x25519_public_key};
+///
+/// // test vector from RFC 7748, section 6.1
+/// let alice = hex::decode("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a").unwrap();
+/// let bob = hex::decode("5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb").unwrap();
+/// let secret = hex::decode("4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742").unwrap();
+/// assert_eq!(x25519(&alice, &x25519_public_key(&bob).unwrap()).unwrap().to_vec(), secret);
+/// assert_eq!(x25519(&bob, &x25519_public_key(&alice).unwrap()).unwrap().to_vec(), secret);
+/// ```
+pub fn x25519(priv_key: &[u8], peer_pub_key: &[u8]) -> Result<[u8; 32], error::Error> {
+    let pkey = PKey::private_key_from_raw_bytes(priv_key, Id::X25519)?;
+    let peer = PKey::public_key_from_raw_bytes(peer_pub_key, Id::X25519)?;
+
+    let mut deriver = Deriver::new(&pkey)?;
+    deriver.set_peer(&peer)?;
+
+    let mut secret = [0xFF; 32];
+    deriver.derive(&mut secret)?;
+    Ok(secret)
+}
+
 /// Writes the specified byte `fill` repeatedly to the writer for the given length.
 ///
 /// This function writes the byte `fill` to the writer in chunks, filling the writer
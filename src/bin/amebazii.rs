@@ -1,5 +1,6 @@
 use clap::Parser;
 use colored::Colorize;
+use std::process::ExitCode;
 
 mod cli;
 use cli::{error, Cli, Commands};
@@ -18,29 +19,35 @@ fn cli_entry() -> Result<(), Error> {
         Some(Commands::Build { subcommand }) => cli::builder::main(&cli, subcommand.as_ref())?,
         Some(Commands::Mod { subcommand }) => cli::modify::main(&cli, subcommand.as_ref())?,
         Some(Commands::NVDM { subcommand }) => cli::nvdm::main(&cli, subcommand.as_ref())?,
+        Some(Commands::Secure { subcommand }) => cli::secure::main(&cli, subcommand.as_ref())?,
     }
 
     Ok(())
 }
 
-fn main() -> Result<(), Error> {
+fn main() -> ExitCode {
     match cli_entry() {
-        Ok(()) => (),
-        Err(e) => match e {
-            Error::InvalidState(msg) => {
-                error!("Encountered an invalid state: {}", msg);
-            }
-            Error::NotImplemented(msg) => {
-                error!("The following feature is not implemented: {}", msg);
-            }
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            match e {
+                Error::InvalidState(msg) => {
+                    error!("Encountered an invalid state: {}", msg);
+                }
+                Error::NotImplemented(msg) => {
+                    error!("The following feature is not implemented: {}", msg);
+                }
 
-            Error::UnknownImageType(msg) => {
-                error!("Could not translate image type {} to an enum!", msg);
-            }
-            _ => {
-                error!("{}", e);
+                Error::UnknownImageType(msg) => {
+                    error!("Could not translate image type {} to an enum!", msg);
+                }
+                Error::VerificationFailed(msg) => {
+                    error!("Verification failed: {}", msg);
+                }
+                _ => {
+                    error!("{}", e);
+                }
             }
-        },
+            ExitCode::FAILURE
+        }
     }
-    Ok(())
 }
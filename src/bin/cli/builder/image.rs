@@ -1,25 +1,25 @@
-use std::{fs, io::Read, path::PathBuf, str::FromStr};
+use std::{fs, path::PathBuf, str::FromStr};
 
 use amebazii::{
+    build,
     conf::image::{FSTCfg, ImageCfg},
-    types::{transfer_to, BootImage, HashAlgo, ToStream},
+    keystore::{FW_HASH_KEY_NAME, HASH_KEY_NAME},
+    types::{transfer_to, HashAlgo, ToStream},
 };
 use colored::Colorize;
-use object::{
-    elf,
-    read::elf::{ElfFile, ElfFile32, ElfFile64, FileHeader},
-    Endianness, ReadRef,
-};
 
-use crate::cli::{debug, error, util, Cli};
+use crate::cli::{debug, error, util, Cli, OutputOptions};
 
-use super::{BuildImageOptions, OutputOptions};
+use super::BuildImageOptions;
 
 pub fn build_image(cli: &Cli, options: &BuildImageOptions) -> Result<(), amebazii::error::Error> {
     if let Some(default_config_file) = &options.gen_defaults.generate_config {
         generate_config(cli, default_config_file)
     } else {
-        let config = config_from_options(cli, options)?;
+        let config = match config_from_options(cli, options)? {
+            Some(config) => config,
+            None => return Ok(()),
+        };
         if options.boot {
             build_boot_image(cli, options, &config)
         } else {
@@ -29,6 +29,7 @@ pub fn build_image(cli: &Cli, options: &BuildImageOptions) -> Result<(), amebazi
 }
 
 fn generate_config(cli: &Cli, config_file: &PathBuf) -> Result<(), amebazii::error::Error> {
+    debug!(cli, "Generating default config file: {:#?}", config_file);
     let mut cfgout = std::fs::File::create(config_file)?;
     let config = ImageCfg::default();
     serde_json::to_writer_pretty(&mut cfgout, &config)?;
@@ -38,12 +39,12 @@ fn generate_config(cli: &Cli, config_file: &PathBuf) -> Result<(), amebazii::err
 fn config_from_options(
     cli: &Cli,
     options: &BuildImageOptions,
-) -> Result<ImageCfg, amebazii::error::Error> {
+) -> Result<Option<ImageCfg>, amebazii::error::Error> {
     let mut config: ImageCfg;
     if let Some(config_file) = &options.config.file {
-        let cfgin = util::open_file(cli, config_file.clone(), Some("Config File"));
+        let cfgin = util::open_file(cli, config_file.clone(), Some("Config"));
         if cfgin.is_err() {
-            return Err(amebazii::error::Error::InvalidState("".to_string()));
+            return Ok(None);
         }
         let mut cfgin = cfgin.unwrap();
         config = serde_json::from_reader(&mut cfgin)?;
@@ -56,7 +57,7 @@ fn config_from_options(
     }
 
     if let Some(hash_algo) = &options.hash_algo {
-        let hash_algo = HashAlgo::from_str(&hash_algo)?;
+        let hash_algo = HashAlgo::from_str(hash_algo)?;
         if let Some(fst) = &mut config.fstcfg {
             fst.hash_algo = Some(hash_algo);
         } else {
@@ -67,64 +68,55 @@ fn config_from_options(
         }
     }
 
-    Ok(config)
+    Ok(Some(config))
 }
 
-fn build_boot_image(
-    cli: &Cli,
-    options: &BuildImageOptions,
-    config: &ImageCfg,
-) -> Result<(), amebazii::error::Error> {
-    if let Some(source) = &options.source {
-        // revisit: we don't want to use unsafe here but we need a reader that can be used to parse the ELF
-        let elf_file = util::open_file(cli, source.clone(), Some("ELF"));
-        if elf_file.is_err() {
-            return Err(amebazii::error::Error::InvalidState("".to_string()));
+fn read_source(cli: &Cli, options: &BuildImageOptions) -> Option<Vec<u8>> {
+    match &options.source {
+        Some(source) => {
+            debug!(cli, "Reading ELF file: {:#?}", source.display());
+            match fs::read(source) {
+                Ok(data) => Some(data),
+                Err(e) => {
+                    error!("Failed to read ELF file {:#?}: {}", source.display(), e);
+                    None
+                }
+            }
         }
-        let mut elf_file = elf_file.unwrap();
-        let mut data = Vec::with_capacity(elf_file.metadata().unwrap().len() as usize);
-        elf_file.read_to_end(&mut data)?;
-
-        let in_elf = elf::FileHeader32::<Endianness>::parse(data.as_slice());
-        if in_elf.is_err() {
-            error!("Failed to parse ELF file: {}", source.display());
-            return Ok(());
+        None => {
+            error!(
+                "No source file specified. Use {} to set one.",
+                "--source".bold()
+            );
+            None
         }
-        debug!(
-            cli,
-            "Parsed ELF file: {:#?} successfully!",
-            source.display()
-        );
-        build_boot_image_impl(cli, options, &in_elf, config)?;
-    } else {
-        error!("{}", "No source file specified.");
-        return Ok(());
     }
-    Ok(())
 }
 
-fn build_boot_image_impl<Elf>(
+fn build_boot_image(
     cli: &Cli,
     options: &BuildImageOptions,
-    source: &Elf,
     config: &ImageCfg,
 ) -> Result<(), amebazii::error::Error> {
-    let mut image = BootImage::default();
-
-    if config.secfg.is_empty() {
-        error!(
-            "{}",
-            "Please specify at least one section (SRAM) in config file."
-        );
-    }
-
-    let section = config.secfg.first().unwrap();
-    image.entry.entry_address = section.entry_addr;
-    image.entry.load_address = section.load_addr;
-
-    image.header = config.imghdr.clone().try_into()?;
-
-    Ok(())
+    let elf_data = match read_source(cli, options) {
+        Some(data) => data,
+        None => return Ok(()),
+    };
+
+    let keys = util::load_key_store(cli)?;
+    let signer = options
+        .signer
+        .signer(HASH_KEY_NAME, || Ok(keys.get_hash_key().to_vec()))?;
+    let image = build::build_boot_image(&elf_data, config, signer.as_ref())?;
+
+    println!("{}:", "Boot Image".bold().underline());
+    println!(
+        "  - Bootloader (length: 0x{:08x}, load: 0x{:08x}, entry: 0x{:08x})",
+        image.entry.length,
+        image.entry.load_address,
+        image.entry.entry_address.unwrap_or(0xFFFF_FFFF)
+    );
+    save_image(cli, &image, &options.output)
 }
 
 fn build_ota_image(
@@ -132,7 +124,33 @@ fn build_ota_image(
     options: &BuildImageOptions,
     config: &ImageCfg,
 ) -> Result<(), amebazii::error::Error> {
-    Ok(())
+    let elf_data = match read_source(cli, options) {
+        Some(data) => data,
+        None => return Ok(()),
+    };
+
+    let keys = util::load_key_store(cli)?;
+    let signer = options
+        .signer
+        .signer(FW_HASH_KEY_NAME, || Ok(keys.get_fw_hash_key().to_vec()))?;
+    let image = build::build_ota_image(&elf_data, config, &keys, signer.as_ref())?;
+
+    println!("{}:", "OTA Image".bold().underline());
+    for (i, subimage) in image.get_subimages().iter().enumerate() {
+        println!(
+            "  [{}] {:?} (size: 0x{:08x})",
+            i, subimage.header.img_type, subimage.header.segment_size
+        );
+        for section in subimage.get_sections() {
+            println!(
+                "      - {:?} (length: 0x{:08x}, load: 0x{:08x})",
+                section.header.sect_type,
+                section.entry_header.length,
+                section.entry_header.load_address
+            );
+        }
+    }
+    save_image(cli, &image, &options.output)
 }
 
 fn save_image<T>(
@@ -149,6 +167,7 @@ where
                 "Output file already exists. Use {} to overwrite.",
                 "--force".bold()
             );
+            return Ok(());
         }
         debug!(cli, "Saving image to: {:#?}", outfile_path.display());
         let mut outfp = std::fs::File::create(outfile_path.clone())?;
@@ -2,6 +2,7 @@ use super::{headings, BuildSubCommand, Cli};
 use clap::Parser;
 use std::path::PathBuf;
 
+mod image;
 mod parttab;
 mod sysdata;
 
@@ -123,6 +124,9 @@ pub struct BuildPartitionTableOptions {
     #[clap(verbatim_doc_comment)]
     #[arg(long, help_heading = headings::PARTTAB_OPTIONS)]
     pub user_bin: Option<String>,
+
+    #[command(flatten)]
+    pub signer: super::SignerOptions,
 }
 
 #[derive(Parser)]
@@ -174,11 +178,50 @@ pub struct BuildSystemDataOptions {
     pub gen_defaults: GenerateConfigOptions,
 }
 
+#[derive(Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct BuildImageOptions {
+    #[command(flatten)]
+    pub output: super::OutputOptions,
+
+    #[command(flatten)]
+    pub config: ConfigInputOptions,
+
+    #[command(flatten)]
+    pub gen_defaults: GenerateConfigOptions,
+
+    /// The application ELF file (e.g. application.axf) or bootloader ELF file
+    /// (e.g. bootloader.axf) to build the image from.
+    #[clap(verbatim_doc_comment)]
+    #[arg(short, long, value_name = "ELF", help_heading = headings::BUILD_OPTIONS)]
+    pub source: Option<PathBuf>,
+
+    /// Build a boot image from the bootloader instead of an OTA image.
+    ///
+    /// The boot image is signed using the hash key instead of the firmware hash key.
+    #[clap(verbatim_doc_comment)]
+    #[arg(long, action = clap::ArgAction::SetTrue, help_heading = headings::BUILD_OPTIONS)]
+    pub boot: bool,
+
+    /// Set the serial number (version) of the image.
+    #[clap(verbatim_doc_comment)]
+    #[arg(long, help_heading = headings::IMAGE_OPTIONS)]
+    pub serial: Option<u32>,
+
+    /// Set the hash algorithm used to hash all sub-images (currently only sha256).
+    #[clap(verbatim_doc_comment)]
+    #[arg(long, value_name = "ALGO", help_heading = headings::IMAGE_OPTIONS)]
+    pub hash_algo: Option<String>,
+
+    #[command(flatten)]
+    pub signer: super::SignerOptions,
+}
 
 pub fn main(cli: &Cli, subcommand: Option<&BuildSubCommand>) -> Result<(), amebazii::error::Error> {
     match subcommand {
         Some(BuildSubCommand::Parttab { options }) => parttab::build_parttab(cli, options)?,
         Some(BuildSubCommand::Sysdata { options }) => sysdata::build_sysdata(cli, options)?,
+        Some(BuildSubCommand::Image { options }) => image::build_image(cli, options)?,
         _ => {}
     }
 
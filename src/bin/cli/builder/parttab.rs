@@ -5,9 +5,10 @@ use colored::Colorize;
 use crate::cli::{debug, error, util, Cli};
 use amebazii::{
     conf::{DataArray, PartitionItemCfg, PartitionTableCfg},
-    keys::{FLASH_PATTERN, HASH_KEY, KEY_PAIR_003},
+    keys::FLASH_PATTERN,
+    keystore::HASH_KEY_NAME,
     types::{
-        key_to_hex, set_default_segment_size, set_default_signature, AsImage, EncryptedOr,
+        key_to_hex, set_default_segment_size, set_signature_with, AsImage, EncryptedOr,
         PartitionTableImage, PartitionType, ToStream,
     },
     util::write_fill,
@@ -20,6 +21,7 @@ pub fn build_parttab(
     cli: &Cli,
     options: &BuildPartitionTableOptions,
 ) -> Result<(), amebazii::error::Error> {
+    let keys = util::load_key_store(cli)?;
     if let Some(default_config_file) = &options.gen_defaults.generate_config {
         debug!(
             cli,
@@ -40,7 +42,7 @@ pub fn build_parttab(
                 length: 0xF8000,
                 part_type: PartitionType::Fw1,
                 debug_skip: false,
-                hash_key: key_to_hex(Some(KEY_PAIR_003.get_priv_key())).unwrap(),
+                hash_key: hex::encode(keys.get_fw_hash_key()),
             });
         }
 
@@ -93,7 +95,10 @@ pub fn build_parttab(
     image.header.segment_size = image.build_segment_size();
 
     set_default_segment_size(&mut image);
-    set_default_signature(&mut image, Some(HASH_KEY))?;
+    let signer = options
+        .signer
+        .signer(HASH_KEY_NAME, || Ok(keys.get_hash_key().to_vec()))?;
+    set_signature_with(&mut image, signer.as_ref())?;
 
     let mut out = Vec::new();
     let mut writer = Cursor::new(&mut out);
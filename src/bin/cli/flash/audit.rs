@@ -0,0 +1,21 @@
+use amebazii::{audit::audit_flash, error::Error, types::Flash};
+use colored::Colorize;
+
+use super::AuditOptions;
+use crate::cli::{debug, util, Cli};
+
+pub fn audit_flash_image(cli: &Cli, options: &AuditOptions) -> Result<(), Error> {
+    let input_file = options.input.file.as_ref().unwrap();
+    let mut fp = match util::open_file(cli, input_file.clone(), Some("Flash")) {
+        Ok(fp) => fp,
+        Err(_) => return Ok(()),
+    };
+
+    let keys = util::load_key_store(cli)?;
+    let mut flash = Flash::default();
+    flash.read_with_key(&mut fp, &options.decrypt.key_source(&keys)?)?;
+    debug!(cli, "Finished parsing file: {}", input_file.display());
+
+    let report = audit_flash(&flash, &mut fp)?;
+    util::print_audit_report(&report, options.json)
+}
@@ -4,10 +4,11 @@ use std::{fs, io, io::Read, io::Seek, path::PathBuf};
 use amebazii::{
     error::Error,
     keys::FLASH_PATTERN,
+    keystore::{partition_key_name, HASH_KEY_NAME},
     read_padding,
     types::{
-        from_stream, transfer_to, Flash, FromStream, Partition, PartitionTableImage, PartitionType,
-        SystemData,
+        from_stream, transfer_to, EncryptedOr, Flash, FromStream, Partition, PartitionTableImage,
+        PartitionType, SystemData,
     },
 };
 
@@ -37,7 +38,19 @@ pub fn combine_images(cli: &Cli, options: &CombineOptions) -> Result<(), amebazi
             .copy_from_slice(FLASH_PATTERN);
     }
 
-    let ptimage: PartitionTableImage = from_stream(&mut ptfp)?;
+    let mut ptimage: PartitionTableImage = from_stream(&mut ptfp)?;
+    let mut keys = util::load_key_store(cli)?;
+    if ptimage.is_encrypted() {
+        // the records are required to place all partitions, the table will be
+        // encrypted again when the flash image is written
+        debug!(cli, "Decrypting partition table");
+        ptimage.decrypt(&options.decrypt.key_source(&keys)?)?;
+    }
+
+    if let EncryptedOr::Plain(pt) = &ptimage.pt {
+        keys.add_partition_keys(pt);
+    }
+
     // setup partition table
     flash.set_partition(PartitionType::PartTab, Partition::PartitionTable(ptimage));
 
@@ -45,7 +58,6 @@ pub fn combine_images(cli: &Cli, options: &CombineOptions) -> Result<(), amebazi
         load_from_dir(cli, &mut flash, src_dir)?;
     }
 
-    // todo: support encrypted partition tables
     if let Some(sys_data_file) = &options.system {
         set_system_partition(cli, &mut flash, sys_data_file)?;
     }
@@ -96,6 +108,19 @@ pub fn combine_images(cli: &Cli, options: &CombineOptions) -> Result<(), amebazi
         return Ok(());
     }
 
+    if options.resign || options.signer.is_external() {
+        debug!(cli, "Updating image signatures");
+        flash.update_signatures_with(|part_type| {
+            let key_id = match part_type {
+                PartitionType::PartTab | PartitionType::Boot => HASH_KEY_NAME.to_string(),
+                _ => partition_key_name(part_type),
+            };
+            options.signer.signer(&key_id, || {
+                Ok(keys.get_partition_hash_key(part_type).to_vec())
+            })
+        })?;
+    }
+
     let mut out_file = fs::File::create(out_path.clone())?;
     transfer_to(&flash, &mut out_file)?;
 
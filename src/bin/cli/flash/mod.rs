@@ -1,12 +1,51 @@
 use std::path::PathBuf;
 
+use amebazii::{error::Error, keystore::KeyStore, types::KeySource};
 use clap::Parser;
 
 use super::{headings, Cli, FlashSubCommand};
 
+mod audit;
 mod combine;
 mod parse;
+mod rekey;
+mod scan;
 mod split;
+mod verify;
+
+/// Options to decrypt an encrypted partition table
+#[derive(Parser)]
+pub struct PtDecryptOptions {
+    /// The AES key used to decrypt an encrypted partition table (name, hex or file).
+    ///
+    /// If not set, the user key from the partition table header is used.
+    #[arg(long, value_name = "KEY", help_heading = headings::ENCRYPTION_OPTIONS)]
+    pub pt_key: Option<String>,
+
+    /// The AES IV used to decrypt an encrypted partition table in CBC mode (name, hex or file).
+    #[arg(
+        long,
+        value_name = "IV",
+        requires = "pt_key",
+        help_heading = headings::ENCRYPTION_OPTIONS
+    )]
+    pub pt_iv: Option<String>,
+}
+
+impl PtDecryptOptions {
+    pub fn key_source(&self, keys: &KeyStore) -> Result<KeySource, Error> {
+        match &self.pt_key {
+            Some(key) => {
+                let iv = match &self.pt_iv {
+                    Some(iv) => Some(keys.resolve(iv)?),
+                    None => None,
+                };
+                Ok(KeySource::Custom(keys.resolve(key)?, iv))
+            }
+            None => Ok(KeySource::UserKey),
+        }
+    }
+}
 
 /// Combine partitions to a usable flash image.
 #[derive(Parser)]
@@ -52,6 +91,20 @@ pub struct CombineOptions {
     /// Do not overwrite destination files
     #[arg(long, action = clap::ArgAction::SetTrue)]
     pub no_overwrite: bool,
+
+    #[command(flatten)]
+    pub decrypt: PtDecryptOptions,
+
+    /// Recompute the hashes of all images before writing the flash image.
+    ///
+    /// Partition hash keys are taken from the key store (or the partition table records)
+    /// unless an external signer is given, which implies this option.
+    #[clap(verbatim_doc_comment)]
+    #[arg(long, action = clap::ArgAction::SetTrue, help_heading = headings::SIGNER_OPTIONS)]
+    pub resign: bool,
+
+    #[command(flatten)]
+    pub signer: super::SignerOptions,
 }
 
 #[derive(Parser)]
@@ -66,22 +119,99 @@ pub struct SplitOptions {
 
     #[arg(short = 'c', long, action = clap::ArgAction::SetTrue)]
     pub include_common: bool,
+
+    #[command(flatten)]
+    pub decrypt: PtDecryptOptions,
+}
+
+#[derive(Parser)]
+pub struct VerifyOptions {
+    #[command(flatten)]
+    pub input: super::InputOptions,
+
+    #[command(flatten)]
+    pub decrypt: PtDecryptOptions,
+}
+
+#[derive(Parser)]
+pub struct RekeyOptions {
+    #[command(flatten)]
+    pub input: super::InputOptions,
+
+    #[command(flatten)]
+    pub output: super::OutputOptionsInPlace,
+
+    /// Key files storing the new keys (JSON, hex or raw binary).
+    ///
+    /// The current keys are taken from the global key store.
+    #[clap(verbatim_doc_comment)]
+    #[arg(short = 'n', long, value_name = "FILE", required = true)]
+    pub new_keys: Vec<PathBuf>,
+
+    /// Rekey the flash image even if it does not verify using the current keys.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub skip_verify: bool,
+
+    #[command(flatten)]
+    pub decrypt: PtDecryptOptions,
+}
+
+#[derive(Parser)]
+pub struct AuditOptions {
+    #[command(flatten)]
+    pub input: super::InputOptions,
+
+    /// Print the report as JSON.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub json: bool,
+
+    #[command(flatten)]
+    pub decrypt: PtDecryptOptions,
+}
+
+#[derive(Parser)]
+pub struct ScanOptions {
+    #[command(flatten)]
+    pub input: super::InputOptions,
+
+    /// Print all findings as JSON.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub json: bool,
+
+    /// Do not report high-entropy key candidates.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub no_key_candidates: bool,
+
+    #[command(flatten)]
+    pub decrypt: PtDecryptOptions,
 }
 
 pub fn main(cli: &Cli, subcommand: Option<&FlashSubCommand>) -> Result<(), amebazii::error::Error> {
     match subcommand {
-        Some(FlashSubCommand::Parse { file, pt_only }) => {
-            parse::parse(cli, file.clone().expect("File is required"), *pt_only)?;
+        Some(FlashSubCommand::Parse {
+            file,
+            pt_only,
+            decrypt,
+            identify,
+        }) => {
+            parse::parse(
+                cli,
+                file.clone().expect("File is required"),
+                *pt_only,
+                decrypt,
+                identify,
+            )?;
         }
         Some(FlashSubCommand::Split { options }) => {
             split::split_flash(cli, options)?;
         }
-        Some(FlashSubCommand::Combine { options }) => match options {
-            Some(options) => {
-                combine::combine_images(cli, options)?;
-            }
-            None => {}
-        },
+        Some(FlashSubCommand::Combine { options }) => {
+            combine::combine_images(cli, options)?;
+        }
+        Some(FlashSubCommand::Verify { options }) => verify::verify_flash(cli, options)?,
+        Some(FlashSubCommand::Rekey { options }) => rekey::rekey_flash(cli, options)?,
+        Some(FlashSubCommand::Audit { options }) => audit::audit_flash_image(cli, options)?,
+        Some(FlashSubCommand::Scan { options }) => scan::scan_flash(cli, options)?,
         _ => {}
     }
     Ok(())
@@ -2,20 +2,43 @@ use colored::Colorize;
 use openssl::memcmp::eq;
 use std::{io::Seek, path::PathBuf};
 
-use crate::cli::{debug, util, Cli};
+use super::PtDecryptOptions;
+use crate::cli::{debug, error, util, Cli, IdentifyKeysOptions};
 use amebazii::{
-    keys::{HASH_KEY, KEY_PAIR_000, KEY_PAIR_001, KEY_PAIR_003},
-    types::{from_stream, EncryptedOr, Flash, Partition, PartitionTableImage, PartitionType},
+    keys::{KEY_PAIR_000, KEY_PAIR_001, KEY_PAIR_003},
+    keystore::{KeyStore, HASH_KEY_NAME},
+    types::{
+        from_stream, EncryptedOr, Flash, KeyIdentification, Partition, PartitionTableImage,
+        PartitionType,
+    },
 };
 
-pub fn parse(cli: &Cli, file: PathBuf, pt_only: bool) -> Result<(), amebazii::error::Error> {
+pub fn parse(
+    cli: &Cli,
+    file: PathBuf,
+    pt_only: bool,
+    decrypt: &PtDecryptOptions,
+    identify: &IdentifyKeysOptions,
+) -> Result<(), amebazii::error::Error> {
     if let Ok(mut fp) = util::open_file(cli, file.clone(), None) {
+        let keys = util::load_key_store(cli)?;
         if pt_only {
             fp.seek(std::io::SeekFrom::Start(32))?;
-            let pt_image: PartitionTableImage = from_stream(&mut fp)?;
-            dump_partition_table(&pt_image, &mut fp)?;
+            let mut pt_image: PartitionTableImage = from_stream(&mut fp)?;
+            if let Err(e) = pt_image.decrypt(&decrypt.key_source(&keys)?) {
+                error!("Could not decrypt partition table: {}", e);
+            }
+            dump_partition_table(&pt_image, &keys, &mut fp)?;
+
+            if identify.identify_keys {
+                let known_keys = identify.key_store(&keys)?.known_keys();
+                fp.seek(std::io::SeekFrom::Start(32))?;
+                let result = pt_image.identify_key(&mut fp, &known_keys)?;
+                dump_key_identification(&[(PartitionType::PartTab, vec![result])]);
+            }
         } else {
-            let flash: Flash = from_stream(&mut fp)?;
+            let mut flash = Flash::default();
+            flash.read_with_key(&mut fp, &decrypt.key_source(&keys)?)?;
 
             if cli.verbose > 2 {
                 debug!(cli, "Finished parsing file: {}", file.display());
@@ -29,7 +52,12 @@ pub fn parse(cli: &Cli, file: PathBuf, pt_only: bool) -> Result<(), amebazii::er
             if let Some(Partition::PartitionTable(partition_table)) =
                 flash.get_partition(PartitionType::PartTab)
             {
-                dump_partition_table(partition_table, &mut fp)?;
+                dump_partition_table(partition_table, &keys, &mut fp)?;
+            }
+
+            if identify.identify_keys {
+                let results = flash.identify_keys(&mut fp, &identify.key_store(&keys)?)?;
+                dump_key_identification(&results);
             }
         }
     }
@@ -37,8 +65,23 @@ pub fn parse(cli: &Cli, file: PathBuf, pt_only: bool) -> Result<(), amebazii::er
     Ok(())
 }
 
+fn dump_key_identification(results: &[(PartitionType, Vec<KeyIdentification>)]) {
+    println!(
+        "{} {} {}",
+        "=".repeat(36),
+        "Key Identification".bold(),
+        "=".repeat(35)
+    );
+    for (part_type, part_results) in results {
+        println!("[{:?}]", part_type);
+        util::print_key_identification(part_results);
+    }
+    println!("{}\n", "=".repeat(91));
+}
+
 fn dump_partition_table(
     pt_image: &PartitionTableImage,
+    keys: &KeyStore,
     fp: &mut std::fs::File,
 ) -> Result<(), amebazii::error::Error> {
     println!(
@@ -71,14 +114,15 @@ fn dump_partition_table(
         );
     }
 
-    println!(
-        "\n{}: ({})",
-        "Signature".bold(),
-        "using default hash key".italic()
-    );
+    let key_name = if keys.get_key(HASH_KEY_NAME).is_some() {
+        "using hash key from key store"
+    } else {
+        "using default hash key"
+    };
+    println!("\n{}: ({})", "Signature".bold(), key_name.italic());
 
     fp.seek(std::io::SeekFrom::Start(32))?;
-    let signature = pt_image.create_signature(fp, HASH_KEY)?;
+    let signature = pt_image.create_signature(fp, keys.get_hash_key())?;
     let pt_hash = pt_image.get_hash();
     print!("  - {:?} ", hex::encode(pt_hash));
     if eq(&signature, pt_hash) {
@@ -87,6 +131,13 @@ fn dump_partition_table(
         println!("{}", "invalid/encrypted/wrong key".red().italic());
     }
 
+    print!("\n{}: ", "Encryption".bold());
+    if pt_image.header.is_encrypt {
+        println!("{}", "enabled".yellow());
+    } else {
+        println!("{}", "disabled".green());
+    }
+
     if let EncryptedOr::Plain(pt) = &pt_image.pt {
         println!("\n{}: ", "User Data".bold());
         println!("  - {}: {:?} ", "UserExt", hex::encode(pt.get_user_ext()));
@@ -0,0 +1,73 @@
+use colored::Colorize;
+use std::io::{Cursor, Read, Seek, Write};
+
+use amebazii::{
+    error::Error,
+    keystore::KeyStore,
+    types::{EncryptedOr, Flash, Partition, PartitionType},
+};
+
+use super::RekeyOptions;
+use crate::cli::{debug, util, Cli};
+
+pub fn rekey_flash(cli: &Cli, options: &RekeyOptions) -> Result<(), Error> {
+    let input_file = options.input.file.as_ref().unwrap();
+    let mut fp = match util::open_file(cli, input_file.clone(), Some("Flash")) {
+        Ok(fp) => fp,
+        Err(_) => return Ok(()),
+    };
+
+    let old_keys = util::load_key_store(cli)?;
+    let mut flash = Flash::default();
+    flash.read_with_key(&mut fp, &options.decrypt.key_source(&old_keys)?)?;
+    debug!(cli, "Finished parsing file: {}", input_file.display());
+
+    // re-signing a broken image would make it look valid
+    if !options.skip_verify {
+        let reports = flash.verify(&mut fp, &old_keys)?;
+        let failed: Vec<_> = reports
+            .iter()
+            .filter(|(_, report)| !report.is_ok())
+            .map(|(part_type, _)| format!("{:?}", part_type))
+            .collect();
+        if !failed.is_empty() {
+            return Err(Error::VerificationFailed(format!(
+                "{} could not be verified using the current keys (use --skip-verify to rekey anyway)",
+                failed.join(", ")
+            )));
+        }
+        debug!(cli, "Verified all partitions using the current keys");
+    }
+
+    let mut new_keys = KeyStore::new();
+    for file in &options.new_keys {
+        debug!(cli, "Loading new keys from {:#?}", file.display());
+        new_keys.load_file(file)?;
+    }
+
+    flash.rekey(&old_keys, &new_keys)?;
+    if let Some(Partition::PartitionTable(pt_image)) = flash.get_partition(PartitionType::PartTab) {
+        if let EncryptedOr::Plain(pt) = &pt_image.pt {
+            for record in pt.get_records() {
+                if let Some(key) = record.get_hash_key() {
+                    println!(
+                        "[{:?}] New hash key: {}",
+                        record.part_type,
+                        hex::encode(key)
+                    );
+                }
+            }
+        }
+    }
+
+    // all partitions that have not been re-signed are copied from the input
+    let mut buffer = Vec::new();
+    fp.rewind()?;
+    fp.read_to_end(&mut buffer)?;
+    flash.patch_to(&mut Cursor::new(&mut buffer))?;
+
+    let mut out_file = util::open_output_file(cli, Some(&options.input), &options.output)?;
+    out_file.write_all(&buffer)?;
+    println!("{}", "Rekeyed all partitions".green());
+    Ok(())
+}
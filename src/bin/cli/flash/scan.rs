@@ -0,0 +1,24 @@
+use colored::Colorize;
+
+use amebazii::{error::Error, scan::SecretScanner, types::Flash};
+
+use super::ScanOptions;
+use crate::cli::{debug, util, Cli};
+
+pub fn scan_flash(cli: &Cli, options: &ScanOptions) -> Result<(), Error> {
+    let input_file = options.input.file.as_ref().unwrap();
+    let mut fp = match util::open_file(cli, input_file.clone(), Some("Flash")) {
+        Ok(fp) => fp,
+        Err(_) => return Ok(()),
+    };
+
+    let keys = util::load_key_store(cli)?;
+    let mut flash = Flash::default();
+    flash.read_with_key(&mut fp, &options.decrypt.key_source(&keys)?)?;
+    debug!(cli, "Finished parsing file: {}", input_file.display());
+
+    let mut scanner = SecretScanner::default();
+    scanner.set_key_candidates(!options.no_key_candidates);
+    let findings = scanner.scan_flash(&flash, &mut fp)?;
+    util::print_secret_findings(&findings, options.json)
+}
@@ -18,7 +18,11 @@ pub fn split_flash(cli: &Cli, options: &super::SplitOptions) -> Result<(), ameba
 
         let mut input = input.unwrap();
         input.seek(io::SeekFrom::Start(32))?;
-        let pt_image: PartitionTableImage = from_stream(&mut input)?;
+        let keys = util::load_key_store(cli)?;
+        let mut pt_image: PartitionTableImage = from_stream(&mut input)?;
+        if let Err(e) = pt_image.decrypt(&options.decrypt.key_source(&keys)?) {
+            error!("Could not decrypt partition table: {}", e);
+        }
 
         if let Some(outdir) = &options.outdir {
             if !outdir.is_dir() {
@@ -53,7 +57,7 @@ pub fn split_flash(cli: &Cli, options: &super::SplitOptions) -> Result<(), ameba
             } else {
                 error!(
                     "{}",
-                    "Partition table is encrypted (decryption not supported)"
+                    "Partition table is encrypted (use --pt-key to specify the key)"
                 );
             }
 
@@ -0,0 +1,69 @@
+use colored::Colorize;
+
+use amebazii::{error::Error, types::Flash};
+
+use super::VerifyOptions;
+use crate::cli::{debug, util, Cli};
+
+pub fn verify_flash(cli: &Cli, options: &VerifyOptions) -> Result<(), Error> {
+    let input_file = options.input.file.as_ref().unwrap();
+    let mut fp = match util::open_file(cli, input_file.clone(), Some("Flash")) {
+        Ok(fp) => fp,
+        Err(_) => {
+            return Err(Error::VerificationFailed(
+                "input file could not be read".to_string(),
+            ))
+        }
+    };
+
+    let keys = util::load_key_store(cli)?;
+    let mut flash = Flash::default();
+    flash.read_with_key(&mut fp, &options.decrypt.key_source(&keys)?)?;
+    debug!(cli, "Finished parsing file: {}", input_file.display());
+
+    let reports = flash.verify(&mut fp, &keys)?;
+    println!(
+        "{} {} {}",
+        "=".repeat(40),
+        "Flash Verification".bold(),
+        "=".repeat(40)
+    );
+
+    let mut failures = 0;
+    for (part_type, report) in &reports {
+        print!("  [{:?}] ", part_type);
+        if report.is_ok() {
+            println!("{}", "OK".green());
+        } else {
+            failures += 1;
+            println!("{}", "FAILED".red().bold());
+        }
+
+        for check in report.get_checks() {
+            if check.is_ok() {
+                if cli.verbose > 0 {
+                    println!("    - {}: {} {}", check.kind, check.actual, "OK".green());
+                }
+            } else {
+                println!(
+                    "    - {}: {} {}",
+                    check.kind,
+                    check.actual,
+                    "invalid".red().italic()
+                );
+                println!("      expected: {}", check.expected);
+            }
+        }
+    }
+    println!("{}", "=".repeat(100));
+
+    if failures > 0 {
+        return Err(Error::VerificationFailed(format!(
+            "{} of {} partitions failed",
+            failures,
+            reports.len()
+        )));
+    }
+    println!("{}", "All partitions passed".green());
+    Ok(())
+}
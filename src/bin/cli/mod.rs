@@ -1,3 +1,8 @@
+use amebazii::{
+    error::Error,
+    keystore::KeyStore,
+    signer::{CommandSigner, KeySigner, Signer, SocketSigner},
+};
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
@@ -5,6 +10,7 @@ pub mod builder;
 pub mod flash;
 pub mod modify;
 pub mod ota;
+pub mod secure;
 pub mod util;
 pub mod nvdm;
 
@@ -16,6 +22,10 @@ mod headings {
     pub const SYSDATA_OPTIONS: &str = "SystemData Options";
     pub const PART_OPTIONS: &str = "Partition Options";
     pub const OUTPUT_OPTIONS: &str = "Output Options";
+    pub const ENCRYPTION_OPTIONS: &str = "Encryption Options";
+    pub const KEY_OPTIONS: &str = "Key Identification Options";
+    pub const SIGNER_OPTIONS: &str = "Signer Options";
+    pub const IMAGE_OPTIONS: &str = "Image Options";
 }
 
 /// AmebaZ2 Tools to work with OTA and flash images
@@ -29,6 +39,13 @@ pub struct Cli {
     #[arg(short, long, action = clap::ArgAction::Count)]
     pub verbose: u8,
 
+    /// Key file with named keys (JSON, hex or raw), can be repeated.
+    ///
+    /// All key arguments accept the name of a key from these files. The keys
+    /// `hash_key`, `fw_hash_key` and `enc_priv_key` replace the default keys.
+    #[arg(long, value_name = "FILE", global = true)]
+    pub keystore: Vec<PathBuf>,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
@@ -123,6 +140,78 @@ impl OutputOptionsExt for OutputOptionsInPlace {
     }
 }
 
+/// Options to identify the keys used to create all hashes of an image
+#[derive(Parser)]
+pub struct IdentifyKeysOptions {
+    /// Try all known keys with SHA-256 and MD5 to identify which key created each hash.
+    #[arg(long, action = clap::ArgAction::SetTrue, help_heading = headings::KEY_OPTIONS)]
+    pub identify_keys: bool,
+
+    /// Additional key to try when identifying keys (name, hex or file, can be repeated).
+    #[arg(
+        long,
+        value_name = "KEY",
+        requires = "identify_keys",
+        help_heading = headings::KEY_OPTIONS
+    )]
+    pub try_key: Vec<String>,
+}
+
+impl IdentifyKeysOptions {
+    /// Returns the given key store extended by the additional keys given on the command line.
+    pub fn key_store(&self, keys: &KeyStore) -> Result<KeyStore, Error> {
+        let mut keys = keys.clone();
+        for key in &self.try_key {
+            keys.add_key(key, &keys.resolve(key)?);
+        }
+        Ok(keys)
+    }
+}
+
+/// Options to compute all signatures using an external signer
+#[derive(Parser)]
+pub struct SignerOptions {
+    /// External command computing all signatures.
+    ///
+    /// The data is passed on stdin, the signature is read from stdout (raw or hex).
+    /// The hash algorithm and key name are passed in AMEBAZII_HASH_ALGO and
+    /// AMEBAZII_KEY_ID.
+    #[arg(
+        long,
+        value_name = "CMD",
+        conflicts_with = "signer_socket",
+        help_heading = headings::SIGNER_OPTIONS
+    )]
+    pub signer_cmd: Option<String>,
+
+    /// Local socket service computing all signatures (host:port or Unix socket path).
+    #[arg(long, value_name = "ADDR", help_heading = headings::SIGNER_OPTIONS)]
+    pub signer_socket: Option<String>,
+}
+
+impl SignerOptions {
+    /// Returns `true` if an external signer has been configured.
+    pub fn is_external(&self) -> bool {
+        self.signer_cmd.is_some() || self.signer_socket.is_some()
+    }
+
+    /// Returns the external signer for the given key name or, if no external signer has
+    /// been configured, a signer using the local key.
+    pub fn signer<F>(&self, key_id: &str, local_key: F) -> Result<Box<dyn Signer>, Error>
+    where
+        F: FnOnce() -> Result<Vec<u8>, Error>,
+    {
+        if let Some(command) = &self.signer_cmd {
+            return Ok(Box::new(CommandSigner::new(command, key_id)?));
+        }
+
+        if let Some(address) = &self.signer_socket {
+            return Ok(Box::new(SocketSigner::new(address, key_id)));
+        }
+        Ok(Box::new(KeySigner::new(Some(&local_key()?))))
+    }
+}
+
 /// Main commands in the CLI.
 #[derive(Subcommand)]
 pub enum Commands {
@@ -154,7 +243,13 @@ pub enum Commands {
     NVDM {
         #[command(subcommand)]
         subcommand: Option<NvdmSubCommand>,
-    }
+    },
+
+    #[command(arg_required_else_help = true)]
+    Secure {
+        #[command(subcommand)]
+        subcommand: Option<SecureSubCommand>,
+    },
 }
 
 /// Flash-related operations.
@@ -170,6 +265,12 @@ pub enum FlashSubCommand {
         /// Parse only the partition table
         #[arg(long, action = clap::ArgAction::SetTrue)]
         pt_only: bool,
+
+        #[command(flatten)]
+        decrypt: flash::PtDecryptOptions,
+
+        #[command(flatten)]
+        identify: IdentifyKeysOptions,
     },
 
     /// Split a flash image file into partitions and store them in a directory.
@@ -182,7 +283,64 @@ pub enum FlashSubCommand {
     #[command(arg_required_else_help = true)]
     Combine {
         #[command(flatten)]
-        options: Option<flash::CombineOptions>,
+        options: flash::CombineOptions,
+    },
+
+    /// Verify the partition table, boot image and firmware images of a flash image.
+    ///
+    /// Exits with a non-zero status code if any check fails.
+    ///
+    /// Example:
+    ///     - amebazii flash verify ./flash.bin
+    #[clap(verbatim_doc_comment)]
+    #[command(arg_required_else_help = true)]
+    Verify {
+        #[command(flatten)]
+        options: flash::VerifyOptions,
+    },
+
+    /// Replace all hash keys of a flash image and re-sign all images.
+    ///
+    /// The partition record keys, key block public keys, hashes and checksums are
+    /// updated using the new keys. The input is verified using the current keys
+    /// (global key store) first.
+    ///
+    /// Example:
+    ///     - amebazii --keystore old.json flash rekey -n new.json ./flash.bin ./new_flash.bin
+    #[clap(verbatim_doc_comment)]
+    #[command(arg_required_else_help = true)]
+    Rekey {
+        #[command(flatten)]
+        options: flash::RekeyOptions,
+    },
+
+    /// Audit the security configuration of a flash image.
+    ///
+    /// Reports weaknesses such as images signed using public default keys, disabled
+    /// encryption, debug options or active GPIO traps, ordered by severity.
+    ///
+    /// Example:
+    ///     - amebazii flash audit ./flash.bin
+    ///     - amebazii flash audit --json ./flash.bin
+    #[clap(verbatim_doc_comment)]
+    #[command(arg_required_else_help = true)]
+    Audit {
+        #[command(flatten)]
+        options: flash::AuditOptions,
+    },
+
+    /// Scan a flash image for embedded certificates, private keys and key candidates.
+    ///
+    /// Firmware sections and the boot image are reported using their load address,
+    /// all other partitions using their flash offset.
+    ///
+    /// Example:
+    ///     - amebazii flash scan ./flash.bin
+    #[clap(verbatim_doc_comment)]
+    #[command(arg_required_else_help = true)]
+    Scan {
+        #[command(flatten)]
+        options: flash::ScanOptions,
     },
 }
 
@@ -194,7 +352,7 @@ pub enum OtaSubCommand {
     Parse {
         /// The OTA image file to parse
         #[command(flatten)]
-        options: Option<ota::ParseOptions>,
+        options: ota::ParseOptions,
     },
 
     /// Extracts sections from subimages within an OTA image
@@ -215,6 +373,9 @@ pub enum OtaSubCommand {
         /// The directory to store the sections or the target file to store the section
         #[arg(value_name = "DIR/FILE")]
         outdir: Option<PathBuf>,
+
+        #[command(flatten)]
+        decrypt: ota::DecryptOptions,
     },
 
     /// Relink a firmware binary (OTA image)
@@ -225,7 +386,7 @@ pub enum OtaSubCommand {
     #[command(arg_required_else_help = true, about, long_about)]
     Relink {
         #[command(flatten)]
-        options: Option<ota::RelinkOptions>,
+        options: Box<ota::RelinkOptions>,
     },
 
     /// Resign a firmware binary (OTA image)
@@ -238,6 +399,122 @@ pub enum OtaSubCommand {
         #[command(flatten)]
         options: ota::ReSignOptions,
     },
+
+    /// Patch an OTA image by virtual address
+    ///
+    /// Applies all patches of a JSON patch file after checking the original bytes and
+    /// signs the image again.
+    ///
+    /// Example:
+    ///     - amebazii ota patch -p ./patch.json ./ota.bin ./patched.bin
+    #[clap(verbatim_doc_comment)]
+    #[command(arg_required_else_help = true)]
+    Patch {
+        #[command(flatten)]
+        options: ota::PatchOptions,
+    },
+
+    /// Repack a relinked ELF file into an OTA image
+    ///
+    /// The original OTA image is used as template. The data of each section is replaced
+    /// by the ELF sections at its load address and all lengths, offsets, signatures and
+    /// the checksum are recomputed.
+    ///
+    /// Example:
+    ///     - amebazii ota repack -t ./ota.bin ./ota.elf ./new_ota.bin
+    #[clap(verbatim_doc_comment)]
+    #[command(arg_required_else_help = true)]
+    Repack {
+        #[command(flatten)]
+        options: ota::RepackOptions,
+    },
+
+    /// Repair an OTA image after it has been modified
+    ///
+    /// Recomputes all section lengths and offsets, subimage segment sizes and offsets,
+    /// signatures and the checksum. All changed fields are reported.
+    ///
+    /// Example:
+    ///     - amebazii ota repair ./modified.bin ./repaired.bin
+    #[clap(verbatim_doc_comment)]
+    #[command(arg_required_else_help = true)]
+    Repair {
+        #[command(flatten)]
+        options: ota::RepairOptions,
+    },
+
+    /// Resolve runtime addresses to sections, file and flash offsets
+    ///
+    /// Each address is mapped to its subimage, section, offset in the section data,
+    /// offset in the OTA image and (if the partition offset is known) flash offset.
+    /// XIP addresses are resolved through the XIP page remapping of their section.
+    ///
+    /// Example:
+    ///     - amebazii ota resolve --base 0x10000 ./ota.bin 0x9b000200 0x10000520
+    ///     - amebazii ota resolve --file-offsets ./ota.bin 0x4200
+    #[clap(verbatim_doc_comment)]
+    #[command(arg_required_else_help = true)]
+    Resolve {
+        #[command(flatten)]
+        options: ota::ResolveOptions,
+    },
+
+    /// Verify all hashes, signatures and the checksum of an OTA image
+    ///
+    /// Exits with a non-zero status code if any check fails.
+    ///
+    /// Example:
+    ///     - amebazii ota verify ./ota.bin
+    #[clap(verbatim_doc_comment)]
+    #[command(arg_required_else_help = true)]
+    Verify {
+        #[command(flatten)]
+        options: ota::VerifyOptions,
+    },
+
+    /// Audit the security configuration of an OTA image.
+    ///
+    /// Reports weaknesses such as hashes created using public default keys or
+    /// disabled encryption, ordered by severity.
+    ///
+    /// Example:
+    ///     - amebazii ota audit ./ota.bin
+    ///     - amebazii ota audit --json ./ota.bin
+    #[clap(verbatim_doc_comment)]
+    #[command(arg_required_else_help = true)]
+    Audit {
+        #[command(flatten)]
+        options: ota::AuditOptions,
+    },
+
+    /// Scan all sections of an OTA image for embedded certificates, private keys and
+    /// key candidates.
+    ///
+    /// Example:
+    ///     - amebazii ota scan ./ota.bin
+    ///     - amebazii ota scan --json --no-key-candidates ./ota.bin
+    #[clap(verbatim_doc_comment)]
+    #[command(arg_required_else_help = true)]
+    Scan {
+        #[command(flatten)]
+        options: ota::ScanOptions,
+    },
+
+    /// Symbolize the addresses of a captured console log (e.g. a hard fault dump).
+    ///
+    /// Every address is mapped to its memory region and, if the OTA image is given, to
+    /// its subimage and section. Symbols are resolved from an ELF file and SDK ROM
+    /// symbol files.
+    ///
+    /// Example:
+    ///     - amebazii ota symbolize -i ./ota.bin -e ./ota.elf ./crash.log
+    ///     - amebazii ota symbolize -r ./rom_symbol.ld --json ./crash.log
+    #[clap(verbatim_doc_comment)]
+    #[command(arg_required_else_help = true)]
+    Symbolize {
+        #[command(flatten)]
+        options: ota::SymbolizeOptions,
+    },
 }
 
 /// Builder for Partition tables and mroe
@@ -256,6 +533,13 @@ pub enum BuildSubCommand {
         #[command(flatten)]
         options: builder::BuildSystemDataOptions,
     },
+
+    /// Builds an OTA image or boot image from an ELF file
+    #[command(arg_required_else_help = true)]
+    Image {
+        #[command(flatten)]
+        options: builder::BuildImageOptions,
+    },
 }
 
 /// Modification operations
@@ -275,6 +559,16 @@ pub enum ModSubCommand {
     },
 }
 
+/// Secure boot operations
+#[derive(Subcommand)]
+pub enum SecureSubCommand {
+    #[command(arg_required_else_help = true)]
+    Provision {
+        #[command(flatten)]
+        options: secure::ProvisionOptions,
+    },
+}
+
 /// NVDM parser/viewer
 #[derive(Parser)]
 #[clap(verbatim_doc_comment)]
@@ -284,6 +578,12 @@ pub enum NvdmSubCommand {
         #[command(flatten)]
         options: nvdm::ParseOptions,
     },
+
+    #[command(arg_required_else_help = true)]
+    Scan {
+        #[command(flatten)]
+        options: nvdm::ScanOptions,
+    },
 }
 
 /// Macro for printing debug messages with formatting.
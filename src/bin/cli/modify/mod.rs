@@ -123,9 +123,11 @@ pub struct ModParttabOptions {
     #[arg(long, action = clap::ArgAction::SetTrue, help_heading = headings::RECORD_OPTIONS)]
     pub debug_skip: bool,
 
-    /// Provides a hash key or file path used to sign the partition.
+    /// Provides a hash key (name, hex or file) used to sign the partition.
     #[arg(long, value_name = "FILE/KEY", help_heading = headings::RECORD_OPTIONS)]
     pub hash_key: Option<String>,
+    #[command(flatten)]
+    pub decrypt: super::flash::PtDecryptOptions,
 }
 
 #[derive(clap::ValueEnum, Clone, Debug, PartialEq, Eq)]
@@ -1,14 +1,12 @@
 use colored::Colorize;
-use std::{
-    fs,
-    io::{Read, Seek, Write},
-};
+use std::io::{Seek, Write};
 
 use amebazii::{
-    keys::{FLASH_PATTERN, HASH_KEY},
+    keys::FLASH_PATTERN,
+    keystore::KeyStore,
     types::{
-        from_stream, key_from_hex, set_default_segment_size, set_default_signature, transfer_to,
-        PartTab, PartitionTableImage, Record,
+        from_stream, set_default_segment_size, set_signature_from, transfer_to, PartTab,
+        PartitionTableImage, PartitionType, Record,
     },
     util::write_fill,
     write_padding,
@@ -33,26 +31,33 @@ pub fn modify_parttab(
             infile.seek(std::io::SeekFrom::Start(0x20))?;
         }
 
+        let keys = util::load_key_store(cli)?;
         let mut pt: PartitionTableImage = from_stream(&mut infile)?;
+        if pt.is_encrypted() {
+            // the table will be encrypted again using the same key when it is saved
+            debug!(cli, "Decrypting partition table");
+            pt.decrypt(&options.decrypt.key_source(&keys)?)?;
+        }
 
         println!("{}", "Modified Partition Table:".bold());
         modify_parttab_info(options, &mut pt)?;
         if let Some(part_type) = &options.add {
-            add_record(cli, options, &mut pt, part_type)?;
+            add_record(&keys, options, &mut pt, part_type)?;
         }
         if let Some(part_type) = &options.alter {
-            modify_record(cli, options, &mut pt, part_type)?;
+            modify_record(&keys, options, &mut pt, part_type)?;
         }
         if let Some(part_type) = &options.remove {
             rem_record(&mut pt, part_type);
         }
-        save_parttab(cli, options, &mut pt)?;
+        save_parttab(cli, &keys, options, &mut pt)?;
     }
     Ok(())
 }
 
 fn save_parttab(
     cli: &Cli,
+    keys: &KeyStore,
     options: &ModParttabOptions,
     image: &mut PartitionTableImage,
 ) -> Result<(), amebazii::error::Error> {
@@ -63,7 +68,7 @@ fn save_parttab(
     }
 
     set_default_segment_size(image);
-    set_default_signature(image, Some(HASH_KEY))?;
+    set_signature_from(image, keys, PartitionType::PartTab)?;
     transfer_to(image, &mut outfile)?;
     Ok(())
 }
@@ -93,18 +98,13 @@ fn modify_parttab_info(
 fn record_set_hash_key(
     record: &mut Record,
     options: &ModParttabOptions,
-    cli: &Cli,
+    keys: &KeyStore,
 ) -> Result<(), amebazii::error::Error> {
-    if let Some(key_or_file) = &options.hash_key {
-        if fs::exists(key_or_file)? {
-            debug!(cli, "Reading hash key from file: {}", key_or_file);
-            let mut key = [0u8; 32];
-            let mut key_file = fs::File::open(key_or_file.clone())?;
-            key_file.read_exact(&mut key)?;
-            record.set_hash_key(Some(key));
-        } else {
-            record.set_hash_key(key_from_hex(key_or_file));
-        }
+    if let Some(key) = &options.hash_key {
+        let key: [u8; 32] = keys.resolve(key)?.try_into().map_err(|_| {
+            amebazii::error::Error::InvalidState("Hash key must be 32 bytes long".to_string())
+        })?;
+        record.set_hash_key(Some(key));
     }
     if let Some(key) = record.get_hash_key() {
         println!("{:>14}: {}", "└─ Hash Key", hex::encode(key));
@@ -133,7 +133,7 @@ fn rem_record(image: &mut PartitionTableImage, part_type: &super::PartitionType)
 }
 
 fn add_record(
-    cli: &Cli,
+    keys: &KeyStore,
     options: &ModParttabOptions,
     image: &mut PartitionTableImage,
     part_type: &super::PartitionType,
@@ -181,13 +181,13 @@ fn add_record(
         }
     );
 
-    record_set_hash_key(&mut record, options, cli)?;
+    record_set_hash_key(&mut record, options, keys)?;
     info.add_record(record);
     Ok(())
 }
 
 fn modify_record(
-    cli: &Cli,
+    keys: &KeyStore,
     options: &ModParttabOptions,
     image: &mut PartitionTableImage,
     part_type: &super::PartitionType,
@@ -238,7 +238,7 @@ fn modify_record(
     }
     println!();
 
-    record_set_hash_key(record, options, cli)?;
+    record_set_hash_key(record, options, keys)?;
 
     Ok(())
 }
@@ -5,6 +5,7 @@ use std::path::PathBuf;
 use crate::cli::{Cli, NvdmSubCommand};
 
 mod parse;
+mod scan;
 
 /// List all entries of an NVDM image.
 #[derive(Parser)]
@@ -34,11 +35,32 @@ pub struct ParseOptions {
     pub block_size: Option<u32>,
 }
 
+/// Scan all data items of an NVDM image for secrets.
+#[derive(Parser)]
+pub struct ScanOptions {
+    /// The input NVDM flash image to be scanned.
+    #[arg(value_name = "FILE")]
+    file: Option<PathBuf>,
+
+    /// Print all findings as JSON.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    json: bool,
+
+    /// Do not report high-entropy key candidates.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    no_key_candidates: bool,
+
+    /// Flash block size (default is 4096)
+    #[arg(short, long, value_name = "SIZE")]
+    pub block_size: Option<u32>,
+}
+
 pub fn main(cli: &Cli, command: Option<&NvdmSubCommand>) -> Result<(), Error> {
     match command {
         Some(NvdmSubCommand::View { options }) => {
             parse::parse(cli, options)?;
         },
+        Some(NvdmSubCommand::Scan { options }) => scan::scan(cli, options)?,
         _ => {}
     }
 
@@ -0,0 +1,22 @@
+use amebazii::{
+    error::Error,
+    scan::SecretScanner,
+    types::{FromStream, NVDM, NVDM_PORT_PEB_SIZE},
+};
+
+use crate::cli::{nvdm::ScanOptions, util, Cli};
+
+pub fn scan(cli: &Cli, options: &ScanOptions) -> Result<(), Error> {
+    let input_file = options.file.as_ref().unwrap();
+    let mut fp = match util::open_file(cli, input_file.clone(), None) {
+        Ok(fp) => fp,
+        Err(_) => return Ok(()),
+    };
+
+    let mut nvdm = NVDM::from_peb_size(options.block_size.unwrap_or(NVDM_PORT_PEB_SIZE));
+    nvdm.read_from(&mut fp)?;
+
+    let mut scanner = SecretScanner::default();
+    scanner.set_key_candidates(!options.no_key_candidates);
+    util::print_secret_findings(&scanner.scan_nvdm(&nvdm), options.json)
+}
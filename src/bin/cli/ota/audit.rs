@@ -0,0 +1,36 @@
+use colored::Colorize;
+use std::io::Seek;
+
+use amebazii::{
+    audit::audit_ota,
+    error::Error,
+    types::{from_stream, OTAImage},
+};
+
+use super::AuditOptions;
+use crate::cli::{debug, error, util, Cli};
+
+pub fn audit(cli: &Cli, options: &AuditOptions) -> Result<(), Error> {
+    let input_file = options.file.as_ref().unwrap();
+    let mut fp = match util::open_file(cli, input_file.clone(), None) {
+        Ok(fp) => fp,
+        Err(_) => return Ok(()),
+    };
+
+    let mut image: OTAImage = from_stream(&mut fp)?;
+    debug!(cli, "Finished parsing file: {}", input_file.display());
+
+    let keys = util::load_key_store(cli)?;
+    let key_source = options.decrypt.key_source(&keys)?;
+    for (idx, subimage) in image.get_subimages_mut().iter_mut().enumerate() {
+        if subimage.is_encrypted() {
+            if let Err(e) = subimage.decrypt(&key_source) {
+                error!("Could not decrypt subimage {}: {}", idx, e);
+            }
+        }
+    }
+
+    fp.seek(std::io::SeekFrom::Start(0))?;
+    let report = audit_ota(&image, &mut fp)?;
+    util::print_audit_report(&report, options.json)
+}
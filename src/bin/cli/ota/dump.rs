@@ -4,12 +4,15 @@ use std::{io::Write, path::PathBuf};
 use crate::cli::{debug, error, util, Cli};
 use amebazii::types::{from_stream, image::ota::OTAImage, section};
 
+use super::DecryptOptions;
+
 pub fn dump_sections(
     cli: &Cli,
     file: PathBuf,
     img_idx: u32,
     outfile: PathBuf,
     section: Option<u32>,
+    decrypt: &DecryptOptions,
 ) -> Result<(), amebazii::error::Error> {
     let fp = util::open_file(cli, file.clone(), None);
     if fp.is_err() {
@@ -25,10 +28,10 @@ pub fn dump_sections(
     }
 
     let mut reader = fp.unwrap();
-    let image: OTAImage = from_stream(&mut reader)?;
+    let mut image: OTAImage = from_stream(&mut reader)?;
     debug!(cli, "Finished parsing file: {}", file.display());
 
-    let subimages = image.get_subimages();
+    let subimages = image.get_subimages_mut();
     if subimages.len() <= img_idx as usize {
         error!(
             "Invalid subimage index: {} (maximum is {})",
@@ -38,7 +41,7 @@ pub fn dump_sections(
         return Ok(());
     }
 
-    let subimage = &subimages[img_idx as usize];
+    let subimage = &mut subimages[img_idx as usize];
     println!(
         "[{}] {}: {:?}",
         img_idx,
@@ -46,10 +49,11 @@ pub fn dump_sections(
         subimage.header.img_type
     );
 
-    // REVISIT: encryption not supported here
-    if subimage.header.is_encrypt {
-        error!("{}", "Encryption not supported");
-        return Ok(());
+    let keys = util::load_key_store(cli)?;
+    if subimage.is_encrypted() {
+        let key_source = decrypt.key_source(&keys)?;
+        debug!(cli, "Decrypting subimage using {:?}", key_source);
+        subimage.decrypt(&key_source)?;
     }
 
     let sections = subimage.get_sections();
@@ -1,17 +1,73 @@
 use clap::Parser;
+use clap_num::maybe_hex;
 use std::path::PathBuf;
 
 use super::{headings, Cli, OtaSubCommand};
 use amebazii::error::Error;
+use amebazii::keystore::KeyStore;
 use amebazii::map::{
     AddressRange, DTCM_RAM, PSRAM, RAM_FUN_TABLE, RAM_IMG_SIGN, VECTORS_RAM, XIP_FLASH_C,
     XIP_FLASH_P,
 };
+use amebazii::types::KeySource;
 
+mod audit;
 mod dump;
 mod parse;
+mod patch;
 mod relink;
+mod repack;
+mod repair;
 mod resign;
+mod resolve;
+mod scan;
+mod symbolize;
+mod verify;
+
+/// Options to decrypt encrypted subimages
+#[derive(Parser)]
+pub struct DecryptOptions {
+    /// Use the user key from the image header to decrypt all sections (instead of the
+    /// cipher key stored in the FST).
+    #[arg(
+        long,
+        action = clap::ArgAction::SetTrue,
+        conflicts_with = "cipher_key",
+        help_heading = headings::ENCRYPTION_OPTIONS
+    )]
+    pub user_key: bool,
+
+    /// The AES key used to decrypt encrypted subimages (name, hex or file).
+    #[arg(long, value_name = "KEY", help_heading = headings::ENCRYPTION_OPTIONS)]
+    pub cipher_key: Option<String>,
+
+    /// The AES IV used to decrypt encrypted subimages in CBC mode (name, hex or file).
+    #[arg(
+        long,
+        value_name = "IV",
+        requires = "cipher_key",
+        help_heading = headings::ENCRYPTION_OPTIONS
+    )]
+    pub cipher_iv: Option<String>,
+}
+
+impl DecryptOptions {
+    pub fn key_source(&self, keys: &KeyStore) -> Result<KeySource, Error> {
+        if let Some(key) = &self.cipher_key {
+            let iv = match &self.cipher_iv {
+                Some(iv) => Some(keys.resolve(iv)?),
+                None => None,
+            };
+            return Ok(KeySource::Custom(keys.resolve(key)?, iv));
+        }
+
+        if self.user_key {
+            Ok(KeySource::UserKey)
+        } else {
+            Ok(KeySource::Fst)
+        }
+    }
+}
 
 #[derive(Parser)]
 pub struct RelinkOptions {
@@ -31,6 +87,12 @@ pub struct RelinkOptions {
     #[arg(short, long, action = clap::ArgAction::SetTrue)]
     cap_length: bool,
 
+    /// A ROM symbol file of the SDK (`name = 0x...;` entries), can be repeated.
+    ///
+    /// All symbols are added as absolute function or object symbols.
+    #[arg(long, value_name = "FILE")]
+    rom_symbols: Vec<PathBuf>,
+
     /// Start address of the RAM vector table.
     #[arg(long, value_name = "ADDR", help_heading = headings::ADDRESS_OPTIONS)]
     ram_vector_start: Option<u64>,
@@ -86,6 +148,9 @@ pub struct RelinkOptions {
     /// End address of the XIP plaintext section in flash.
     #[arg(long, value_name = "ADDR", help_heading = headings::ADDRESS_OPTIONS)]
     xip_p_end: Option<u64>,
+
+    #[command(flatten)]
+    decrypt: DecryptOptions,
 }
 
 #[derive(Parser)]
@@ -97,6 +162,11 @@ pub struct ParseOptions {
     /// specifies whether the input file stores a bootloader image
     #[arg(long, action = clap::ArgAction::SetTrue)]
     boot: bool,
+
+    #[command(flatten)]
+    decrypt: DecryptOptions,
+    #[command(flatten)]
+    identify: super::IdentifyKeysOptions,
 }
 
 #[derive(Parser)]
@@ -107,7 +177,10 @@ pub struct ReSignOptions {
     #[command(flatten)]
     pub output: super::OutputOptionsInPlace,
 
-    /// The key to be used for the signing operation. (hex or file)
+    /// The key to be used for the signing operation. (name, hex or file)
+    ///
+    /// When using an external signer, this is the key name passed to the signer
+    /// (default: fw_hash_key).
     #[arg(short, long, value_name = "KEY")]
     pub key: Option<String>,
 
@@ -120,6 +193,191 @@ pub struct ReSignOptions {
     /// This flag ensures that the signing operation uses the same algorithm as the previous one.
     #[arg(long, action = clap::ArgAction::SetTrue)]
     pub same_algo: bool,
+
+    #[command(flatten)]
+    pub decrypt: DecryptOptions,
+
+    #[command(flatten)]
+    pub signer: super::SignerOptions,
+}
+
+#[derive(Parser)]
+pub struct PatchOptions {
+    #[command(flatten)]
+    pub input: super::InputOptions,
+
+    #[command(flatten)]
+    pub output: super::OutputOptionsInPlace,
+
+    /// The JSON patch file.
+    #[arg(short, long, value_name = "FILE", required = true)]
+    pub patch: PathBuf,
+
+    /// The key to be used for the signing operation. (name, hex or file)
+    ///
+    /// When using an external signer, this is the key name passed to the signer
+    /// (default: fw_hash_key).
+    #[arg(short, long, value_name = "KEY")]
+    pub key: Option<String>,
+
+    #[command(flatten)]
+    pub decrypt: DecryptOptions,
+
+    #[command(flatten)]
+    pub signer: super::SignerOptions,
+}
+
+#[derive(Parser)]
+pub struct RepackOptions {
+    /// The (relinked) ELF file containing the new section data.
+    #[arg(value_name = "ELF")]
+    pub elf: PathBuf,
+
+    #[command(flatten)]
+    pub output: super::OutputOptions,
+
+    /// The original OTA image used as template.
+    #[arg(short, long, value_name = "FILE", required = true)]
+    pub template: PathBuf,
+
+    /// The key to be used for the signing operation. (name, hex or file)
+    ///
+    /// When using an external signer, this is the key name passed to the signer
+    /// (default: fw_hash_key).
+    #[arg(short, long, value_name = "KEY")]
+    pub key: Option<String>,
+
+    #[command(flatten)]
+    pub decrypt: DecryptOptions,
+
+    #[command(flatten)]
+    pub signer: super::SignerOptions,
+}
+
+#[derive(Parser)]
+pub struct RepairOptions {
+    #[command(flatten)]
+    pub input: super::InputOptions,
+
+    #[command(flatten)]
+    pub output: super::OutputOptionsInPlace,
+
+    /// The key to be used for the signing operation. (name, hex or file)
+    ///
+    /// When using an external signer, this is the key name passed to the signer
+    /// (default: fw_hash_key).
+    #[arg(short, long, value_name = "KEY")]
+    pub key: Option<String>,
+
+    #[command(flatten)]
+    pub decrypt: DecryptOptions,
+
+    #[command(flatten)]
+    pub signer: super::SignerOptions,
+}
+
+#[derive(Parser)]
+pub struct ResolveOptions {
+    /// The OTA image file.
+    #[arg(value_name = "FILE", required = true)]
+    pub file: Option<PathBuf>,
+
+    /// The runtime addresses (or offsets) to resolve, given as decimal or hexadecimal values.
+    #[arg(value_name = "ADDR", required = true, value_parser = maybe_hex::<u64>)]
+    pub values: Vec<u64>,
+
+    /// The absolute flash offset of the OTA image (start of the firmware partition).
+    #[arg(long, value_name = "OFFSET", value_parser = maybe_hex::<u64>)]
+    pub base: Option<u64>,
+
+    /// Treat all values as offsets relative to the start of the OTA image.
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "flash_offsets")]
+    pub file_offsets: bool,
+
+    /// Treat all values as absolute flash offsets.
+    #[arg(long, action = clap::ArgAction::SetTrue, requires = "base")]
+    pub flash_offsets: bool,
+
+    #[command(flatten)]
+    pub decrypt: DecryptOptions,
+}
+
+#[derive(Parser)]
+pub struct SymbolizeOptions {
+    /// The captured console log (hard fault dump or backtrace).
+    #[arg(value_name = "LOG", required = true)]
+    pub log: Option<PathBuf>,
+
+    /// The OTA image running on the device, used to resolve sub-images and sections.
+    #[arg(short, long, value_name = "FILE")]
+    pub image: Option<PathBuf>,
+
+    /// The absolute flash offset of the OTA image (start of the firmware partition).
+    #[arg(long, value_name = "OFFSET", requires = "image", value_parser = maybe_hex::<u64>)]
+    pub base: Option<u64>,
+
+    /// A relinked or original ELF file providing symbols.
+    #[arg(short, long, value_name = "FILE")]
+    pub elf: Option<PathBuf>,
+
+    /// A ROM symbol file of the SDK (`name = 0x...;` entries), can be repeated.
+    #[arg(short, long, value_name = "FILE")]
+    pub rom_symbols: Vec<PathBuf>,
+
+    /// Print all addresses as JSON.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub json: bool,
+
+    #[command(flatten)]
+    pub decrypt: DecryptOptions,
+}
+
+#[derive(Parser)]
+pub struct VerifyOptions {
+    /// The input firmware file to be verified.
+    #[arg(value_name = "FILE")]
+    file: Option<PathBuf>,
+
+    /// The hash key used to verify all signatures. (name, hex or file)
+    ///
+    /// If not set, the firmware hash key of the key store will be used.
+    #[arg(short, long, value_name = "KEY")]
+    key: Option<String>,
+
+    #[command(flatten)]
+    decrypt: DecryptOptions,
+}
+
+#[derive(Parser)]
+pub struct AuditOptions {
+    /// The input firmware file to be audited.
+    #[arg(value_name = "FILE")]
+    file: Option<PathBuf>,
+
+    /// Print the report as JSON.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    json: bool,
+
+    #[command(flatten)]
+    decrypt: DecryptOptions,
+}
+
+#[derive(Parser)]
+pub struct ScanOptions {
+    /// The input firmware file to be scanned.
+    #[arg(value_name = "FILE")]
+    file: Option<PathBuf>,
+
+    /// Print all findings as JSON.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    json: bool,
+
+    /// Do not report high-entropy key candidates.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    no_key_candidates: bool,
+
+    #[command(flatten)]
+    decrypt: DecryptOptions,
 }
 
 fn get_address_range(
@@ -137,68 +395,74 @@ fn get_address_range(
 
 pub fn main(cli: &Cli, command: Option<&OtaSubCommand>) -> Result<(), Error> {
     match command {
-        Some(OtaSubCommand::Parse { options }) => {
-            if let Some(options) = options {
-                parse::parse(cli, options)?;
-            }
-        }
+        Some(OtaSubCommand::Parse { options }) => parse::parse(cli, options)?,
         Some(OtaSubCommand::Dump {
             file,
             subimage,
             outdir,
             section,
+            decrypt,
         }) => dump::dump_sections(
             cli,
             file.clone().unwrap(),
             subimage.unwrap(),
             outdir.clone().unwrap(),
             *section,
+            decrypt,
         )?,
         Some(OtaSubCommand::Resign { options }) => {
             resign::re_sign(cli, options)?;
         }
+        Some(OtaSubCommand::Patch { options }) => patch::patch(cli, options)?,
+        Some(OtaSubCommand::Repack { options }) => repack::repack(cli, options)?,
+        Some(OtaSubCommand::Repair { options }) => repair::repair(cli, options)?,
+        Some(OtaSubCommand::Resolve { options }) => resolve::resolve(cli, options)?,
+        Some(OtaSubCommand::Verify { options }) => verify::verify(cli, options)?,
+        Some(OtaSubCommand::Audit { options }) => audit::audit(cli, options)?,
+        Some(OtaSubCommand::Scan { options }) => scan::scan(cli, options)?,
+        Some(OtaSubCommand::Symbolize { options }) => symbolize::symbolize(cli, options)?,
         Some(OtaSubCommand::Relink { options }) => {
             // wrapping these options is somewhat ugly
-            if let Some(options) = options {
-                let relink_options = relink::Options {
-                    infile: options.file.clone().unwrap(),
-                    outfile: options.outfile.clone().unwrap(),
-                    save_intermediate: options.save_intermediate.clone(),
-                    cap_length: *&options.cap_length,
-                    ram_vector: get_address_range(
-                        &options.ram_vector_start,
-                        &options.ram_vector_end,
-                        &VECTORS_RAM,
-                    ),
-                    ram_func_table: get_address_range(
-                        &options.ram_func_table_start,
-                        &options.ram_func_table_end,
-                        &RAM_FUN_TABLE,
-                    ),
-                    ram_img_signature: get_address_range(
-                        &options.ram_img_signature,
-                        &options.ram_img_signature_end,
-                        &RAM_IMG_SIGN,
-                    ),
-                    ram_text: get_address_range(
-                        &options.ram_code_text,
-                        &options.ram_code_text_end,
-                        &DTCM_RAM,
-                    ),
-                    psram_text: get_address_range(&options.dtcm_ram, &options.dtcm_ram_end, &PSRAM),
-                    xip_c_text: get_address_range(
-                        &options.xip_c_start,
-                        &options.xip_c_end,
-                        &XIP_FLASH_C,
-                    ),
-                    xip_p_text: get_address_range(
-                        &options.xip_p_start,
-                        &options.xip_p_end,
-                        &XIP_FLASH_P,
-                    ),
-                };
-                relink::relink(&cli, &relink_options)?;
-            }
+            let relink_options = relink::Options {
+                infile: options.file.clone().unwrap(),
+                outfile: options.outfile.clone().unwrap(),
+                save_intermediate: options.save_intermediate.clone(),
+                cap_length: *&options.cap_length,
+                rom_symbols: options.rom_symbols.clone(),
+                decrypt: &options.decrypt,
+                ram_vector: get_address_range(
+                    &options.ram_vector_start,
+                    &options.ram_vector_end,
+                    &VECTORS_RAM,
+                ),
+                ram_func_table: get_address_range(
+                    &options.ram_func_table_start,
+                    &options.ram_func_table_end,
+                    &RAM_FUN_TABLE,
+                ),
+                ram_img_signature: get_address_range(
+                    &options.ram_img_signature,
+                    &options.ram_img_signature_end,
+                    &RAM_IMG_SIGN,
+                ),
+                ram_text: get_address_range(
+                    &options.ram_code_text,
+                    &options.ram_code_text_end,
+                    &DTCM_RAM,
+                ),
+                psram_text: get_address_range(&options.dtcm_ram, &options.dtcm_ram_end, &PSRAM),
+                xip_c_text: get_address_range(
+                    &options.xip_c_start,
+                    &options.xip_c_end,
+                    &XIP_FLASH_C,
+                ),
+                xip_p_text: get_address_range(
+                    &options.xip_p_start,
+                    &options.xip_p_end,
+                    &XIP_FLASH_P,
+                ),
+            };
+            relink::relink(&cli, &relink_options)?;
         }
         _ => (),
     }
@@ -1,16 +1,13 @@
 use amebazii::{
-    keys::HASH_KEY,
-    types::{BootImage, HashAlgo, KeyBlock},
+    keystore::{KeyStore, FW_HASH_KEY_NAME},
+    types::{BootImage, HashAlgo, KeyBlock, KeyIdentification},
 };
 use colored::Colorize;
 use openssl::memcmp::eq;
 use std::io::{Read, Seek};
 
-use crate::cli::{debug, util, Cli};
-use amebazii::{
-    keys::KEY_PAIR_003,
-    types::{from_stream, BinarySize, EncryptedOr, ImageHeader, OTAImage, SubImage},
-};
+use crate::cli::{debug, error, util, Cli};
+use amebazii::types::{from_stream, BinarySize, EncryptedOr, ImageHeader, OTAImage, SubImage};
 
 use super::ParseOptions;
 
@@ -23,21 +20,60 @@ pub fn parse(cli: &Cli, options: &ParseOptions) -> Result<(), amebazii::error::E
         }
 
         let mut fp = file_reader.unwrap();
+        let keys = util::load_key_store(cli)?;
         if options.boot {
-            let image: BootImage = from_stream(&mut fp)?;
+            let mut image: BootImage = from_stream(&mut fp)?;
             debug!(cli, "Finished parsing file: {}", input_file.display());
-            dump_bootloader(&image, &mut fp)?;
+
+            if let Err(e) = image.decrypt(&options.decrypt.key_source(&keys)?) {
+                error!("Could not decrypt boot image: {}", e);
+            }
+            dump_bootloader(&image, &keys, &mut fp)?;
+
+            if options.identify.identify_keys {
+                let known_keys = options.identify.key_store(&keys)?.known_keys();
+                fp.seek(std::io::SeekFrom::Start(0))?;
+                let result = image.identify_key(&mut fp, &known_keys)?;
+                dump_key_identification(&[result]);
+            }
         } else {
-            let image: OTAImage = from_stream(&mut fp)?;
+            let mut image: OTAImage = from_stream(&mut fp)?;
             debug!(cli, "Finished parsing file: {}", input_file.display());
-            dump_ota_image(&image, &mut fp)?;
+
+            for (idx, subimage) in image.get_subimages_mut().iter_mut().enumerate() {
+                if subimage.is_encrypted() {
+                    if let Err(e) = subimage.decrypt(&options.decrypt.key_source(&keys)?) {
+                        error!("Could not decrypt subimage {}: {}", idx, e);
+                    }
+                }
+            }
+            dump_ota_image(&image, &keys, &mut fp)?;
+
+            if options.identify.identify_keys {
+                let known_keys = options.identify.key_store(&keys)?.known_keys();
+                fp.seek(std::io::SeekFrom::Start(0))?;
+                let results = image.identify_keys(&mut fp, &known_keys)?;
+                dump_key_identification(&results);
+            }
         }
     }
     Ok(())
 }
 
+fn dump_key_identification(results: &[KeyIdentification]) {
+    println!(
+        "{} {} {}",
+        "=".repeat(40),
+        "Key Identification".bold(),
+        "=".repeat(40)
+    );
+    util::print_key_identification(results);
+    println!("{}\n", "=".repeat(100));
+}
+
 fn dump_ota_image(
     ota_image: &OTAImage,
+    keys: &KeyStore,
     fp: &mut std::fs::File,
 ) -> Result<(), amebazii::error::Error> {
     println!(
@@ -61,11 +97,12 @@ fn dump_ota_image(
         }
     }
 
-    println!(
-        "\n{}: ({})",
-        "OTA-Signature".bold(),
-        "using default hash key".italic()
-    );
+    let key_name = if keys.get_key(FW_HASH_KEY_NAME).is_some() {
+        "using firmware hash key from key store"
+    } else {
+        "using default hash key"
+    };
+    println!("\n{}: ({})", "OTA-Signature".bold(), key_name.italic());
 
     let first_subimage = ota_image.get_subimage(0).unwrap();
     if let EncryptedOr::Plain(fst) = &first_subimage.fst {
@@ -73,7 +110,7 @@ fn dump_ota_image(
         if let Some(algo) = &fst.hash_algo {
             fp.seek(std::io::SeekFrom::Start(224))?;
             let signature =
-                OTAImage::ota_signature_from_stream(fp, *algo, Some(KEY_PAIR_003.get_priv_key()))?;
+                OTAImage::ota_signature_from_stream(fp, *algo, Some(keys.get_fw_hash_key()))?;
             let image_signature = ota_image.get_ota_signature();
             print!("  - {:?} ", hex::encode(image_signature));
             if eq(&signature, image_signature) {
@@ -110,7 +147,7 @@ fn dump_ota_image(
             "-".repeat(45)
         );
 
-        dump_subimage(idx, subimage, fp, offset)?;
+        dump_subimage(idx, subimage, keys, fp, offset)?;
         println!("{}\n", "-".repeat(100));
 
         if let Some(next_offset) = subimage.header.next_offset {
@@ -126,6 +163,7 @@ fn dump_ota_image(
 fn dump_subimage(
     _idx: usize,
     subimage: &SubImage,
+    keys: &KeyStore,
     fp: &mut std::fs::File,
     offset: u64,
 ) -> Result<(), amebazii::error::Error> {
@@ -155,8 +193,19 @@ fn dump_subimage(
 
     println!("\n{}:", "Security".bold());
     print!("  - Encryption: ");
-    if let EncryptedOr::Plain(fst) = &subimage.fst {
+    if !subimage.header.is_encrypt {
         println!("{}", "disabled".yellow().italic());
+    } else if let EncryptedOr::Plain(fst) = &subimage.fst {
+        println!(
+            "{} ({:?})",
+            "enabled".green(),
+            fst.enc_algo.unwrap_or_default()
+        );
+    } else {
+        println!("{}", "enabled".green());
+    }
+
+    if let EncryptedOr::Plain(fst) = &subimage.fst {
         print!("  - Hashing: ");
         if let Some(hash_algo) = &fst.hash_algo {
             println!("{} ({:?})", "enabled".green(), hash_algo);
@@ -173,14 +222,11 @@ fn dump_subimage(
                         ImageHeader::binary_size() + subimage.header.segment_size as usize + 224
                     ];
                 fp.read_exact(&mut buffer)?;
-                hash = hash_algo.compute_hash(&buffer, Some(KEY_PAIR_003.get_priv_key()))?
+                hash = hash_algo.compute_hash(&buffer, Some(keys.get_fw_hash_key()))?
             } else {
                 fp.seek(std::io::SeekFrom::Start(offset))?;
-                hash = subimage.signature_from_stream(
-                    fp,
-                    *hash_algo,
-                    Some(KEY_PAIR_003.get_priv_key()),
-                )?;
+                hash =
+                    subimage.signature_from_stream(fp, *hash_algo, Some(keys.get_fw_hash_key()))?;
             }
 
             if eq(&hash, subimage_hash) {
@@ -189,8 +235,10 @@ fn dump_subimage(
                 println!("{}", "invalid/encrypted/wrong key".red().italic());
             }
         }
-    } else {
-        println!("{}", "enabled".green());
+    }
+
+    if subimage.is_encrypted() {
+        return Ok(());
     }
 
     println!("\n{}:", "Sections".bold());
@@ -211,6 +259,7 @@ fn dump_subimage(
 
 fn dump_bootloader(
     image: &BootImage,
+    keys: &KeyStore,
     fp: &mut std::fs::File,
 ) -> Result<(), amebazii::error::Error> {
     println!(
@@ -242,7 +291,7 @@ fn dump_bootloader(
             + ImageHeader::binary_size()
     ];
     fp.read_exact(&mut buffer)?;
-    let hash = HashAlgo::Sha256.compute_hash(&buffer, Some(HASH_KEY))?;
+    let hash = HashAlgo::Sha256.compute_hash(&buffer, Some(keys.get_hash_key()))?;
     if eq(&hash, image_hash) {
         println!("{}", " OK".green());
     } else {
@@ -250,7 +299,7 @@ fn dump_bootloader(
         println!("  - Hash: {}", hex::encode(hash));
     }
 
-    if !image.header.is_encrypt {
+    if !image.is_encrypted() {
         println!("\n{}:", "Sections".bold());
         println!(
             "  [0] - {} (length: 0x{:08x}, load: 0x{:08x}, entry: 0x{:08x})",
@@ -0,0 +1,70 @@
+use colored::Colorize;
+use std::io::{Seek, Write};
+
+use amebazii::{
+    conf::PatchCfg,
+    keystore::FW_HASH_KEY_NAME,
+    types::{from_stream, OTAImage, SubImage, ToStream},
+};
+
+use crate::cli::{debug, util, Cli};
+
+use super::PatchOptions;
+
+pub fn patch(cli: &Cli, options: &PatchOptions) -> Result<(), amebazii::error::Error> {
+    if let Some(input_file) = &options.input.file {
+        let input = util::open_file(cli, input_file.clone(), None);
+        if input.is_err() {
+            return Ok(());
+        }
+
+        let patch_file = util::open_file(cli, options.patch.clone(), Some("Patch"));
+        if patch_file.is_err() {
+            return Ok(());
+        }
+        let patch: PatchCfg = serde_json::from_reader(&mut patch_file.unwrap())?;
+
+        let mut input = input.unwrap();
+        let mut ota: OTAImage = from_stream(&mut input)?;
+
+        // encrypted subimages are encrypted again when written
+        let keys = util::load_key_store(cli)?;
+        if ota.get_subimages().iter().any(SubImage::is_encrypted) {
+            let key_source = options.decrypt.key_source(&keys)?;
+            debug!(cli, "Decrypting subimages using {:?}", key_source);
+            ota.decrypt(&key_source)?;
+        }
+
+        let key_id = options.key.as_deref().unwrap_or(FW_HASH_KEY_NAME);
+        let signer = options.signer.signer(key_id, || match &options.key {
+            Some(key) => keys.resolve(key),
+            None => Ok(keys.get_fw_hash_key().to_vec()),
+        })?;
+        let locations = ota.apply_patch(&patch, signer.as_ref())?;
+
+        println!("{}:", "Patches".bold().underline());
+        for (item, (idx, sect_idx, offset)) in patch.patches.iter().zip(locations) {
+            println!(
+                "  - 0x{:08x} (subimage: {}, section: {}, offset: 0x{:08x}): {} -> {}",
+                item.address,
+                idx,
+                sect_idx,
+                offset,
+                hex::encode(&item.original).red(),
+                hex::encode(&item.replacement).green()
+            );
+            if let Some(description) = &item.description {
+                println!("    {}", description.italic());
+            }
+        }
+
+        let output = util::open_output_file(cli, Some(&options.input), &options.output)?;
+        let mut writer = std::io::BufWriter::new(output);
+        ota.write_to(&mut writer)?;
+        let length = writer.stream_position()?;
+        writer.flush()?;
+        writer.get_ref().set_len(length)?;
+    }
+
+    Ok(())
+}
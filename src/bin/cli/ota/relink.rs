@@ -5,16 +5,21 @@ use std::path::PathBuf;
 
 use crate::cli::{debug, error, util, Cli};
 
+use super::DecryptOptions;
+
 use amebazii::{
     map::AddressRange,
+    symbolize::{SymbolKind, SymbolTable},
     types::{from_stream, section, ImageType, OTAImage, SectionType, SubImage},
 };
 
-pub(super) struct Options {
+pub(super) struct Options<'o> {
     pub infile: PathBuf,
     pub outfile: PathBuf,
     pub save_intermediate: Option<PathBuf>,
     pub cap_length: bool,
+    pub rom_symbols: Vec<PathBuf>,
+    pub decrypt: &'o DecryptOptions,
 
     // linker options
     pub ram_vector: AddressRange,
@@ -45,13 +50,20 @@ pub fn relink(cli: &Cli, options: &Options) -> Result<(), amebazii::error::Error
     }
 
     let mut reader = fp.unwrap();
-    let image: OTAImage = from_stream(&mut reader)?;
+    let mut image: OTAImage = from_stream(&mut reader)?;
     debug!(
         cli,
         "Parsed OTA image with {} subimages",
         image.get_subimages().len()
     );
 
+    let keys = util::load_key_store(cli)?;
+    if image.get_subimages().iter().any(SubImage::is_encrypted) {
+        let key_source = options.decrypt.key_source(&keys)?;
+        debug!(cli, "Decrypting subimages using {:?}", key_source);
+        image.decrypt(&key_source)?;
+    }
+
     if let Some(outdir) = &options.save_intermediate {
         debug!(cli, "Creating directory: {}", outdir.display());
         fs::create_dir(outdir)?;
@@ -139,10 +151,45 @@ pub fn relink(cli: &Cli, options: &Options) -> Result<(), amebazii::error::Error
         }
     }
 
+    add_rom_symbols(cli, options, &mut data)?;
     create_obj(cli, options, data)?;
     Ok(())
 }
 
+fn add_rom_symbols(
+    cli: &Cli,
+    options: &Options,
+    data: &mut ElfData<'_>,
+) -> Result<(), amebazii::error::Error> {
+    for rom_file in &options.rom_symbols {
+        debug!(cli, "Reading ROM symbols from: {}", rom_file.display());
+        let script = fs::read_to_string(rom_file)?;
+        let symbols = SymbolTable::from_ld_script(&script)?;
+        println!(
+            "{}: {} symbols from {}",
+            "ROM Symbols".bold().underline(),
+            symbols.len(),
+            rom_file.display()
+        );
+
+        for rom_symbol in symbols.get_symbols() {
+            let (st_type, st_value) = match rom_symbol.kind {
+                // functions are identified by the Thumb bit, which is restored here
+                SymbolKind::Function => (object::elf::STT_FUNC, rom_symbol.address | 1),
+                SymbolKind::Object => (object::elf::STT_OBJECT, rom_symbol.address),
+                SymbolKind::Label => (object::elf::STT_NOTYPE, rom_symbol.address),
+            };
+
+            let symbol = data.builder.symbols.add();
+            symbol.name = rom_symbol.name.as_bytes().to_vec().into();
+            symbol.st_value = st_value;
+            symbol.st_shndx = object::elf::SHN_ABS;
+            symbol.set_st_info(object::elf::STB_GLOBAL, st_type);
+        }
+    }
+    Ok(())
+}
+
 fn extract_ram_from_fhwss(
     cli: &Cli,
     options: &Options,
@@ -0,0 +1,84 @@
+use colored::Colorize;
+use std::{
+    fs,
+    io::{Seek, Write},
+};
+
+use amebazii::{
+    build,
+    keystore::FW_HASH_KEY_NAME,
+    types::{from_stream, OTAImage, SubImage, ToStream},
+};
+
+use crate::cli::{debug, error, util, Cli};
+
+use super::RepackOptions;
+
+pub fn repack(cli: &Cli, options: &RepackOptions) -> Result<(), amebazii::error::Error> {
+    let template = util::open_file(cli, options.template.clone(), Some("Template"));
+    if template.is_err() {
+        return Ok(());
+    }
+
+    let mut template = template.unwrap();
+    let mut ota: OTAImage = from_stream(&mut template)?;
+
+    // encrypted subimages are encrypted again when written
+    let keys = util::load_key_store(cli)?;
+    if ota.get_subimages().iter().any(SubImage::is_encrypted) {
+        let key_source = options.decrypt.key_source(&keys)?;
+        debug!(cli, "Decrypting subimages using {:?}", key_source);
+        ota.decrypt(&key_source)?;
+    }
+
+    debug!(cli, "Reading ELF file: {:#?}", options.elf.display());
+    let elf_data = match fs::read(&options.elf) {
+        Ok(data) => data,
+        Err(e) => {
+            error!(
+                "Failed to read ELF file {:#?}: {}",
+                options.elf.display(),
+                e
+            );
+            return Ok(());
+        }
+    };
+
+    let key_id = options.key.as_deref().unwrap_or(FW_HASH_KEY_NAME);
+    let signer = options.signer.signer(key_id, || match &options.key {
+        Some(key) => keys.resolve(key),
+        None => Ok(keys.get_fw_hash_key().to_vec()),
+    })?;
+    let report = build::repack_ota_image(&elf_data, &mut ota, signer.as_ref())?;
+
+    println!("{}:", "OTA Image".bold().underline());
+    for (i, subimage) in ota.get_subimages().iter().enumerate() {
+        println!(
+            "  [{}] {:?} (size: 0x{:08x})",
+            i, subimage.header.img_type, subimage.header.segment_size
+        );
+        for section in subimage.get_sections() {
+            println!(
+                "      - {:?} (length: 0x{:08x}, load: 0x{:08x})",
+                section.header.sect_type,
+                section.entry_header.length,
+                section.entry_header.load_address
+            );
+        }
+    }
+    for check in report.get_failures() {
+        debug!(
+            cli,
+            "Updated {}: {} -> {}", check.kind, check.actual, check.expected
+        );
+    }
+
+    // an existing output file may be larger than the new image
+    let output = util::open_output_file(cli, None, &options.output)?;
+    let mut writer = std::io::BufWriter::new(output);
+    ota.write_to(&mut writer)?;
+    let length = writer.stream_position()?;
+    writer.flush()?;
+    writer.get_ref().set_len(length)?;
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use colored::Colorize;
+use std::io::{Seek, Write};
+
+use amebazii::{
+    keystore::FW_HASH_KEY_NAME,
+    types::{from_stream, OTAImage, SubImage, ToStream},
+};
+
+use crate::cli::{debug, util, Cli};
+
+use super::RepairOptions;
+
+pub fn repair(cli: &Cli, options: &RepairOptions) -> Result<(), amebazii::error::Error> {
+    if let Some(input_file) = &options.input.file {
+        let input = util::open_file(cli, input_file.clone(), None);
+        if input.is_err() {
+            return Ok(());
+        }
+
+        let mut input = input.unwrap();
+        let mut ota: OTAImage = from_stream(&mut input)?;
+
+        // section fields of encrypted subimages can only be repaired in plaintext
+        let keys = util::load_key_store(cli)?;
+        if ota.get_subimages().iter().any(SubImage::is_encrypted) {
+            let key_source = options.decrypt.key_source(&keys)?;
+            debug!(cli, "Decrypting subimages using {:?}", key_source);
+            ota.decrypt(&key_source)?;
+        }
+
+        let key_id = options.key.as_deref().unwrap_or(FW_HASH_KEY_NAME);
+        let signer = options.signer.signer(key_id, || match &options.key {
+            Some(key) => keys.resolve(key),
+            None => Ok(keys.get_fw_hash_key().to_vec()),
+        })?;
+        let report = ota.normalize_with(signer.as_ref())?;
+
+        println!(
+            "{} {} {}",
+            "=".repeat(43),
+            "OTA Repair".bold(),
+            "=".repeat(45)
+        );
+        for check in report.get_failures() {
+            println!("  - {}:", check.kind.to_string().bold());
+            println!("    old: {}", check.actual.to_string().red());
+            println!("    new: {}", check.expected.to_string().green());
+        }
+        if report.is_ok() {
+            println!("  No changes, the image is already consistent");
+        }
+        println!("{}", "=".repeat(100));
+
+        // the repaired image may be smaller than the input file
+        let output = util::open_output_file(cli, Some(&options.input), &options.output)?;
+        let mut writer = std::io::BufWriter::new(output);
+        ota.write_to(&mut writer)?;
+        let length = writer.stream_position()?;
+        writer.flush()?;
+        writer.get_ref().set_len(length)?;
+    }
+
+    Ok(())
+}
@@ -1,19 +1,15 @@
-use amebazii::types::{AsImage, key_from_hex};
+use amebazii::types::AsImage;
 use colored::Colorize;
 use openssl::memcmp::eq;
-use std::io::Read;
-use std::{fs, io::Write};
+use std::io::Write;
 
 use amebazii::{
-    is_valid_data,
     keys::HASH_KEY,
-    read_valid_data,
-    types::{
-        from_stream, set_default_signature, DataType, EncryptedOr, HashAlgo, OTAImage, ToStream,
-    },
+    keystore::FW_HASH_KEY_NAME,
+    types::{from_stream, set_signature_with, EncryptedOr, HashAlgo, OTAImage, SubImage, ToStream},
 };
 
-use crate::cli::{util, Cli};
+use crate::cli::{debug, util, Cli};
 
 use super::ReSignOptions;
 
@@ -27,27 +23,29 @@ pub fn re_sign(cli: &Cli, options: &ReSignOptions) -> Result<(), amebazii::error
         let mut input = input.unwrap();
         let mut ota: OTAImage = from_stream(&mut input)?;
 
-        let mut hash_key: DataType<32> = None;
-        if let Some(key) = &options.key {
-            if fs::exists(key)? {
-                let mut r = fs::File::open(key)?;
-                read_valid_data!(hash_key, 32, &mut r);
-            } else {
-                hash_key = key_from_hex(key);
-            }
+        // encrypted subimages are encrypted again when written, so that the new
+        // signatures are calculated over the encrypted image
+        let keys = util::load_key_store(cli)?;
+        if ota.get_subimages().iter().any(SubImage::is_encrypted) {
+            let key_source = options.decrypt.key_source(&keys)?;
+            debug!(cli, "Decrypting subimages using {:?}", key_source);
+            ota.decrypt(&key_source)?;
         }
 
-        let hash_key = match hash_key {
-            Option::Some(hash_key) => hash_key,
-            Option::None => HASH_KEY.clone(),
-        };
+        // without a firmware hash key in the key store, HASH_KEY remains the default.
+        // An external signer receives the key name instead of the key itself.
+        let key_id = options.key.as_deref().unwrap_or(FW_HASH_KEY_NAME);
+        let signer = options.signer.signer(key_id, || match &options.key {
+            Some(key) => keys.resolve(key),
+            None => Ok(keys.get_key(FW_HASH_KEY_NAME).unwrap_or(HASH_KEY).to_vec()),
+        })?;
         let algo = if options.use_md5 {
             HashAlgo::Md5
         } else {
             HashAlgo::Sha256
         };
 
-        let first_signature = ota.build_first_signature(Some(&hash_key))?;
+        let first_signature = ota.build_first_signature_with(signer.as_ref())?;
         for (idx, subimage) in ota.get_subimages_mut().iter_mut().enumerate() {
             if let EncryptedOr::Plain(fst) = &mut subimage.fst {
                 if !options.same_algo {
@@ -61,7 +59,7 @@ pub fn re_sign(cli: &Cli, options: &ReSignOptions) -> Result<(), amebazii::error
                 new_signature = first_signature.clone();
                 subimage.set_signature(&first_signature);
             } else {
-                set_default_signature(subimage, Some(&hash_key))?;
+                set_signature_with(subimage, signer.as_ref())?;
                 new_signature = subimage.get_hash().to_vec();
             }
 
@@ -72,7 +70,7 @@ pub fn re_sign(cli: &Cli, options: &ReSignOptions) -> Result<(), amebazii::error
             }
         }
 
-        let new_ota_signature = ota.build_ota_signature(Some(&hash_key))?;
+        let new_ota_signature = ota.build_ota_signature_with(signer.as_ref())?;
         let old_ota_signature = ota.get_ota_signature();
 
         if !eq(&new_ota_signature, old_ota_signature) {
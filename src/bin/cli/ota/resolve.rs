@@ -0,0 +1,77 @@
+use colored::Colorize;
+
+use amebazii::{
+    map::{AddressTranslator, ImageLocation},
+    types::{from_stream, OTAImage, SubImage},
+};
+
+use crate::cli::{debug, error, util, Cli};
+
+use super::ResolveOptions;
+
+pub fn resolve(cli: &Cli, options: &ResolveOptions) -> Result<(), amebazii::error::Error> {
+    if let Some(file) = &options.file {
+        let input = util::open_file(cli, file.clone(), None);
+        if input.is_err() {
+            return Ok(());
+        }
+
+        let mut input = input.unwrap();
+        let mut ota: OTAImage = from_stream(&mut input)?;
+
+        // sections of encrypted subimages can only be mapped in plaintext
+        if ota.get_subimages().iter().any(SubImage::is_encrypted) {
+            let keys = util::load_key_store(cli)?;
+            let key_source = options.decrypt.key_source(&keys)?;
+            debug!(cli, "Decrypting subimages using {:?}", key_source);
+            ota.decrypt(&key_source)?;
+        }
+
+        let mut translator = AddressTranslator::new(&ota);
+        if let Some(base) = options.base {
+            translator = translator.with_flash_offset(base);
+        }
+
+        println!("{}:", "Locations".bold().underline());
+        for value in &options.values {
+            let location = if options.file_offsets {
+                translator.resolve_file_offset(*value)
+            } else if options.flash_offsets {
+                translator.resolve_flash_offset(*value)
+            } else {
+                translator.resolve_address(*value)
+            };
+
+            match location {
+                Some(location) => print_location(&ota, &location),
+                None => {
+                    error!("0x{:08x} is not stored in any (decrypted) section", value);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_location(ota: &OTAImage, location: &ImageLocation) {
+    let sect_type = ota
+        .get_subimage(location.subimage)
+        .and_then(|subimage| subimage.get_sections().get(location.section))
+        .map(|section| format!("{:?}", section.header.sect_type))
+        .unwrap_or_default();
+
+    print!(
+        "  - 0x{:08x} (subimage: {}, section: {} {}, offset: 0x{:08x}) -> file: 0x{:08x}",
+        location.address,
+        location.subimage,
+        location.section,
+        sect_type,
+        location.section_offset,
+        location.file_offset
+    );
+    match location.flash_offset {
+        Some(offset) => println!(", flash: 0x{:08x}", offset),
+        None => println!(),
+    }
+}
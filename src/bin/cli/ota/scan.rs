@@ -0,0 +1,36 @@
+use colored::Colorize;
+
+use amebazii::{
+    error::Error,
+    scan::SecretScanner,
+    types::{from_stream, OTAImage},
+};
+
+use super::ScanOptions;
+use crate::cli::{debug, error, util, Cli};
+
+pub fn scan(cli: &Cli, options: &ScanOptions) -> Result<(), Error> {
+    let input_file = options.file.as_ref().unwrap();
+    let mut fp = match util::open_file(cli, input_file.clone(), None) {
+        Ok(fp) => fp,
+        Err(_) => return Ok(()),
+    };
+
+    let mut image: OTAImage = from_stream(&mut fp)?;
+    debug!(cli, "Finished parsing file: {}", input_file.display());
+
+    let keys = util::load_key_store(cli)?;
+    let key_source = options.decrypt.key_source(&keys)?;
+    for (idx, subimage) in image.get_subimages_mut().iter_mut().enumerate() {
+        if subimage.is_encrypted() {
+            if let Err(e) = subimage.decrypt(&key_source) {
+                error!("Could not decrypt subimage {}: {}", idx, e);
+                continue;
+            }
+        }
+    }
+
+    let mut scanner = SecretScanner::default();
+    scanner.set_key_candidates(!options.no_key_candidates);
+    util::print_secret_findings(&scanner.scan_ota(&image, ""), options.json)
+}
@@ -0,0 +1,138 @@
+use colored::Colorize;
+use std::fs;
+
+use amebazii::{
+    error::Error,
+    map::AddressTranslator,
+    symbolize::{SymbolTable, SymbolizedAddress, Symbolizer},
+    types::{from_stream, OTAImage, SubImage},
+};
+
+use super::SymbolizeOptions;
+use crate::cli::{debug, error, util, Cli};
+
+pub fn symbolize(cli: &Cli, options: &SymbolizeOptions) -> Result<(), Error> {
+    let log_file = options.log.as_ref().unwrap();
+    let log = match fs::read(log_file) {
+        // console logs may contain invalid UTF-8 (e.g. garbage after a reset)
+        Ok(data) => String::from_utf8_lossy(&data).into_owned(),
+        Err(e) => {
+            error!("Failed to read log file {:#?}: {}", log_file.display(), e);
+            return Ok(());
+        }
+    };
+
+    let mut symbolizer = Symbolizer::new();
+    if let Some(image_file) = &options.image {
+        let mut fp = match util::open_file(cli, image_file.clone(), Some("Image")) {
+            Ok(fp) => fp,
+            Err(_) => return Ok(()),
+        };
+        let mut image: OTAImage = from_stream(&mut fp)?;
+
+        // sections of encrypted subimages can only be mapped in plaintext
+        if image.get_subimages().iter().any(SubImage::is_encrypted) {
+            let keys = util::load_key_store(cli)?;
+            let key_source = options.decrypt.key_source(&keys)?;
+            debug!(cli, "Decrypting subimages using {:?}", key_source);
+            image.decrypt(&key_source)?;
+        }
+
+        let mut translator = AddressTranslator::new(&image);
+        if let Some(base) = options.base {
+            translator = translator.with_flash_offset(base);
+        }
+        symbolizer.set_translator(translator);
+    }
+
+    if let Some(elf_file) = &options.elf {
+        let elf_data = match fs::read(elf_file) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("Failed to read ELF file {:#?}: {}", elf_file.display(), e);
+                return Ok(());
+            }
+        };
+        let symbols = SymbolTable::from_elf(&elf_data)?;
+        debug!(
+            cli,
+            "Loaded {} symbols from {}",
+            symbols.len(),
+            elf_file.display()
+        );
+        symbolizer.add_symbols(symbols);
+    }
+
+    for rom_file in &options.rom_symbols {
+        let script = match fs::read_to_string(rom_file) {
+            Ok(script) => script,
+            Err(e) => {
+                error!(
+                    "Failed to read symbol file {:#?}: {}",
+                    rom_file.display(),
+                    e
+                );
+                return Ok(());
+            }
+        };
+        let symbols = SymbolTable::from_ld_script(&script)?;
+        debug!(
+            cli,
+            "Loaded {} symbols from {}",
+            symbols.len(),
+            rom_file.display()
+        );
+        symbolizer.add_symbols(symbols);
+    }
+
+    let addresses = symbolizer.symbolize_log(&log);
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&addresses)?);
+        return Ok(());
+    }
+
+    println!(
+        "{} {} {}",
+        "=".repeat(41),
+        "Symbolized".bold(),
+        "=".repeat(43)
+    );
+    for address in &addresses {
+        print_address(address);
+    }
+    println!("{}", "=".repeat(96));
+    println!("{} addresses", addresses.len());
+    Ok(())
+}
+
+fn print_address(address: &SymbolizedAddress) {
+    let label = match &address.label {
+        Some(label) => format!("{} ", label.bold()),
+        None => String::new(),
+    };
+    let symbol = match &address.symbol {
+        Some(symbol) => symbol.green().to_string(),
+        None => "??".bright_black().to_string(),
+    };
+    println!(
+        "  - [line {}] {}0x{:08x}: {}",
+        address.line, label, address.address, symbol
+    );
+
+    let mut details = Vec::new();
+    if let Some(region) = address.region {
+        details.push(region.to_string());
+    }
+    if let Some(location) = &address.location {
+        details.push(format!(
+            "subimage: {}, section: {}, offset: 0x{:08x}, file: 0x{:08x}",
+            location.subimage, location.section, location.section_offset, location.file_offset
+        ));
+        if let Some(offset) = location.flash_offset {
+            details.push(format!("flash: 0x{:08x}", offset));
+        }
+    }
+    if !details.is_empty() {
+        println!("    {}", details.join(", ").italic());
+    }
+}
@@ -0,0 +1,72 @@
+use colored::Colorize;
+use std::io::Seek;
+
+use amebazii::{
+    error::Error,
+    keystore::FW_HASH_KEY_NAME,
+    types::{from_stream, OTAImage},
+};
+
+use super::VerifyOptions;
+use crate::cli::{debug, error, util, Cli};
+
+pub fn verify(cli: &Cli, options: &VerifyOptions) -> Result<(), Error> {
+    let input_file = options.file.as_ref().unwrap();
+    let mut fp = match util::open_file(cli, input_file.clone(), None) {
+        Ok(fp) => fp,
+        Err(_) => {
+            return Err(Error::VerificationFailed(
+                "input file could not be read".to_string(),
+            ))
+        }
+    };
+
+    let mut image: OTAImage = from_stream(&mut fp)?;
+    debug!(cli, "Finished parsing file: {}", input_file.display());
+
+    let mut keys = util::load_key_store(cli)?;
+    let key_source = options.decrypt.key_source(&keys)?;
+    for (idx, subimage) in image.get_subimages_mut().iter_mut().enumerate() {
+        if subimage.is_encrypted() {
+            if let Err(e) = subimage.decrypt(&key_source) {
+                error!("Could not decrypt subimage {}: {}", idx, e);
+            }
+        }
+    }
+
+    if let Some(key) = &options.key {
+        let hash_key = keys.resolve(key)?;
+        keys.add_key(FW_HASH_KEY_NAME, &hash_key);
+    }
+
+    fp.seek(std::io::SeekFrom::Start(0))?;
+    let report = image.verify(&mut fp, &keys)?;
+
+    println!(
+        "{} {} {}",
+        "=".repeat(41),
+        "OTA Verification".bold(),
+        "=".repeat(41)
+    );
+    for check in report.get_checks() {
+        print!("  - {}: {} ", check.kind.to_string().bold(), check.actual);
+        if check.is_ok() {
+            println!("{}", "OK".green());
+        } else {
+            println!("{}", "invalid".red().italic());
+            println!("    expected: {}", check.expected);
+        }
+    }
+    println!("{}", "=".repeat(100));
+
+    let failures = report.get_failures().count();
+    if failures > 0 {
+        return Err(Error::VerificationFailed(format!(
+            "{} of {} checks failed",
+            failures,
+            report.get_checks().len()
+        )));
+    }
+    println!("{}", "All checks passed".green());
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use std::path::PathBuf;
+
+use amebazii::error::Error;
+use clap::Parser;
+
+use super::{Cli, SecureSubCommand};
+
+mod provision;
+
+/// Generate a secure boot provisioning bundle.
+///
+/// The bundle stores all eFuse values (efuse.json), the matching signed partition
+/// table (partition.bin) and a summary of all public values (summary.json). All
+/// keys are taken from the key store (secure_boot_key, hash_key, enc_priv_key and
+/// optionally secure_key and super_secure_key).
+#[derive(Parser)]
+#[clap(verbatim_doc_comment)]
+pub struct ProvisionOptions {
+    /// The partition table configuration file (JSON).
+    #[arg(short = 'c', long = "config", value_name = "CFG", required = true)]
+    pub config: Option<PathBuf>,
+
+    /// The directory to store the bundle.
+    #[arg(value_name = "DIR", required = true)]
+    pub outdir: Option<PathBuf>,
+
+    /// Overwrite existing files in the target directory.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub force: bool,
+}
+
+pub fn main(cli: &Cli, subcommand: Option<&SecureSubCommand>) -> Result<(), Error> {
+    if let Some(SecureSubCommand::Provision { options }) = subcommand {
+        provision::provision(cli, options)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,114 @@
+use colored::Colorize;
+use std::{fs, path::Path};
+
+use amebazii::{
+    conf::PartitionTableCfg, error::Error, keys::FLASH_PATTERN, provision::ProvisionBundle,
+    types::to_bytes,
+};
+
+use super::ProvisionOptions;
+use crate::cli::{debug, error, util, Cli};
+
+pub fn provision(cli: &Cli, options: &ProvisionOptions) -> Result<(), Error> {
+    let config_file = options.config.as_ref().unwrap();
+    let config: PartitionTableCfg = match util::open_file(cli, config_file.clone(), Some("Config"))
+    {
+        Ok(fp) => serde_json::from_reader(fp)?,
+        Err(_) => return Ok(()),
+    };
+
+    let outdir = options.outdir.as_ref().unwrap();
+    if outdir.exists() && !outdir.is_dir() {
+        error!("{} is not a directory", outdir.display());
+        return Ok(());
+    }
+
+    let files = ["efuse.json", "partition.bin", "summary.json"];
+    if !options.force {
+        if let Some(file) = files.iter().find(|file| outdir.join(file).exists()) {
+            error!(
+                "{} already exists (use --force to overwrite)",
+                outdir.join(file).display()
+            );
+            return Ok(());
+        }
+    }
+
+    let keys = util::load_key_store(cli)?;
+    let bundle = ProvisionBundle::build(&keys, config)?;
+    let summary = bundle.summary(&keys)?;
+
+    println!("{}:", "eFuse Values".bold());
+    for value in bundle.get_efuse_values() {
+        if value.secret {
+            println!("  - {}: {}", value.name, "<secret>".italic().yellow());
+        } else {
+            println!("  - {}: {}", value.name, hex::encode(&value.value));
+        }
+    }
+    println!("{}:", "Partition Table".bold());
+    println!("  - Hash: {}", summary.partition_table_hash);
+    for record in &summary.records {
+        println!(
+            "  - [{:?}] hash key: {}",
+            record.part_type,
+            record.hash_key_name.as_deref().unwrap_or("<custom>")
+        );
+    }
+
+    fs::create_dir_all(outdir)?;
+
+    // the partition table is written with calibration pattern, so that it can be
+    // flashed at offset 0
+    let mut partition = FLASH_PATTERN.to_vec();
+    partition.extend_from_slice(&[0xFF; 16]);
+    partition.extend_from_slice(&to_bytes(bundle.get_partition_table())?);
+    if partition.len() > 0x1000 {
+        error!(
+            "Partition table size is too large! ({} bytes > 4096 bytes)",
+            partition.len()
+        );
+        return Ok(());
+    }
+
+    let efuse: serde_json::Map<String, serde_json::Value> = bundle
+        .get_efuse_values()
+        .iter()
+        .map(|value| (value.name.clone(), hex::encode(&value.value).into()))
+        .collect();
+
+    write_file(
+        cli,
+        outdir,
+        "efuse.json",
+        &serde_json::to_vec_pretty(&efuse)?,
+    )?;
+    write_file(cli, outdir, "partition.bin", &partition)?;
+    write_file(
+        cli,
+        outdir,
+        "summary.json",
+        &serde_json::to_vec_pretty(&summary)?,
+    )?;
+    println!(
+        "{} {}",
+        "Bundle written to".green(),
+        outdir.display().to_string().green()
+    );
+    if bundle.get_efuse_values().iter().any(|value| value.secret) {
+        println!(
+            "{}",
+            "Note: efuse.json contains secret keys, keep it in a safe place"
+                .yellow()
+                .italic()
+        );
+    }
+    Ok(())
+}
+
+fn write_file(cli: &Cli, outdir: &Path, name: &str, data: &[u8]) -> Result<(), Error> {
+    let path = outdir.join(name);
+    debug!(cli, "Writing {}", path.display());
+    fs::write(path, data)?;
+    Ok(())
+}
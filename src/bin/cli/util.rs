@@ -1,3 +1,9 @@
+use amebazii::{
+    audit::{AuditReport, Severity},
+    keystore::KeyStore,
+    scan::{SecretFinding, SecretKind},
+    types::KeyIdentification,
+};
 use colored::Colorize;
 use std::{fs, path::PathBuf};
 
@@ -32,6 +38,99 @@ pub fn open_file(cli: &Cli, file: PathBuf, file_type: Option<&str>) -> Result<fs
     }
 }
 
+/// Loads all key files given on the command line (`--keystore`).
+pub fn load_key_store(cli: &Cli) -> Result<KeyStore, amebazii::error::Error> {
+    let mut keys = KeyStore::new();
+    for file in &cli.keystore {
+        debug!(cli, "Loading keys from {:#?}", file.display());
+        keys.load_file(file)?;
+    }
+    Ok(keys)
+}
+
+/// Prints the key and hash algorithm that matched each hash.
+pub fn print_key_identification(results: &[KeyIdentification]) {
+    for result in results {
+        print!("  - {}: ", result.kind.to_string().bold());
+        match &result.matched {
+            Some(matched) => println!("{}", matched.to_string().green()),
+            None => println!("{}", "unknown key".red().italic()),
+        }
+    }
+}
+
+/// Prints all findings of an audit report, either as text or as JSON.
+pub fn print_audit_report(report: &AuditReport, json: bool) -> Result<(), amebazii::error::Error> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(report)?);
+        return Ok(());
+    }
+
+    println!("{} {} {}", "=".repeat(44), "Audit".bold(), "=".repeat(45));
+    for finding in report.get_findings() {
+        let severity = format!("{:<8}", finding.severity.to_string());
+        let severity = match finding.severity {
+            Severity::Critical => severity.red().bold(),
+            Severity::High => severity.red(),
+            Severity::Medium => severity.yellow(),
+            Severity::Low => severity.blue(),
+            Severity::Info => severity.normal(),
+        };
+        println!(
+            "  {} [{}] {}: {}",
+            severity,
+            finding.id,
+            finding.location.bold(),
+            finding.message
+        );
+    }
+    println!("{}", "=".repeat(95));
+
+    let counts: Vec<String> = [
+        Severity::Critical,
+        Severity::High,
+        Severity::Medium,
+        Severity::Low,
+        Severity::Info,
+    ]
+    .iter()
+    .map(|severity| format!("{} {}", report.count(*severity), severity))
+    .collect();
+    println!(
+        "{} findings ({})",
+        report.get_findings().len(),
+        counts.join(", ")
+    );
+    Ok(())
+}
+
+/// Prints all secrets found by the scanner, either as text or as JSON.
+pub fn print_secret_findings(
+    findings: &[SecretFinding],
+    json: bool,
+) -> Result<(), amebazii::error::Error> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(findings)?);
+        return Ok(());
+    }
+
+    println!("{} {} {}", "=".repeat(43), "Secrets".bold(), "=".repeat(44));
+    for finding in findings {
+        print!("  - {}: {}", finding.kind.to_string().bold(), finding.label);
+        match finding.address {
+            Some(address) => print!(" at 0x{:08x}", address),
+            None => print!(" at offset 0x{:x}", finding.offset),
+        }
+        println!(" ({}, {} bytes)", finding.location.italic(), finding.length);
+        if finding.kind == SecretKind::KeyCandidate {
+            println!("    {}", hex::encode(&finding.data).bright_black());
+        }
+    }
+    println!("{}", "=".repeat(95));
+    println!("{} findings", findings.len());
+    Ok(())
+}
+
 pub fn open_output_file<O: OutputOptionsExt>(
     cli: &Cli,
     input_options: Option<&super::InputOptions>,